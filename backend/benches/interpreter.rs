@@ -0,0 +1,296 @@
+// Benchmarks of the stages of a hard-mode execution: parsing the Blockly
+// workspace, type checking and running the program. Run with `cargo bench`.
+//
+// The stages log to the debug files created by the server (`ex.txt`,
+// `statement_exec.txt`, ...) when they exist, so benchmark from a directory
+// without them.
+
+use std::hint::black_box;
+
+use backend::environment::environment::Environment;
+use backend::handlers::hard_interpreter::parse_workspace;
+use backend::interpreter::{Value, run};
+use backend::ir::ast::{Statement, Type};
+use backend::models::Block2;
+use backend::parser::{parse, parse_chained_blocks};
+use backend::type_checker::check_stmt;
+use criterion::{BatchSize, Criterion, criterion_group, criterion_main};
+use serde_json::{Value as Json, json};
+
+// A program is written as a Blockly workspace, or in the text syntax when it
+// uses values that no block can declare, such as lists
+enum Source {
+    Workspace(Vec<Block2>),
+    Text(&'static str),
+}
+
+fn text(text: &str) -> Json {
+    json!({"shadow": {"type": "expression_block", "id": "text", "fields": {"TEXT": text}}})
+}
+
+fn chain(mut blocks: Vec<Json>) -> Json {
+    let mut next: Option<Json> = None;
+    while let Some(mut block) = blocks.pop() {
+        if let Some(next_block) = next {
+            block["next"] = json!({"block": next_block});
+        }
+        next = Some(block);
+    }
+    next.unwrap_or(Json::Null)
+}
+
+fn declaration(variable: &str, type_name: &str) -> Json {
+    json!({
+        "type": "declaration_block",
+        "id": format!("declare-{}", variable),
+        "fields": {"VARIABLE": variable, "TYPE": type_name}
+    })
+}
+
+fn assignment(variable: &str, expression: &str) -> Json {
+    json!({
+        "type": "assignment_block",
+        "id": format!("assign-{}", variable),
+        "fields": {"VARIABLE": variable},
+        "inputs": {"EXPRESSION": text(expression)}
+    })
+}
+
+fn print(expression: &str) -> Json {
+    json!({"type": "print_block", "id": "print", "inputs": {"EXPRESSION": text(expression)}})
+}
+
+fn while_loop(condition: &str, body: Vec<Json>) -> Json {
+    json!({
+        "type": "while_block",
+        "id": "while",
+        "inputs": {"CONDITION": text(condition), "WHILE_BODY": {"block": chain(body)}}
+    })
+}
+
+fn if_else(condition: &str, then_body: Vec<Json>, else_body: Vec<Json>) -> Json {
+    json!({
+        "type": "if_else_block",
+        "id": "if",
+        "inputs": {
+            "CONDITION": text(condition),
+            "IF_BODY": {"block": chain(then_body)},
+            "ELSE_BODY": {"block": chain(else_body)}
+        }
+    })
+}
+
+fn when(value: &str, cases: Vec<(&str, Vec<Json>)>) -> Json {
+    let cases = cases
+        .into_iter()
+        .map(|(case_value, body)| {
+            json!({
+                "type": "case_block",
+                "id": "case",
+                "inputs": {"VALUE": text(case_value), "CASE_BODY": {"block": chain(body)}}
+            })
+        })
+        .collect();
+    json!({
+        "type": "when_block",
+        "id": "when",
+        "inputs": {"VALUE": text(value), "CASES": {"block": chain(cases)}}
+    })
+}
+
+fn function(
+    name: &str,
+    param: (&str, &str),
+    return_type: &str,
+    body: Vec<Json>,
+    return_expression: &str,
+) -> Json {
+    json!({
+        "type": "function_declaration_block",
+        "id": name,
+        "fields": {"FUNCTION_NAME": name, "RETURN_TYPE": return_type},
+        "inputs": {
+            "FORMAL_ARGUMENTS": {"block": {
+                "type": "formal_argument_block",
+                "id": param.0,
+                "fields": {"FORMAL_ARGUMENT": param.0, "ARGUMENT_TYPE": param.1}
+            }},
+            "FUNCTION_BODY": {"block": chain(body)},
+            "RETURN_EXPRESSION": text(return_expression)
+        }
+    })
+}
+
+fn workspace(blocks: Vec<Json>) -> Source {
+    let blocks = blocks
+        .into_iter()
+        .map(|block| serde_json::from_value(block).expect("benchmark block should deserialize"))
+        .collect();
+    Source::Workspace(blocks)
+}
+
+// Recursive factorial, like the `factorial.json` workspace of `execute_with_json`
+fn factorial() -> Source {
+    workspace(vec![
+        function(
+            "factorial",
+            ("n", "INT"),
+            "INT",
+            vec![
+                declaration("result", "INT"),
+                if_else(
+                    "n <= 1",
+                    vec![assignment("result", "1")],
+                    vec![assignment("result", "n * factorial(n - 1)")],
+                ),
+            ],
+            "result",
+        ),
+        chain(vec![
+            declaration("i", "INT"),
+            declaration("x", "INT"),
+            while_loop(
+                "i < 100",
+                vec![assignment("x", "factorial(12)"), assignment("i", "i + 1")],
+            ),
+            print("x"),
+        ]),
+    ])
+}
+
+fn nested_loops() -> Source {
+    workspace(vec![chain(vec![
+        declaration("i", "INT"),
+        declaration("j", "INT"),
+        declaration("count", "INT"),
+        while_loop(
+            "i < 60",
+            vec![
+                assignment("j", "0"),
+                while_loop(
+                    "j < 60",
+                    vec![
+                        if_else(
+                            "(i + j) % 3 == 0",
+                            vec![assignment("count", "count + 1")],
+                            vec![assignment("count", "count - 1")],
+                        ),
+                        assignment("j", "j + 1"),
+                    ],
+                ),
+                assignment("i", "i + 1"),
+            ],
+        ),
+        print("count"),
+    ])])
+}
+
+fn strings() -> Source {
+    workspace(vec![chain(vec![
+        declaration("i", "INT"),
+        declaration("count", "INT"),
+        declaration("word", "STRING"),
+        declaration("text", "STRING"),
+        while_loop(
+            "i < 300",
+            vec![
+                assignment("word", "\"ab\""),
+                when(
+                    "word",
+                    vec![
+                        ("\"ab\"", vec![assignment("count", "count + 1")]),
+                        ("\"cd\"", vec![assignment("count", "count - 1")]),
+                    ],
+                ),
+                assignment("text", "text + word"),
+                assignment("i", "i + 1"),
+            ],
+        ),
+        print("text"),
+    ])])
+}
+
+fn lists() -> Source {
+    Source::Text(
+        "var total = 0; var i = 0; \
+         while i < 300: \
+             val xs = [i, i + 1, i + 2, i * 2, i * 3]; \
+             for x in xs: total = total + x % 5; end; \
+             i = i + 1; \
+         end; \
+         for k in range(0, 300): total = total - k % 5; end;",
+    )
+}
+
+fn parse_source(source: &Source) -> Statement {
+    match source {
+        Source::Workspace(blocks) => {
+            parse_workspace(blocks).expect("benchmark workspace should parse")
+        }
+        Source::Text(text) => {
+            let (_, stmts) = parse(text).expect("benchmark program should parse");
+            Statement::Block(stmts)
+        }
+    }
+}
+
+fn programs() -> Vec<(&'static str, Source)> {
+    vec![
+        ("factorial", factorial()),
+        ("nested_loops", nested_loops()),
+        ("strings", strings()),
+        ("lists", lists()),
+    ]
+}
+
+fn bench_parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse");
+    for (name, source) in programs() {
+        group.bench_function(name, |b| match &source {
+            Source::Workspace(blocks) => b.iter(|| {
+                for block in blocks {
+                    black_box(parse_chained_blocks(black_box(block))).ok();
+                }
+            }),
+            Source::Text(text) => b.iter(|| black_box(parse(black_box(text))).ok()),
+        });
+    }
+    group.finish();
+}
+
+fn bench_type_check(c: &mut Criterion) {
+    let mut group = c.benchmark_group("type_check");
+    for (name, source) in programs() {
+        let program = parse_source(&source);
+        let env: Environment<Type> = Environment::new();
+        check_stmt(&mut program.clone(), &env).expect("benchmark program should type check");
+        group.bench_function(name, |b| {
+            b.iter_batched(
+                || program.clone(),
+                |mut program| black_box(check_stmt(&mut program, &env)).ok(),
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn bench_run(c: &mut Criterion) {
+    let mut group = c.benchmark_group("run");
+    for (name, source) in programs() {
+        // The interpreter runs the program as the type checker annotates it
+        let mut program = parse_source(&source);
+        check_stmt(&mut program, &Environment::new()).expect("benchmark program should type check");
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                let mut env: Environment<Value> = Environment::new();
+                run(&program, &mut env).expect("benchmark program should run");
+                black_box(env.output)
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_parse, bench_type_check, bench_run);
+criterion_main!(benches);
@@ -1,20 +1,60 @@
 use crate::ir::ast::FuncSignature;
 use crate::ir::ast::Function;
 use crate::ir::ast::Name;
+use crate::ir::ast::Statement;
 use crate::ir::ast::ValueConstructor;
 use crate::{show, show_counter};
 use std::collections::HashMap;
+use std::collections::HashSet;
 use std::collections::LinkedList;
+use std::fmt;
 use std::fmt::Debug;
 use std::fmt::format;
+use std::rc::Rc;
 use std::sync::atomic::{AtomicUsize, Ordering};
 static NEXT_ENVIRONMENT_ID: AtomicUsize = AtomicUsize::new(0);
 
+pub type FunctionTable = HashMap<FuncSignature, Rc<Function>>;
+pub type AdtTable = HashMap<Name, Vec<ValueConstructor>>;
+
 #[derive(Clone, Debug)]
 pub struct Scope<A: Clone + Debug> {
     pub variables: HashMap<Name, (bool, A)>,
-    pub functions: HashMap<FuncSignature, Function>,
+    pub functions: FunctionTable,
+    // Functions bound to formal arguments, which see the scopes enclosing their
+    // own definition instead of those of the function they were passed to
+    pub links: HashMap<FuncSignature, Link>,
+    // Top-level variables that the function declared with `global`, which it
+    // can assign
+    pub declared_globals: HashSet<Name>,
+    // How the immutable variables of the scope were declared, for the messages
+    // of the type checker
+    pub sites: HashMap<Name, String>,
     pub adts: HashMap<Name, Vec<ValueConstructor>>,
+    // The order in which the variables of the scope were declared
+    pub positions: HashMap<Name, usize>,
+    // How many of the variables of the scope each function defined in it sees:
+    // those declared before its definition, or none if it may run earlier
+    pub visible_counts: HashMap<FuncSignature, usize>,
+}
+
+// Where the definition of a function is: in the frame with the given index,
+// inside the scope with the given index, counting its globals as the first.
+// The body of the function sees the variables and functions of the scopes
+// enclosing its definition, the functions of the scope that defines it, and
+// the given number of its variables, in the order they were declared
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Link {
+    frame: usize,
+    scope: usize,
+    visible: usize,
+}
+
+impl Link {
+    // The top-level block of the program, whose variables are global
+    fn is_top_level(&self) -> bool {
+        self.frame == 0 && self.scope <= 1
+    }
 }
 
 impl<A: Clone + Debug> Scope<A> {
@@ -22,18 +62,26 @@ impl<A: Clone + Debug> Scope<A> {
         Scope {
             variables: HashMap::new(),
             functions: HashMap::new(),
+            links: HashMap::new(),
+            declared_globals: HashSet::new(),
+            sites: HashMap::new(),
             adts: HashMap::new(),
+            positions: HashMap::new(),
+            visible_counts: HashMap::new(),
         }
     }
 
     fn map_variable(&mut self, var: Name, mutable: bool, value: A) -> () {
+        let position = self.positions.len();
+        self.positions.entry(var.clone()).or_insert(position);
         self.variables.insert(var, (mutable, value));
         return ();
     }
 
     fn map_function(&mut self, function: Function) -> () {
         let func_signature = FuncSignature::from_func(&function);
-        self.functions.insert(func_signature, function);
+        self.links.remove(&func_signature);
+        self.functions.insert(func_signature, Rc::new(function));
         return ();
     }
 
@@ -48,18 +96,38 @@ impl<A: Clone + Debug> Scope<A> {
             .map(|(mutable, value)| (*mutable, value.clone()))
     }
 
-    fn lookup_function(&self, func_signature: &FuncSignature) -> Option<&Function> {
-        self.functions.get(func_signature)
-    }
-
-    fn lookup_function_by_name(&self, name: &Name) -> Option<&Function> {
-        self.functions.iter().find_map(|(signature, function)| {
-            if &signature.name == name {
-                Some(function)
-            } else {
-                None
-            }
-        })
+    // A variable among the given number of the first ones declared in the scope
+    fn lookup_visible_var(&self, var: &Name, visible: usize) -> Option<(bool, A)> {
+        self.lookup_var(var)
+            .filter(|_| self.positions.get(var).is_some_and(|p| *p < visible))
+    }
+
+    // A function declared in the scope, with the link to its definition, which is
+    // the given one unless the function was bound to a formal argument
+    fn lookup_function(
+        &self,
+        func_signature: &FuncSignature,
+        link: Link,
+    ) -> Option<(&Rc<Function>, Link)> {
+        let func = self.functions.get(func_signature)?;
+        let visible = self.visible_counts.get(func_signature).copied();
+        Some((
+            func,
+            self.links.get(func_signature).copied().unwrap_or(Link {
+                visible: visible.unwrap_or(0),
+                ..link
+            }),
+        ))
+    }
+
+    // Of the overloads of the name, the first in the order of their signatures
+    fn lookup_function_by_name(&self, name: &Name, link: Link) -> Option<(&Rc<Function>, Link)> {
+        let func_signature = self
+            .functions
+            .keys()
+            .filter(|signature| &signature.name == name)
+            .min_by_key(|signature| signature.to_string())?;
+        self.lookup_function(func_signature, link)
     }
 
     fn lookup_adt(&self, name: &Name) -> Option<&Vec<ValueConstructor>> {
@@ -67,14 +135,86 @@ impl<A: Clone + Debug> Scope<A> {
     }
 }
 
+// The functions of a block that may run before their definition is reached:
+// those that the statements before it refer to, directly or through the other
+// functions of the block
+pub fn run_before_definition(stmts: &[Statement]) -> HashSet<Name> {
+    let mut bodies: HashMap<&Name, HashSet<Name>> = HashMap::new();
+    for stmt in stmts {
+        if let Statement::FuncDef(func) = stmt {
+            func.collect_names(bodies.entry(&func.name).or_default());
+        }
+    }
+    let mut reached: HashSet<Name> = HashSet::new();
+    let mut early = HashSet::new();
+    for stmt in stmts {
+        if let Statement::FuncDef(func) = stmt {
+            if reached.contains(&func.name) {
+                early.insert(func.name.clone());
+            }
+            continue;
+        }
+        let mut names = HashSet::new();
+        stmt.collect_names(&mut names);
+        let mut pending: Vec<Name> = names.into_iter().collect();
+        while let Some(name) = pending.pop() {
+            if let Some(names) = bodies.get(&name).filter(|_| !reached.contains(&name)) {
+                pending.extend(names.iter().cloned());
+            }
+            reached.insert(name);
+        }
+    }
+    early
+}
+
+// The scopes of a function that called the current one, saved while it runs
 #[derive(Clone, Debug)]
+struct Frame<A: Clone + Debug> {
+    stack_len: usize,
+    current_func: FuncSignature,
+    loop_depth: usize,
+    ended_variables: HashSet<Name>,
+    globals: Scope<A>,
+    stack: LinkedList<Scope<A>>,
+    link: Option<Link>,
+    inherited_adts: Rc<AdtTable>,
+}
+
+#[derive(Clone)]
 pub struct Environment<A: Clone + Debug> {
     pub id: usize,
     pub stack_len: usize,
     pub current_func: FuncSignature,
+    // Number of loops enclosing the statement being checked, so that the type
+    // checker can reject `break` and `continue` outside of them
+    pub loop_depth: usize,
+    // Variables of the blocks that have ended, so that the type checker can
+    // tell why they cannot be read
+    pub ended_variables: HashSet<Name>,
     pub output: Vec<String>,
     pub globals: Scope<A>,
     pub stack: LinkedList<Scope<A>>,
+    // Algebraic data types visible where the current function was called. They
+    // are shared with the caller instead of copied into each call
+    pub inherited_adts: Rc<AdtTable>,
+    // The definition of the current function, whose enclosing scopes are in
+    // the frames of the functions that were running when it was defined
+    link: Option<Link>,
+    frames: Vec<Frame<A>>,
+}
+
+// The output is left out, since the environment is logged at every statement
+impl<A: Clone + Debug> Debug for Environment<A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Environment")
+            .field("id", &self.id)
+            .field("current_func", &self.current_func)
+            .field("output_lines", &self.output.len())
+            .field("globals", &self.globals)
+            .field("stack", &self.stack)
+            .field("frames", &self.frames.len())
+            .finish()
+    }
 }
 
 impl<A: Clone + Debug> Environment<A> {
@@ -86,9 +226,14 @@ impl<A: Clone + Debug> Environment<A> {
             id,
             stack_len: 0,
             current_func: FuncSignature::new(),
+            loop_depth: 0,
+            ended_variables: HashSet::new(),
             output: Vec::new(),
             globals: Scope::new(),
             stack: LinkedList::new(),
+            inherited_adts: Rc::new(HashMap::new()),
+            link: None,
+            frames: Vec::new(),
         }
     }
 
@@ -109,16 +254,24 @@ impl<A: Clone + Debug> Environment<A> {
         self.stack = stack;
     }
 
-    pub fn set_global_functions(&mut self, global_functions: HashMap<FuncSignature, Function>) {
-        self.globals.functions = global_functions;
-    }
-
     //pub fn set_stack
 
     pub fn set_current_func(&mut self, func_signature: &FuncSignature) {
         self.current_func = func_signature.clone();
     }
 
+    pub fn enter_loop(&mut self) {
+        self.loop_depth += 1;
+    }
+
+    pub fn exit_loop(&mut self) {
+        self.loop_depth -= 1;
+    }
+
+    pub fn in_loop(&self) -> bool {
+        self.loop_depth > 0
+    }
+
     pub fn insert_output_line(&mut self, line: &str) {
         self.output.push(line.to_string());
     }
@@ -148,6 +301,12 @@ impl<A: Clone + Debug> Environment<A> {
     ) -> Result<String, String> {
         show_counter_env();
         show_env(format!("Trying to create variable ..."));
+        if self.declared_global(&var) {
+            return Err(format!(
+                "Variable '{}' was declared global in function '{}'",
+                var, self.current_func
+            ));
+        }
         let current_scope = self.stack.front_mut().unwrap_or(&mut self.globals);
 
         for (name, _) in &current_scope.variables {
@@ -164,7 +323,6 @@ impl<A: Clone + Debug> Environment<A> {
         }
         current_scope.map_variable(var.clone(), mutable, value);
         show_env(format!("Variable '{}' was successfully created", var));
-        show_env(format!("{:?}", self));
         return Ok("Variable successfully created".to_string());
     }
 
@@ -174,6 +332,18 @@ impl<A: Clone + Debug> Environment<A> {
             "Trying to assign value '{:?}' to variable '{}'...",
             value, var
         ));
+        if self.declared_global(&var)
+            && let Some(scope) = self
+                .top_level_scopes_mut()
+                .find(|scope| scope.variables.contains_key(&var))
+        {
+            scope.map_variable(var.clone(), true, value);
+            show_env(format!(
+                "Assignment to global variable {} was successfull",
+                var
+            ));
+            return Ok(format!("Assingnment of variable {} was successfull", var));
+        }
         for scope in self.stack.iter_mut() {
             for (scope_var, (mutable, _)) in &scope.variables {
                 if scope_var.to_string() == var {
@@ -186,7 +356,6 @@ impl<A: Clone + Debug> Environment<A> {
                     } else {
                         scope.map_variable(var.clone(), true, value);
                         show_env(format!("Assignment was successfull"));
-                        show_env(format!("{:?}", self));
                         return Ok(format!("Assingnment of variable {} was successfull", var));
                     }
                 }
@@ -210,7 +379,73 @@ impl<A: Clone + Debug> Environment<A> {
         }
         show_counter_env();
         show_env(format!("Function {} mapped to Env {}", func_name, self.id));
-        show_env(format!("{:?}", self));
+    }
+
+    // Maps a function of a block before running the block. One that may run
+    // before its definition sees none of the variables of the block
+    pub fn hoist_function(&mut self, function: Function, runs_early: bool) {
+        let func_signature = FuncSignature::from_func(&function);
+        self.map_function(function);
+        if runs_early {
+            let current_scope = self.stack.front_mut().unwrap_or(&mut self.globals);
+            current_scope.visible_counts.insert(func_signature, 0);
+        }
+    }
+
+    // Maps a function when its definition is reached. Unless it was hoisted as
+    // one that may run earlier, it sees the variables declared before it
+    pub fn define_function(&mut self, function: Function) {
+        let func_signature = FuncSignature::from_func(&function);
+        self.map_function(function);
+        let current_scope = self.stack.front_mut().unwrap_or(&mut self.globals);
+        let declared = current_scope.positions.len();
+        current_scope
+            .visible_counts
+            .entry(func_signature)
+            .or_insert(declared);
+    }
+
+    // Maps a function passed as an argument, which keeps seeing the scopes
+    // enclosing its definition
+    pub fn map_closure(&mut self, function: Function, link: Link) {
+        let func_signature = FuncSignature::from_func(&function);
+        self.map_function(function);
+        let current_scope = self.stack.front_mut().unwrap_or(&mut self.globals);
+        current_scope.links.insert(func_signature, link);
+    }
+
+    // Lets the current function assign a top-level variable, which it can
+    // already read
+    pub fn declare_global(&mut self, var: Name) {
+        let current_scope = self.stack.front_mut().unwrap_or(&mut self.globals);
+        current_scope.declared_globals.insert(var);
+    }
+
+    fn declared_global(&self, var: &Name) -> bool {
+        !self.frames.is_empty()
+            && self
+                .stack
+                .iter()
+                .chain(std::iter::once(&self.globals))
+                .any(|scope| scope.declared_globals.contains(var))
+    }
+
+    // The scopes of the top-level block of the program and its globals, which
+    // are saved in the first frame while a function runs
+    fn top_level_scopes(&self) -> impl Iterator<Item = &Scope<A>> {
+        let (stack, globals) = match self.frames.first() {
+            Some(frame) => (&frame.stack, &frame.globals),
+            None => (&self.stack, &self.globals),
+        };
+        stack.back().into_iter().chain(std::iter::once(globals))
+    }
+
+    fn top_level_scopes_mut(&mut self) -> impl Iterator<Item = &mut Scope<A>> {
+        let (stack, globals) = match self.frames.first_mut() {
+            Some(frame) => (&mut frame.stack, &mut frame.globals),
+            None => (&mut self.stack, &mut self.globals),
+        };
+        stack.back_mut().into_iter().chain(std::iter::once(globals))
     }
 
     pub fn map_adt(&mut self, name: Name, cons: Vec<ValueConstructor>) -> () {
@@ -220,59 +455,197 @@ impl<A: Clone + Debug> Environment<A> {
         }
     }
 
-    pub fn lookup(&self, var: &Name) -> Option<(bool, A)> {
-        for scope in self.stack.iter() {
-            if let Some(value) = scope.lookup_var(var) {
-                return Some(value);
+    // Visits the scopes visible from the current one, innermost first: those of
+    // the current function, then those enclosing the definition of each function
+    // up the chain of definitions. Each scope comes with how many of its
+    // variables are visible and the link of the functions declared in it. Those
+    // of the block that defines a function are visible to it when they are
+    // declared before the definition, unless it may run earlier
+    fn find_in_scopes<'a, T>(
+        &'a self,
+        mut find: impl FnMut(&'a Scope<A>, usize, Link) -> Option<T>,
+    ) -> Option<T> {
+        let current = self.frames.len();
+        let depth = self.stack.len();
+        for (i, scope) in self.stack.iter().enumerate() {
+            let link = Link {
+                frame: current,
+                scope: depth - i,
+                visible: 0,
+            };
+            if let Some(found) = find(scope, usize::MAX, link) {
+                return Some(found);
             }
         }
-        self.globals.lookup_var(var)
-    }
-
-    //pub fn lookup_functions_by_name(&self, func_name: Name) -> Vec<&Function> {
-    //    let mut results = Vec::new();
-    //
-    //    for scope in self.stack.iter() {
-    //        for (signature, func) in scope.functions.iter() {
-    //            if signature.name == func_name {
-    //                results.push(func);
-    //            }
-    //        }
-    //    }
-    //
-    //    for (signature, func) in self.globals.functions.iter() {
-    //        if signature.name == func_name {
-    //            results.push(func);
-    //        }
-    //    }
-    //    results
-    //}
-
-    pub fn lookup_function(&self, func_signature: &FuncSignature) -> Option<&Function> {
-        for scope in self.stack.iter() {
-            if let Some(func) = scope.lookup_function(func_signature) {
-                return Some(func);
+        let link = Link {
+            frame: current,
+            scope: 0,
+            visible: 0,
+        };
+        if let Some(found) = find(&self.globals, usize::MAX, link) {
+            return Some(found);
+        }
+
+        let mut definition = self.link;
+        while let Some(Link {
+            frame,
+            scope,
+            visible,
+        }) = definition
+        {
+            let saved = self.frames.get(frame)?;
+            let depth = saved.stack.len();
+            // The scopes above the definition belong to blocks that were entered
+            // after it
+            for (i, enclosing) in saved
+                .stack
+                .iter()
+                .enumerate()
+                .skip(depth.saturating_sub(scope))
+            {
+                let index = depth - i;
+                let link = Link {
+                    frame,
+                    scope: index,
+                    visible: 0,
+                };
+                let visible = if index < scope { usize::MAX } else { visible };
+                if let Some(found) = find(enclosing, visible, link) {
+                    return Some(found);
+                }
+            }
+            let link = Link {
+                frame,
+                scope: 0,
+                visible: 0,
+            };
+            let visible = if scope > 0 { usize::MAX } else { visible };
+            if let Some(found) = find(&saved.globals, visible, link) {
+                return Some(found);
             }
+            definition = saved.link;
+        }
+        None
+    }
+
+    // Variables of the functions enclosing the current one can be read but not
+    // assigned, and so can global variables unless declared with `global`
+    pub fn lookup(&self, var: &Name) -> Option<(bool, A)> {
+        if self.declared_global(var) {
+            return self
+                .top_level_scopes()
+                .find_map(|scope| scope.lookup_var(var));
         }
-        self.globals.lookup_function(func_signature)
+        let current = self.frames.len();
+        self.find_in_scopes(|scope, visible, link| {
+            let (mutable, value) = scope.lookup_visible_var(var, visible)?;
+            Some((mutable && link.frame == current, value))
+        })
+    }
+
+    // A variable declared by the current function, or outside of functions, by
+    // the program. The variables of enclosing functions and the globals a
+    // function reads are left out, since its own variables may hide them
+    pub fn lookup_local(&self, var: &Name) -> Option<(bool, A)> {
+        self.stack
+            .iter()
+            .chain(std::iter::once(&self.globals))
+            .find_map(|scope| scope.lookup_var(var))
+    }
+
+    // Whether a scope enclosing the current function declares the variable, but
+    // after the definition of the function or where it may not exist yet
+    pub fn hidden_from_function(&self, var: &Name) -> bool {
+        self.lookup(var).is_none()
+            && self
+                .find_in_scopes(|scope, _, _| scope.lookup_var(var))
+                .is_some()
+    }
+
+    // The overloads of a function visible from the current scope, in the order
+    // of their signatures
+    pub fn lookup_functions_by_name(&self, func_name: &Name) -> Vec<Rc<Function>> {
+        let mut results: Vec<(FuncSignature, Rc<Function>)> = self
+            .get_all_functions()
+            .into_iter()
+            .filter(|(func_signature, _)| &func_signature.name == func_name)
+            .collect();
+        results.sort_by_cached_key(|(func_signature, _)| func_signature.to_string());
+        results.into_iter().map(|(_, func)| func).collect()
+    }
+
+    pub fn lookup_function(&self, func_signature: &FuncSignature) -> Option<&Rc<Function>> {
+        self.lookup_closure(func_signature).map(|(func, _)| func)
+    }
+
+    // A function with the link to its definition, to call it with `enter_function`
+    pub fn lookup_closure(&self, func_signature: &FuncSignature) -> Option<(&Rc<Function>, Link)> {
+        self.find_in_scopes(|scope, _, link| scope.lookup_function(func_signature, link))
+    }
+
+    // The top-level variable that a name refers to from the current scope, with
+    // whether it was declared with `var`. None if the name is not global or if
+    // a local variable hides it
+    pub fn lookup_global(&self, var: &Name) -> Option<(bool, A)> {
+        self.find_in_scopes(|scope, visible, link| {
+            let found = scope.lookup_visible_var(var, visible)?;
+            Some(Some(found).filter(|_| link.is_top_level()))
+        })
+        .flatten()
+    }
+
+    pub fn set_declaration_site(&mut self, var: Name, site: String) {
+        let current_scope = self.stack.front_mut().unwrap_or(&mut self.globals);
+        current_scope.sites.insert(var, site);
+    }
+
+    // How the variable visible with the given name was declared, if it is immutable
+    pub fn declaration_site(&self, var: &Name) -> Option<String> {
+        self.find_in_scopes(|scope, visible, _| {
+            scope.lookup_visible_var(var, visible)?;
+            Some(scope.sites.get(var).cloned())
+        })
+        .flatten()
     }
 
     pub fn lookup_var_or_func(&self, name: &Name) -> Option<FuncOrVar<A>> {
-        for scope in self.stack.iter() {
-            if let Some(value) = scope.lookup_var(name) {
-                return Some(FuncOrVar::Var(value));
-            }
-            if let Some(func) = scope.lookup_function_by_name(name) {
-                return Some(FuncOrVar::Func(func.clone()));
-            }
+        if let Some(var) = self.lookup(name).filter(|_| self.declared_global(name)) {
+            return Some(FuncOrVar::Var(var));
         }
-        if let Some(value) = self.globals.lookup_var(name) {
-            return Some(FuncOrVar::Var(value));
+        let current = self.frames.len();
+        self.find_in_scopes(|scope, visible, link| {
+            if let Some((mutable, value)) = scope.lookup_visible_var(name, visible) {
+                return Some(FuncOrVar::Var((mutable && link.frame == current, value)));
+            }
+            scope
+                .lookup_function_by_name(name, link)
+                .map(|(func, link)| FuncOrVar::Func(func.clone(), link))
+        })
+    }
+
+    // The link of a function defined in the current scope
+    pub fn definition_link(&self, func_signature: &FuncSignature) -> Link {
+        Link {
+            frame: self.frames.len(),
+            scope: self.stack.len(),
+            visible: self.visible_count(func_signature),
         }
-        if let Some(func) = self.globals.lookup_function_by_name(name) {
-            return Some(FuncOrVar::Func(func.clone()));
+    }
+
+    fn visible_count(&self, func_signature: &FuncSignature) -> usize {
+        let current_scope = self.get_current_scope();
+        let visible = current_scope.visible_counts.get(func_signature);
+        visible.copied().unwrap_or(0)
+    }
+
+    // A link that sees all the current scopes, for a function defined by an
+    // expression, which has no scope of its own
+    pub fn current_link(&self) -> Link {
+        Link {
+            frame: self.frames.len(),
+            scope: self.stack.len() + 1,
+            visible: 0,
         }
-        return None;
     }
 
     pub fn lookup_adt(&self, name: &Name) -> Option<&Vec<ValueConstructor>> {
@@ -281,7 +654,24 @@ impl<A: Clone + Debug> Environment<A> {
                 return Some(cons);
             }
         }
-        self.globals.lookup_adt(name)
+        self.globals
+            .lookup_adt(name)
+            .or_else(|| self.inherited_adts.get(name))
+    }
+
+    // Finds the algebraic data type that declares a value constructor
+    pub fn lookup_constructor(&self, name: &Name) -> Option<(Name, &Vec<ValueConstructor>)> {
+        let scopes = self.stack.iter().chain(std::iter::once(&self.globals));
+        scopes
+            .map(|scope| &scope.adts)
+            .chain(std::iter::once(self.inherited_adts.as_ref()))
+            .flat_map(|adts| adts.iter())
+            .find(|(_, constructors)| constructors.iter().any(|c| &c.name == name))
+            .map(|(adt_name, constructors)| (adt_name.clone(), constructors))
+    }
+
+    pub fn in_function(&self) -> bool {
+        !self.frames.is_empty()
     }
 
     pub fn scoped_function(&self) -> bool {
@@ -294,16 +684,14 @@ impl<A: Clone + Debug> Environment<A> {
 
         show_counter_env();
         show_env(format!("Env {} pushed:", self.id));
-        show_env(format!("{:?}", self));
     }
 
     pub fn pop(&mut self) -> () {
         self.stack.pop_front();
-        self.stack_len -= 1;
+        self.stack_len = self.stack_len.saturating_sub(1);
 
         show_counter_env();
         show_env(format!("Env {} popped:", self.id));
-        show_env(format!("{:?}", self));
     }
 
     pub fn get_all_variables(&self) -> Vec<(Name, (bool, A))> {
@@ -329,20 +717,90 @@ impl<A: Clone + Debug> Environment<A> {
     }
 
     // The type checker ensures that each function is defined only once
-    pub fn get_all_functions(&self) -> HashMap<FuncSignature, Function> {
-        let mut all_functions = HashMap::new();
-        for (func_signature, func) in &self.globals.functions {
-            all_functions.insert(func_signature.clone(), func.clone());
-        }
-        // It is necessary to traverse the scope stack from bottom to top
+    pub fn get_all_functions(&self) -> FunctionTable {
+        let mut scopes = Vec::new();
+        self.find_in_scopes(|scope, _, _| {
+            scopes.push(scope);
+            None::<()>
+        });
+        // It is necessary to traverse the scopes from the outermost to the innermost
         // so that functions defined in inner scopes can shadow those defined in outer scopes.
-        for scope in self.stack.iter().rev() {
+        let mut all_functions = HashMap::new();
+        for scope in scopes.into_iter().rev() {
             for (func_signature, func) in &scope.functions {
                 all_functions.insert(func_signature.clone(), func.clone());
             }
         }
         all_functions
     }
+
+    fn get_all_adts(&self) -> AdtTable {
+        let mut all_adts = (*self.inherited_adts).clone();
+        all_adts.extend(self.globals.adts.clone());
+        for scope in self.stack.iter().rev() {
+            for (name, constructors) in &scope.adts {
+                all_adts.insert(name.clone(), constructors.clone());
+            }
+        }
+        all_adts
+    }
+
+    fn visible_adts(&self) -> Rc<AdtTable> {
+        let scopes = self.stack.iter().chain(std::iter::once(&self.globals));
+        if scopes.clone().all(|scope| scope.adts.is_empty()) {
+            Rc::clone(&self.inherited_adts)
+        } else {
+            Rc::new(self.get_all_adts())
+        }
+    }
+
+    // Starts running the body of a function in fresh scopes, saving those of
+    // the caller until `exit_function`. The body also sees the scopes enclosing
+    // the definition of the function. The output is shared with the caller
+    pub fn enter_function(&mut self, func_signature: &FuncSignature, link: Link) {
+        let inherited_adts = self.visible_adts();
+        let frame = Frame {
+            stack_len: std::mem::take(&mut self.stack_len),
+            current_func: std::mem::replace(&mut self.current_func, func_signature.clone()),
+            loop_depth: std::mem::take(&mut self.loop_depth),
+            ended_variables: std::mem::take(&mut self.ended_variables),
+            globals: std::mem::replace(&mut self.globals, Scope::new()),
+            stack: std::mem::take(&mut self.stack),
+            link: self.link.replace(link),
+            inherited_adts: std::mem::replace(&mut self.inherited_adts, inherited_adts),
+        };
+        self.frames.push(frame);
+
+        show_counter_env();
+        show_env(format!(
+            "Env {} entered function {}",
+            self.id, func_signature
+        ));
+    }
+
+    // Number of function calls that are running, which bounds recursion
+    pub fn call_depth(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn exit_function(&mut self) {
+        if let Some(frame) = self.frames.pop() {
+            self.stack_len = frame.stack_len;
+            self.current_func = frame.current_func;
+            self.loop_depth = frame.loop_depth;
+            self.ended_variables = frame.ended_variables;
+            self.globals = frame.globals;
+            self.stack = frame.stack;
+            self.link = frame.link;
+            self.inherited_adts = frame.inherited_adts;
+        }
+
+        show_counter_env();
+        show_env(format!(
+            "Env {} returned to function {}",
+            self.id, self.current_func
+        ));
+    }
 }
 
 fn show_env(texto: String) {
@@ -354,11 +812,10 @@ fn show_counter_env() {
 }
 
 pub enum FuncOrVar<A: Clone + Debug> {
-    Func(Function),
+    Func(Rc<Function>, Link),
     Var((bool, A)),
 }
 
-/*
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -397,37 +854,89 @@ mod tests {
         assert_eq!(None, env.lookup(&"y".to_string())); // y is gone
     }
 
-    #[test]
-    fn test_function_scoping() {
-        let mut env: Environment<i32> = Environment::new();
-
-        let global_func = Function {
-            name: "global".to_string(),
+    fn function(name: &str) -> Function {
+        Function {
+            name: name.to_string(),
             kind: Type::TVoid,
             params: Vec::new(),
             body: None,
-        };
+        }
+    }
 
-        let local_func = Function {
-            name: "local".to_string(),
-            kind: Type::TVoid,
-            params: Vec::new(),
-            body: None,
-        };
+    fn signature(name: &str) -> FuncSignature {
+        FuncSignature::from_func(&function(name))
+    }
+
+    #[test]
+    fn test_function_scoping() {
+        let mut env: Environment<i32> = Environment::new();
 
         // Test function scoping
-        env.map_function(global_func.clone());
-        assert!(env.lookup_function(&"global".to_string()).is_some());
+        env.map_function(function("global"));
+        assert!(env.lookup_function(&signature("global")).is_some());
+
+        env.push();
+        env.map_function(function("local"));
+
+        assert!(env.lookup_function(&signature("global")).is_some()); // can see global
+        assert!(env.lookup_function(&signature("local")).is_some()); // can see local
+
+        env.pop();
+        assert!(env.lookup_function(&signature("global")).is_some()); // global still visible
+        assert!(env.lookup_function(&signature("local")).is_none()); // local gone
+    }
 
+    #[test]
+    fn test_functions_see_the_scopes_enclosing_their_definition() {
+        let mut env: Environment<i32> = Environment::new();
+        env.push(); // the top-level block
+        env.map_variable("x".to_string(), true, 1);
         env.push();
-        env.map_function(local_func.clone());
+        env.map_variable("y".to_string(), true, 2);
+        env.define_function(function("f"));
+        env.map_variable("w".to_string(), true, 4);
+        let (_, link) = env.lookup_closure(&signature("f")).unwrap();
+
+        env.push(); // a block entered after the definition
+        env.map_variable("z".to_string(), true, 3);
+        env.map_function(function("g"));
+
+        env.enter_function(&signature("f"), link);
+        // Enclosing variables are read only, and those of its own block declared
+        // after it are hidden
+        assert_eq!(Some((false, 1)), env.lookup(&"x".to_string()));
+        assert_eq!(Some((false, 2)), env.lookup(&"y".to_string()));
+        assert_eq!(None, env.lookup(&"w".to_string()));
+        assert_eq!(None, env.lookup(&"z".to_string()));
+        assert!(env.lookup_function(&signature("f")).is_some());
+        assert!(env.lookup_function(&signature("g")).is_none());
+
+        env.exit_function();
+        assert_eq!(Some((true, 3)), env.lookup(&"z".to_string()));
+    }
 
-        assert!(env.lookup_function(&"global".to_string()).is_some()); // can see global
-        assert!(env.lookup_function(&"local".to_string()).is_some()); // can see local
+    #[test]
+    fn test_functions_assign_the_variables_they_declare_global() {
+        let mut env: Environment<i32> = Environment::new();
+        env.push(); // the top-level block
+        env.map_variable("count".to_string(), true, 0);
+        env.define_function(function("f"));
+        env.map_variable("later".to_string(), true, 0);
+        let (_, link) = env.lookup_closure(&signature("f")).unwrap();
 
+        env.enter_function(&signature("f"), link);
+        env.push();
+        // Only the globals declared before the definition are visible
+        assert_eq!(None, env.lookup(&"later".to_string()));
+        assert_eq!(Some((false, 0)), env.lookup(&"count".to_string()));
+        assert_eq!(Some((true, 0)), env.lookup_global(&"count".to_string()));
+        env.declare_global("count".to_string());
+        assert_eq!(Some((true, 0)), env.lookup(&"count".to_string()));
+        assert!(env.change_variable_value("count".to_string(), 1).is_ok());
+        assert!(env.create_variable("count".to_string(), true, 2).is_err());
         env.pop();
-        assert!(env.lookup_function(&"global".to_string()).is_some()); // global still visible
-        assert!(env.lookup_function(&"local".to_string()).is_none()); // local gone
+        env.exit_function();
+
+        assert_eq!(Some((true, 1)), env.lookup(&"count".to_string()));
     }
 }
-*/
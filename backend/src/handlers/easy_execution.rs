@@ -1,10 +1,15 @@
+use crate::handlers::run_guarded;
 use crate::models::{Block, EasyInterpreter, Input, Value, Workspace};
 use actix_web::{HttpResponse, Responder, web};
 
 pub async fn execute(payload: web::Json<Workspace>) -> impl Responder {
-    let mut interpreter = EasyInterpreter::new();
+    let output = run_guarded(|| {
+        let mut interpreter = EasyInterpreter::new();
 
-    interpreter.run(&payload.blocks.blocks);
+        interpreter.run(&payload.blocks.blocks);
 
-    HttpResponse::Ok().json(interpreter.into_output())
+        interpreter.into_output()
+    });
+
+    HttpResponse::Ok().json(output)
 }
@@ -1,10 +1,12 @@
-use crate::COUNTER;
 use crate::environment::environment::Environment;
-use crate::interpreter::run;
-use crate::ir::ast::{Expression, FormalArgument, Function, Statement, Type};
+use crate::handlers::run_guarded;
+use crate::interpreter::{Value, run};
+use crate::ir::ast::{Statement, Type};
 use crate::models::{Block2, Blocks2, Input2, NextBlock2, Workspace2};
 use crate::parser::parse_chained_blocks;
-use crate::type_checker::check_stmt;
+use crate::type_checker::{analyze, check_stmt};
+use crate::vm;
+use crate::{COUNTER, LOG_STEPS};
 use actix_web::{HttpResponse, Responder, post, web};
 use nom::{Err, Finish};
 use nom::{
@@ -26,7 +28,9 @@ use std::str::FromStr;
 use std::{fs::File, process::Output};
 
 pub async fn execute(payload: web::Json<Workspace2>) -> impl Responder {
-    reset_txt_files();
+    if *LOG_STEPS {
+        reset_txt_files();
+    }
 
     let mut output: Vec<String> = Vec::new();
 
@@ -39,7 +43,7 @@ pub async fn execute(payload: web::Json<Workspace2>) -> impl Responder {
         return HttpResponse::InternalServerError().body(err_msg);
     }
 
-    output = process_blocks(blocks_only);
+    output = run_guarded(|| process_blocks(blocks_only, payload.bytecode));
 
     //output = process_blocks(blocks_only);
     HttpResponse::Ok().json(output)
@@ -49,7 +53,7 @@ pub fn execute_with_json() {
     let mut output = Vec::new();
     let blocks_only_res = generate_blocks_only("factorial.json");
     if let Ok(blocks_only) = &blocks_only_res {
-        output = process_blocks(blocks_only);
+        output = process_blocks(blocks_only, false);
     }
     show_ex(format!("{:?}", output));
 }
@@ -76,11 +80,14 @@ pub fn save_json_in_file<T: Serialize>(blocks_only: &T, caminho: &str) -> Result
     Ok(())
 }
 
-pub fn process_blocks(blocks_only: &Vec<Block2>) -> Vec<String> {
-    let mut output = vec!["output is empty".to_string()];
+// Parses the top-level blocks of a workspace into a single block, holding the
+// function declarations followed by the statements of the main chain
+pub fn parse_workspace(blocks_only: &[Block2]) -> Result<Statement, Vec<String>> {
     let mut number_of_global_estatements: u16 = 0;
     let mut global_statements: Option<Vec<Statement>> = None;
     let mut func_def_statements: Vec<Statement> = Vec::new();
+    // Every top-level block is parsed, so that all the parse errors are reported at once
+    let mut parse_errors: Vec<String> = Vec::new();
     for block in blocks_only {
         if block.r#type == "function_declaration_block" {
             let func_name_opt = block
@@ -92,17 +99,17 @@ pub fn process_blocks(blocks_only: &Vec<Block2>) -> Vec<String> {
                     show_counter_ex();
                     show_ex(format!(
                         "Function {} parsed successfully",
-                        func_name_opt.unwrap()
+                        func_name_opt.map_or("<unnamed>", |name| name.as_str())
                     ));
                     show_ex(format!("Function statement: {:?}", statement));
                     match statement.clone() {
-                        Statement::Block(func_def) => func_def_statements.push(func_def[0].clone()),
+                        Statement::Block(func_def) => func_def_statements.extend(func_def),
                         _ => show_ex("parse chained tem que retornar block".to_string()),
                     }
                 }
-                Err(e) => {
-                    output = vec![e.clone()];
-                    show_ex(format!("{:?}", e));
+                Err(errors) => {
+                    show_ex(format!("{:?}", errors));
+                    parse_errors.extend(errors.iter().map(|e| e.to_string()));
                 }
             }
         } else {
@@ -115,61 +122,101 @@ pub fn process_blocks(blocks_only: &Vec<Block2>) -> Vec<String> {
                     match statement {
                         Statement::Block(vector) => global_statements = Some(vector),
                         _ => {
-                            output = vec!["main body cannot be empty".to_string()];
+                            parse_errors.push("main body cannot be empty".to_string());
                             show_ex("main body cannot be empty".to_string());
                         }
                     }
                 }
-                Err(e) => {
-                    output = vec![e.clone()];
-                    show_ex(format!("{:?}", e));
+                Err(errors) => {
+                    show_ex(format!("{:?}", errors));
+                    parse_errors.extend(errors.iter().map(|e| e.to_string()));
                 }
             }
         }
     }
     if number_of_global_estatements != 1 {
-        output = vec!["There must be one and only one global statement".to_string()];
+        parse_errors.push("There must be one and only one global statement".to_string());
         show_ex("There must be one and only one global statement".to_string());
-    } else {
-        let global_statements = global_statements.unwrap();
-        for vector in global_statements {
-            func_def_statements.push(vector);
-        }
-        let final_statement = Statement::Block(func_def_statements);
-        show_counter_ex();
-        show_ex(format!("final statement: {:?}", final_statement));
-        let type_env: Environment<Type> = Environment::new();
-        show_counter_ex();
-        show_ex(format!("Initial Type Env: {:?}", type_env));
-        match check_stmt(final_statement.clone(), &type_env) {
-            Ok(new_type_env) => {
-                show_counter_ex();
-                show_ex(format!("Final Type Env: {:?}", new_type_env));
-                let exp_env: Environment<Expression> = Environment::new();
-                show_counter_ex();
-                show_ex(format!("Initial Exp Env: {:?}", exp_env));
-                match run(final_statement.clone(), &exp_env) {
-                    Ok(mut new_exp_env) => {
+    }
+    if !parse_errors.is_empty() {
+        return Err(parse_errors);
+    }
+    let global_statements = global_statements.unwrap_or_default();
+    for vector in global_statements {
+        func_def_statements.push(vector);
+    }
+    Ok(Statement::Block(func_def_statements))
+}
+
+// Type checks and runs the program of a workspace. The warnings of the static
+// analysis come before the output of a program that type checks
+pub fn process_blocks(blocks_only: &[Block2], bytecode: bool) -> Vec<String> {
+    let mut output = vec!["output is empty".to_string()];
+    let mut warnings = Vec::new();
+    let mut final_statement = match parse_workspace(blocks_only) {
+        Ok(final_statement) => final_statement,
+        Err(parse_errors) => return parse_errors,
+    };
+    show_counter_ex();
+    show_ex(format!("final statement: {:?}", final_statement));
+    let type_env: Environment<Type> = Environment::new();
+    show_counter_ex();
+    show_ex(format!("Initial Type Env: {:?}", type_env));
+    match check_stmt(&mut final_statement, &type_env) {
+        Ok(new_type_env) => {
+            show_counter_ex();
+            show_ex(format!("Final Type Env: {:?}", new_type_env));
+            warnings = analyze(&final_statement);
+            if bytecode {
+                match vm::compile(&final_statement) {
+                    Ok(program) => {
                         show_counter_ex();
-                        show_ex(format!("Final Exp Env: {:?}", new_exp_env));
-                        show_ex(format!("Variables: {:?} ", new_exp_env.get_all_variables()));
-                        if !new_exp_env.get_output().is_empty() {
-                            output = new_exp_env.get_output();
+                        show_ex(format!("Bytecode: {:?}", program));
+                        match vm::execute(&program) {
+                            Ok(vm_output) => {
+                                if !vm_output.is_empty() {
+                                    output = vm_output;
+                                }
+                            }
+                            Err(e) => {
+                                output = vec![e.clone()];
+                                show_ex(format!("{:?}", e));
+                            }
                         }
+                        return [warnings, output].concat();
                     }
+                    // Programs the VM cannot compile still run on the interpreter,
+                    // with a warning saying why
                     Err(e) => {
-                        output = vec![e.clone()];
                         show_ex(format!("{:?}", e));
+                        warnings.push(format!("{}, running on the interpreter", e));
                     }
                 }
             }
-            Err(e) => {
-                output = vec![e.clone()];
-                show_ex(format!("{:?}", e));
+            let mut exp_env: Environment<Value> = Environment::new();
+            show_counter_ex();
+            show_ex(format!("Initial Exp Env: {:?}", exp_env));
+            match run(&final_statement, &mut exp_env) {
+                Ok(()) => {
+                    show_counter_ex();
+                    show_ex(format!("Final Exp Env: {:?}", exp_env));
+                    show_ex(format!("Variables: {:?} ", exp_env.get_all_variables()));
+                    if !exp_env.get_output().is_empty() {
+                        output = exp_env.get_output();
+                    }
+                }
+                Err(e) => {
+                    output = vec![e.clone()];
+                    show_ex(format!("{:?}", e));
+                }
             }
         }
+        Err(e) => {
+            output = vec![e.clone()];
+            show_ex(format!("{:?}", e));
+        }
     }
-    return output;
+    return [warnings, output].concat();
 }
 
 pub fn reset_txt_files() {
@@ -190,17 +237,26 @@ pub fn reset_txt_files() {
 }
 
 pub fn show(texto: String, path: &str) {
-    let mut file = OpenOptions::new()
+    if !*LOG_STEPS {
+        return;
+    }
+    // Ignora erros de abrir ou escrever no arquivo: o log nunca deve derrubar a execução
+    if let Ok(mut file) = OpenOptions::new()
         //.create(true)
         .append(true)
         .open(path)
-        .unwrap(); // Ignora erro de abrir/criar o arquivo, panica se falhar
-
-    writeln!(file, "{}\n", texto).unwrap(); // Ignora erro de escrita, panica se falhar
+    {
+        let _ = writeln!(file, "{}\n", texto);
+    }
 }
 
 pub fn show_counter(path: &str) {
-    let mut count = COUNTER.lock().unwrap();
+    if !*LOG_STEPS {
+        return;
+    }
+    let mut count = COUNTER
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
     *count += 1;
 
     let string = format!(
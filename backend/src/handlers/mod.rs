@@ -1,2 +1,83 @@
-pub mod easy_execution;
-pub mod hard_interpreter;
+pub mod easy_execution;
+pub mod hard_interpreter;
+
+use std::thread;
+
+// Programs run on a stack of their own, deep enough for the interpreter to
+// reach its maximum call depth even in debug builds
+pub const PROGRAM_STACK_SIZE: usize = 256 * 1024 * 1024;
+
+/// Runs a program on behalf of a handler, turning a panic into a diagnostic
+/// line so that a bug in the pipeline never takes down the actix worker. The
+/// program runs on its own thread, whose stack is sized for deep recursion.
+pub fn run_guarded<F>(run: F) -> Vec<String>
+where
+    F: FnOnce() -> Vec<String> + Send,
+{
+    let outcome = thread::scope(|scope| {
+        thread::Builder::new()
+            .stack_size(PROGRAM_STACK_SIZE)
+            .spawn_scoped(scope, run)
+            .map(|handle| handle.join())
+    });
+    match outcome {
+        Ok(Ok(output)) => output,
+        Ok(Err(payload)) => {
+            let message = if let Some(message) = payload.downcast_ref::<&str>() {
+                message.to_string()
+            } else if let Some(message) = payload.downcast_ref::<String>() {
+                message.clone()
+            } else {
+                "unknown panic".to_string()
+            };
+            vec![format!("Internal error: {}", message)]
+        }
+        Err(error) => vec![format!("Internal error: {}", error)],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::environment::environment::Environment;
+    use crate::interpreter::run;
+    use crate::ir::ast::Statement;
+    use crate::parser::parse;
+
+    #[test]
+    fn test_run_guarded_returns_output() {
+        assert_eq!(
+            run_guarded(|| vec!["ok".to_string()]),
+            vec!["ok".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_run_guarded_turns_panic_into_diagnostic() {
+        let output = run_guarded(|| panic!("boom"));
+        assert_eq!(output, vec!["Internal error: boom".to_string()]);
+
+        let output = run_guarded(|| panic!("{} went wrong", "something"));
+        assert_eq!(
+            output,
+            vec!["Internal error: something went wrong".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_run_guarded_stops_endless_recursion() {
+        let output = run_guarded(|| {
+            let source = "def f(n: int) -> int: ret f(n + 1) + 1; end; val x = f(0);";
+            let (_, stmts) = parse(source).expect("program should parse");
+            let mut env = Environment::new();
+            match run(&Statement::Block(stmts), &mut env) {
+                Ok(()) => env.output,
+                Err(error) => vec![error],
+            }
+        });
+        assert_eq!(
+            output,
+            vec!["Maximum recursion depth exceeded calling f(int)".to_string()]
+        );
+    }
+}
@@ -1,701 +1,562 @@
-use core::prelude::v1;
-use std::fmt::format;
+use std::rc::Rc;
 
 use super::statement_execute::Computation;
-use crate::environment::environment::{Environment, FuncOrVar};
-use crate::ir::ast::{Expression, Function, Name};
-use crate::ir::ast::{FuncSignature, Type};
-use crate::type_checker::check_expr;
+use super::value::Value;
+use crate::environment::environment::{Environment, FuncOrVar, Link};
+use crate::ir::ast::FuncSignature;
+use crate::ir::ast::{Comparison, Expression, Function, Name, Resolution, Type};
+use crate::ir::builtins::builtin_call;
 use crate::{show, show_counter};
 
+// Deepest chain of function calls a program may run, like the VM's
+const MAX_CALL_DEPTH: usize = 1000;
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum ExpressionResult {
-    Value(Expression),
-    Propagate(Expression), // For error propagation in Maybe/Result types
+    Value(Value),
+    Propagate(Value), // For error propagation in Maybe/Result types
 }
 
-pub fn eval(
-    exp: Expression,
-    env: &mut Environment<Expression>,
-) -> Result<ExpressionResult, String> {
-    match exp.clone() {
+pub fn eval(exp: &Expression, env: &mut Environment<Value>) -> Result<ExpressionResult, String> {
+    match exp {
+        Expression::CTrue => Ok(ExpressionResult::Value(Value::Bool(true))),
+        Expression::CFalse => Ok(ExpressionResult::Value(Value::Bool(false))),
+        Expression::CInt(value) => Ok(ExpressionResult::Value(Value::Int(*value))),
+        Expression::CReal(value) => Ok(ExpressionResult::Value(Value::Real(*value))),
+        Expression::CString(value) => Ok(ExpressionResult::Value(Value::str(value))),
+        Expression::CVoid => Ok(ExpressionResult::Value(Value::Void)),
+        Expression::CNothing => Ok(ExpressionResult::Value(Value::Nothing)),
+        Expression::Default(e) => eval(e, env),
         Expression::Add(lhs, rhs) => {
             show_counter_exp_eval();
             show_exp_eval(format!("Eval Add:"));
             show_exp_eval(format!("Expression: {:?}", exp));
-            show_exp_eval(format!("Env: {:?}", env));
-            eval_add(*lhs, *rhs, env)
+            eval_binary(lhs, rhs, env, add_values)
         }
-        Expression::Sub(lhs, rhs) => eval_sub(*lhs, *rhs, env),
+        Expression::Sub(lhs, rhs) => eval_binary(lhs, rhs, env, sub_values),
         Expression::Mul(lhs, rhs) => {
             show_counter_exp_eval();
             show_exp_eval(format!("Eval Mul:"));
             show_exp_eval(format!("Expression: {:?}", exp));
-            show_exp_eval(format!("Env: {:?}", env));
-            eval_mul(*lhs, *rhs, env)
+            eval_binary(lhs, rhs, env, mul_values)
         }
         Expression::Div(lhs, rhs) => {
             show_counter_exp_eval();
             show_exp_eval(format!("Eval Div:"));
             show_exp_eval(format!("Expression: {:?}", exp));
-            show_exp_eval(format!("Env: {:?}", env));
-            eval_div(*lhs, *rhs, env)
+            eval_binary(lhs, rhs, env, div_values)
         }
+        Expression::Mod(lhs, rhs) => eval_binary(lhs, rhs, env, mod_values),
+        Expression::Pow(lhs, rhs) => eval_binary(lhs, rhs, env, pow_values),
+        Expression::Neg(e) => eval_unary(e, env, neg_value),
+        Expression::ToReal(e) => eval_unary(e, env, to_real_value),
         Expression::And(lhs, rhs) => {
             show_counter_exp_eval();
             show_exp_eval(format!("Eval And:"));
             show_exp_eval(format!("Expression: {:?}", exp));
-            show_exp_eval(format!("Env: {:?}", env));
-            eval_and(*lhs, *rhs, env)
+            eval_binary(lhs, rhs, env, and_values)
         }
         Expression::Or(lhs, rhs) => {
             show_counter_exp_eval();
             show_exp_eval(format!("Eval Or:"));
             show_exp_eval(format!("Expression: {:?}", exp));
-            show_exp_eval(format!("Env: {:?}", env));
-            eval_or(*lhs, *rhs, env)
+            eval_binary(lhs, rhs, env, or_values)
         }
         Expression::Not(lhs) => {
             show_counter_exp_eval();
             show_exp_eval(format!("Eval Not:"));
             show_exp_eval(format!("Expression: {:?}", exp));
-            show_exp_eval(format!("Env: {:?}", env));
-            eval_not(*lhs, env)
+            eval_unary(lhs, env, not_value)
         }
         Expression::EQ(lhs, rhs) => {
             show_counter_exp_eval();
             show_exp_eval(format!("Eval EQ:"));
             show_exp_eval(format!("Expression: {:?}", exp));
-            show_exp_eval(format!("Env: {:?}", env));
-            eval_eq(*lhs, *rhs, env)
+            eval_binary(lhs, rhs, env, eq_values)
         }
         Expression::NEQ(lhs, rhs) => {
             show_counter_exp_eval();
             show_exp_eval(format!("Eval NEQ:"));
             show_exp_eval(format!("Expression: {:?}", exp));
-            show_exp_eval(format!("Env: {:?}", env));
-            eval_neq(*lhs, *rhs, env)
+            eval_binary(lhs, rhs, env, neq_values)
         }
         Expression::GT(lhs, rhs) => {
             show_counter_exp_eval();
             show_exp_eval(format!("Eval GT:"));
             show_exp_eval(format!("Expression: {:?}", exp));
-            show_exp_eval(format!("Env: {:?}", env));
-            eval_gt(*lhs, *rhs, env)
+            eval_binary(lhs, rhs, env, gt_values)
         }
         Expression::LT(lhs, rhs) => {
             show_counter_exp_eval();
             show_exp_eval(format!("Eval LT:"));
             show_exp_eval(format!("Expression: {:?}", exp));
-            show_exp_eval(format!("Env: {:?}", env));
-            eval_lt(*lhs, *rhs, env)
+            eval_binary(lhs, rhs, env, lt_values)
         }
         Expression::GTE(lhs, rhs) => {
             show_counter_exp_eval();
             show_exp_eval(format!("Eval GTE:"));
             show_exp_eval(format!("Expression: {:?}", exp));
-            show_exp_eval(format!("Env: {:?}", env));
-            eval_gte(*lhs, *rhs, env)
+            eval_binary(lhs, rhs, env, gte_values)
         }
         Expression::LTE(lhs, rhs) => {
             show_counter_exp_eval();
             show_exp_eval(format!("Eval LTE:"));
             show_exp_eval(format!("Expression: {:?}", exp));
-            show_exp_eval(format!("Env: {:?}", env));
-            eval_lte(*lhs, *rhs, env)
+            eval_binary(lhs, rhs, env, lte_values)
+        }
+        Expression::Chain(first, links) => {
+            show_counter_exp_eval();
+            show_exp_eval("Eval Chain:".to_string());
+            show_exp_eval(format!("Expression: {:?}", exp));
+            eval_chain(first, links, env)
         }
         Expression::Var(name) => {
             show_counter_exp_eval();
             show_exp_eval(format!("Eval Var:"));
             show_exp_eval(format!("Expression: {:?}", exp));
-            show_exp_eval(format!("Env: {:?}", env));
             eval_lookup(name, env)
         }
+        Expression::FuncRef(func_signature) => match env.lookup_closure(func_signature) {
+            Some((func, link)) => Ok(ExpressionResult::Value(Value::Closure(func.clone(), link))),
+            None => Err(format!("Function '{}' not found", func_signature)),
+        },
         Expression::COk(e) => {
             show_counter_exp_eval();
             show_exp_eval(format!("Eval COk:"));
             show_exp_eval(format!("Expression: {:?}", exp));
-            show_exp_eval(format!("Env: {:?}", env));
-            eval_ok(*e, env)
+            eval_unary(e, env, ok_value)
         }
         Expression::CErr(e) => {
             show_counter_exp_eval();
             show_exp_eval(format!("Eval CErr:"));
             show_exp_eval(format!("Expression: {:?}", exp));
-            show_exp_eval(format!("Env: {:?}", env));
-            eval_err(*e, env)
+            eval_unary(e, env, err_value)
         }
         Expression::CJust(e) => {
             show_counter_exp_eval();
             show_exp_eval(format!("Eval CJust:"));
             show_exp_eval(format!("Expression: {:?}", exp));
-            show_exp_eval(format!("Env: {:?}", env));
-            eval_just(*e, env)
+            eval_unary(e, env, just_value)
         }
         Expression::Unwrap(e) => {
             show_counter_exp_eval();
             show_exp_eval(format!("Eval Unwrap:"));
             show_exp_eval(format!("Expression: {:?}", exp));
-            show_exp_eval(format!("Env: {:?}", env));
-            eval_unwrap_expression(*e, env)
+            eval_unary(e, env, unwrap_value)
         }
         Expression::Propagate(e) => {
             show_counter_exp_eval();
             show_exp_eval(format!("Eval Propagate:"));
             show_exp_eval(format!("Expression: {:?}", exp));
-            show_exp_eval(format!("Env: {:?}", env));
-            eval_propagate_expression(*e, env)
+            eval_propagate_expression(e, env)
+        }
+        Expression::Map(e, func) => {
+            show_counter_exp_eval();
+            show_exp_eval("Eval Map:".to_string());
+            show_exp_eval(format!("Expression: {:?}", exp));
+            eval_combinator(e, func, false, env)
+        }
+        Expression::AndThen(e, func) => {
+            show_counter_exp_eval();
+            show_exp_eval("Eval AndThen:".to_string());
+            show_exp_eval(format!("Expression: {:?}", exp));
+            eval_combinator(e, func, true, env)
         }
+        Expression::UnwrapOr(e, default) => eval_binary(e, default, env, unwrap_or_values),
+        Expression::OkOr(e, error) => eval_binary(e, error, env, ok_or_values),
         Expression::IsError(e) => {
             show_counter_exp_eval();
             show_exp_eval(format!("Eval IsError:"));
             show_exp_eval(format!("Expression: {:?}", exp));
-            show_exp_eval(format!("Env: {:?}", env));
-            eval_iserror_expression(*e, env)
+            eval_unary(e, env, is_error_value)
         }
         Expression::IsNothing(e) => {
             show_counter_exp_eval();
             show_exp_eval(format!("Eval IsNothing:"));
             show_exp_eval(format!("Expression: {:?}", exp));
-            show_exp_eval(format!("Env: {:?}", env));
-            eval_isnothing_expression(*e, env)
+            eval_unary(e, env, is_nothing_value)
         }
-        Expression::FuncCall(name, args) => {
+        Expression::FuncCall(name, args, resolution) => {
             show_counter_exp_eval();
             show_exp_eval(format!("Eval Function Call:"));
             show_exp_eval(format!("Expression: {:?}", exp));
-            show_exp_eval(format!("Env: {:?}", env));
-            eval_function_call(name, args, env)
+            eval_function_call(name, args, resolution, env)
         }
         Expression::ListValue(values) => {
             show_counter_exp_eval();
             show_exp_eval(format!("Eval ListValue:"));
             show_exp_eval(format!("Expression: {:?}", exp));
-            show_exp_eval(format!("Env: {:?}", env));
             eval_list_value(values, env)
         }
-        _ if is_constant(exp.clone()) => Ok(ExpressionResult::Value(exp)),
+        Expression::Range(start, end) => {
+            show_counter_exp_eval();
+            show_exp_eval(format!("Eval Range:"));
+            show_exp_eval(format!("Expression: {:?}", exp));
+            eval_binary(start, end, env, range_values)
+        }
+        Expression::Constructor(name, args) => {
+            show_counter_exp_eval();
+            show_exp_eval(format!("Eval Constructor:"));
+            show_exp_eval(format!("Expression: {:?}", exp));
+            eval_constructor(name, args, env)
+        }
         _ => Err(format!(
             "Interpreter still does not support expression of type {:?}",
-            exp.clone()
+            exp
         )),
     }
 }
 
-// Helper function for arithmetic operations
-fn eval_binary_arith_op<F>(
-    lhs: Expression,
-    rhs: Expression,
-    env: &mut Environment<Expression>,
-    op: F,
-    error_msg: &str,
-) -> Result<ExpressionResult, String>
-where
-    F: Fn(f64, f64) -> f64,
-{
+// Evaluates both operands, in order, before applying an operation to their values
+fn eval_binary(
+    lhs: &Expression,
+    rhs: &Expression,
+    env: &mut Environment<Value>,
+    op: fn(Value, Value) -> Result<Value, String>,
+) -> Result<ExpressionResult, String> {
     let v1 = match eval(lhs, env)? {
-        ExpressionResult::Value(expr) => expr,
-        ExpressionResult::Propagate(expr) => return Ok(ExpressionResult::Propagate(expr)),
+        ExpressionResult::Value(value) => value,
+        ExpressionResult::Propagate(value) => return Ok(ExpressionResult::Propagate(value)),
     };
     let v2 = match eval(rhs, env)? {
-        ExpressionResult::Value(expr) => expr,
-        ExpressionResult::Propagate(expr) => return Ok(ExpressionResult::Propagate(expr)),
+        ExpressionResult::Value(value) => value,
+        ExpressionResult::Propagate(value) => return Ok(ExpressionResult::Propagate(value)),
     };
+    op(v1, v2).map(ExpressionResult::Value)
+}
 
+// Evaluates every operand once, so the right operand of a comparison is kept
+// as the left operand of the next one
+fn eval_chain(
+    first: &Expression,
+    links: &[(Comparison, Expression)],
+    env: &mut Environment<Value>,
+) -> Result<ExpressionResult, String> {
+    let mut left = match eval(first, env)? {
+        ExpressionResult::Value(value) => value,
+        ExpressionResult::Propagate(value) => return Ok(ExpressionResult::Propagate(value)),
+    };
+    let mut result = Value::Bool(true);
+    for (comparison, operand) in links {
+        let right = match eval(operand, env)? {
+            ExpressionResult::Value(value) => value,
+            ExpressionResult::Propagate(value) => return Ok(ExpressionResult::Propagate(value)),
+        };
+        let op = match comparison {
+            Comparison::EQ => eq_values,
+            Comparison::NEQ => neq_values,
+            Comparison::GT => gt_values,
+            Comparison::LT => lt_values,
+            Comparison::GTE => gte_values,
+            Comparison::LTE => lte_values,
+        };
+        let holds = op(left, right.clone())?;
+        result = and_values(result, holds)?;
+        left = right;
+    }
+    Ok(ExpressionResult::Value(result))
+}
+
+fn eval_unary(
+    exp: &Expression,
+    env: &mut Environment<Value>,
+    op: fn(Value) -> Result<Value, String>,
+) -> Result<ExpressionResult, String> {
+    let v = match eval(exp, env)? {
+        ExpressionResult::Value(value) => value,
+        ExpressionResult::Propagate(value) => return Ok(ExpressionResult::Propagate(value)),
+    };
+    op(v).map(ExpressionResult::Value)
+}
+
+// The operations below work on values, so that they are shared with the bytecode VM
+
+// Helper function for arithmetic operations
+fn arith_values<F>(v1: Value, v2: Value, op: F, error_msg: &str) -> Result<Value, String>
+where
+    F: Fn(f64, f64) -> f64,
+{
     match (v1, v2) {
-        (Expression::CInt(v1), Expression::CInt(v2)) => Ok(ExpressionResult::Value(
-            Expression::CInt(op(v1 as f64, v2 as f64) as i32),
-        )),
-        (Expression::CInt(v1), Expression::CReal(v2)) => Ok(ExpressionResult::Value(
-            Expression::CReal(op(v1 as f64, v2)),
-        )),
-        (Expression::CReal(v1), Expression::CInt(v2)) => Ok(ExpressionResult::Value(
-            Expression::CReal(op(v1, v2 as f64)),
-        )),
-        (Expression::CReal(v1), Expression::CReal(v2)) => {
-            Ok(ExpressionResult::Value(Expression::CReal(op(v1, v2))))
-        }
+        (Value::Int(v1), Value::Int(v2)) => Ok(Value::Int(op(v1 as f64, v2 as f64) as i32)),
+        (Value::Int(v1), Value::Real(v2)) => Ok(Value::Real(op(v1 as f64, v2))),
+        (Value::Real(v1), Value::Int(v2)) => Ok(Value::Real(op(v1, v2 as f64))),
+        (Value::Real(v1), Value::Real(v2)) => Ok(Value::Real(op(v1, v2))),
         _ => Err(error_msg.to_string()),
     }
 }
 
 // Helper function for boolean operations
-fn eval_binary_boolean_op<F>(
-    lhs: Expression,
-    rhs: Expression,
-    env: &mut Environment<Expression>,
-    op: F,
-    error_msg: &str,
-) -> Result<ExpressionResult, String>
+fn boolean_values<F>(v1: Value, v2: Value, op: F, error_msg: &str) -> Result<Value, String>
 where
-    F: Fn(bool, bool) -> Expression,
+    F: Fn(bool, bool) -> bool,
 {
-    let v1 = match eval(lhs, env)? {
-        ExpressionResult::Value(expr) => expr,
-        ExpressionResult::Propagate(expr) => return Ok(ExpressionResult::Propagate(expr)),
-    };
-    let v2 = match eval(rhs, env)? {
-        ExpressionResult::Value(expr) => expr,
-        ExpressionResult::Propagate(expr) => return Ok(ExpressionResult::Propagate(expr)),
-    };
-
     match (v1, v2) {
-        (Expression::CTrue, Expression::CTrue) => Ok(ExpressionResult::Value(op(true, true))),
-        (Expression::CTrue, Expression::CFalse) => Ok(ExpressionResult::Value(op(true, false))),
-        (Expression::CFalse, Expression::CTrue) => Ok(ExpressionResult::Value(op(false, true))),
-        (Expression::CFalse, Expression::CFalse) => Ok(ExpressionResult::Value(op(false, false))),
+        (Value::Bool(v1), Value::Bool(v2)) => Ok(Value::Bool(op(v1, v2))),
         _ => Err(error_msg.to_string()),
     }
 }
 
 // Helper function for relational operations
-fn eval_binary_rel_op<F>(
-    lhs: Expression,
-    rhs: Expression,
-    env: &mut Environment<Expression>,
-    op: F,
-    error_msg: &str,
-) -> Result<ExpressionResult, String>
+fn relational_values<F>(v1: Value, v2: Value, op: F, error_msg: &str) -> Result<Value, String>
 where
-    F: Fn(f64, f64) -> Expression,
+    F: Fn(f64, f64) -> bool,
 {
-    let v1 = match eval(lhs, env)? {
-        ExpressionResult::Value(expr) => expr,
-        ExpressionResult::Propagate(expr) => return Ok(ExpressionResult::Propagate(expr)),
-    };
-    let v2 = match eval(rhs, env)? {
-        ExpressionResult::Value(expr) => expr,
-        ExpressionResult::Propagate(expr) => return Ok(ExpressionResult::Propagate(expr)),
-    };
-
     match (v1, v2) {
-        (Expression::CInt(v1), Expression::CInt(v2)) => {
-            Ok(ExpressionResult::Value(op(v1 as f64, v2 as f64)))
-        }
-        (Expression::CInt(v1), Expression::CReal(v2)) => {
-            Ok(ExpressionResult::Value(op(v1 as f64, v2)))
-        }
-        (Expression::CReal(v1), Expression::CInt(v2)) => {
-            Ok(ExpressionResult::Value(op(v1, v2 as f64)))
-        }
-        (Expression::CReal(v1), Expression::CReal(v2)) => Ok(ExpressionResult::Value(op(v1, v2))),
+        (Value::Int(v1), Value::Int(v2)) => Ok(Value::Bool(op(v1 as f64, v2 as f64))),
+        (Value::Int(v1), Value::Real(v2)) => Ok(Value::Bool(op(v1 as f64, v2))),
+        (Value::Real(v1), Value::Int(v2)) => Ok(Value::Bool(op(v1, v2 as f64))),
+        (Value::Real(v1), Value::Real(v2)) => Ok(Value::Bool(op(v1, v2))),
         _ => Err(error_msg.to_string()),
     }
 }
 
 // Arithmetic Operations
-fn eval_add(
-    lhs: Expression,
-    rhs: Expression,
-    env: &mut Environment<Expression>,
-) -> Result<ExpressionResult, String> {
-    let v1 = match eval(lhs, env)? {
-        ExpressionResult::Value(expr) => expr,
-        ExpressionResult::Propagate(expr) => return Ok(ExpressionResult::Propagate(expr)),
-    };
-    let v2 = match eval(rhs, env)? {
-        ExpressionResult::Value(expr) => expr,
-        ExpressionResult::Propagate(expr) => return Ok(ExpressionResult::Propagate(expr)),
-    };
-
+pub fn add_values(v1: Value, v2: Value) -> Result<Value, String> {
     match (v1, v2) {
-        (Expression::CInt(v1), Expression::CInt(v2)) => {
-            Ok(ExpressionResult::Value(Expression::CInt(v1 + v2)))
-        }
-        (Expression::CInt(v1), Expression::CReal(v2)) => {
-            Ok(ExpressionResult::Value(Expression::CReal(v1 as f64 + v2)))
-        }
-        (Expression::CReal(v1), Expression::CInt(v2)) => {
-            Ok(ExpressionResult::Value(Expression::CReal(v1 + v2 as f64)))
-        }
-        (Expression::CReal(v1), Expression::CReal(v2)) => {
-            Ok(ExpressionResult::Value(Expression::CReal(v1 + v2)))
-        }
-        (Expression::CString(v1), Expression::CString(v2)) => Ok(ExpressionResult::Value(
-            Expression::CString(format!("{}{}", v1, v2)),
-        )),
+        (Value::Int(v1), Value::Int(v2)) => match v1.checked_add(v2) {
+            Some(value) => Ok(Value::Int(value)),
+            None => Err(format!("integer overflow computing {} + {}", v1, v2)),
+        },
+        (Value::Int(v1), Value::Real(v2)) => Ok(Value::Real(v1 as f64 + v2)),
+        (Value::Real(v1), Value::Int(v2)) => Ok(Value::Real(v1 + v2 as f64)),
+        (Value::Real(v1), Value::Real(v2)) => Ok(Value::Real(v1 + v2)),
+        (Value::Str(v1), Value::Str(v2)) => Ok(Value::Str(Rc::from(format!("{}{}", v1, v2)))),
         _ => Err("sum: operands must both be numbers or both be strings".to_string()),
     }
 }
 
-fn eval_sub(
-    lhs: Expression,
-    rhs: Expression,
-    env: &mut Environment<Expression>,
-) -> Result<ExpressionResult, String> {
-    eval_binary_arith_op(
-        lhs,
-        rhs,
-        env,
-        |a, b| a - b,
-        "subtraction '(-)' is only defined for numbers (integers and real).",
-    )
+pub fn sub_values(v1: Value, v2: Value) -> Result<Value, String> {
+    match (v1, v2) {
+        (Value::Int(v1), Value::Int(v2)) => match v1.checked_sub(v2) {
+            Some(value) => Ok(Value::Int(value)),
+            None => Err(format!("integer overflow computing {} - {}", v1, v2)),
+        },
+        (v1, v2) => arith_values(
+            v1,
+            v2,
+            |a, b| a - b,
+            "subtraction '(-)' is only defined for numbers (integers and real).",
+        ),
+    }
 }
 
-fn eval_mul(
-    lhs: Expression,
-    rhs: Expression,
-    env: &mut Environment<Expression>,
-) -> Result<ExpressionResult, String> {
-    let v1 = match eval(lhs, env)? {
-        ExpressionResult::Value(expr) => expr,
-        ExpressionResult::Propagate(expr) => return Ok(ExpressionResult::Propagate(expr)),
-    };
-    let v2 = match eval(rhs, env)? {
-        ExpressionResult::Value(expr) => expr,
-        ExpressionResult::Propagate(expr) => return Ok(ExpressionResult::Propagate(expr)),
-    };
-
+pub fn mul_values(v1: Value, v2: Value) -> Result<Value, String> {
     match (v1, v2) {
-        (Expression::CInt(v1), Expression::CInt(v2)) => {
-            Ok(ExpressionResult::Value(Expression::CInt(v1 * v2)))
-        }
-        (Expression::CInt(v1), Expression::CReal(v2)) => {
-            Ok(ExpressionResult::Value(Expression::CReal(v1 as f64 * v2)))
-        }
-        (Expression::CReal(v1), Expression::CInt(v2)) => {
-            Ok(ExpressionResult::Value(Expression::CReal(v1 * v2 as f64)))
-        }
-        (Expression::CReal(v1), Expression::CReal(v2)) => {
-            Ok(ExpressionResult::Value(Expression::CReal(v1 * v2)))
-        }
-        (Expression::CInt(v1), Expression::CString(v2))
-        | (Expression::CString(v2), Expression::CInt(v1)) => {
+        (Value::Int(v1), Value::Int(v2)) => match v1.checked_mul(v2) {
+            Some(value) => Ok(Value::Int(value)),
+            None => Err(format!("integer overflow computing {} * {}", v1, v2)),
+        },
+        (Value::Int(v1), Value::Real(v2)) => Ok(Value::Real(v1 as f64 * v2)),
+        (Value::Real(v1), Value::Int(v2)) => Ok(Value::Real(v1 * v2 as f64)),
+        (Value::Real(v1), Value::Real(v2)) => Ok(Value::Real(v1 * v2)),
+        (Value::Int(v1), Value::Str(v2)) | (Value::Str(v2), Value::Int(v1)) => {
             if v1 < 0 {
                 return Err("Cannot repeat a string a negative number of times".to_string());
             }
-            Ok(ExpressionResult::Value(Expression::CString(
-                v2.repeat(v1 as usize),
-            )))
+            Ok(Value::Str(Rc::from(v2.repeat(v1 as usize))))
         }
         _ => Err("sum only accepts number + number and string + string".to_string()),
     }
 }
 
-fn eval_div(
-    lhs: Expression,
-    rhs: Expression,
-    env: &mut Environment<Expression>,
-) -> Result<ExpressionResult, String> {
-    eval_binary_arith_op(
-        lhs,
-        rhs,
-        env,
-        |a, b| a / b,
-        "division '(/)' is only defined for numbers (integers and real).",
-    )
+pub fn div_values(v1: Value, v2: Value) -> Result<Value, String> {
+    match (v1, v2) {
+        (Value::Int(_), Value::Int(0)) => Err("division by zero".to_string()),
+        (Value::Int(v1), Value::Int(v2)) => match v1.checked_div(v2) {
+            Some(value) => Ok(Value::Int(value)),
+            None => Err(format!("integer overflow computing {} / {}", v1, v2)),
+        },
+        (v1, v2) => arith_values(
+            v1,
+            v2,
+            |a, b| a / b,
+            "division '(/)' is only defined for numbers (integers and real).",
+        ),
+    }
 }
 
-// Boolean Operations
-fn eval_and(
-    lhs: Expression,
-    rhs: Expression,
-    env: &mut Environment<Expression>,
-) -> Result<ExpressionResult, String> {
-    eval_binary_boolean_op(
-        lhs,
-        rhs,
-        env,
-        |a, b| {
-            if a && b {
-                Expression::CTrue
-            } else {
-                Expression::CFalse
-            }
+pub fn mod_values(v1: Value, v2: Value) -> Result<Value, String> {
+    match (v1, v2) {
+        (Value::Int(_), Value::Int(0)) => Err("modulo by zero".to_string()),
+        (Value::Int(v1), Value::Int(v2)) => Ok(Value::Int(v1.wrapping_rem(v2))),
+        (Value::Int(v1), Value::Real(v2)) => Ok(Value::Real(v1 as f64 % v2)),
+        (Value::Real(v1), Value::Int(v2)) => Ok(Value::Real(v1 % v2 as f64)),
+        (Value::Real(v1), Value::Real(v2)) => Ok(Value::Real(v1 % v2)),
+        _ => Err("modulo '(%)' is only defined for numbers (integers and real).".to_string()),
+    }
+}
+
+pub fn pow_values(v1: Value, v2: Value) -> Result<Value, String> {
+    match (v1, v2) {
+        (Value::Int(_), Value::Int(v2)) if v2 < 0 => {
+            Err("an integer can not be raised to a negative integer power".to_string())
+        }
+        (Value::Int(v1), Value::Int(v2)) => match v1.checked_pow(v2 as u32) {
+            Some(value) => Ok(Value::Int(value)),
+            None => Err(format!("integer overflow computing {} ** {}", v1, v2)),
         },
-        "'and' is only defined for booleans.",
-    )
+        (Value::Int(v1), Value::Real(v2)) => Ok(Value::Real((v1 as f64).powf(v2))),
+        (Value::Real(v1), Value::Int(v2)) => Ok(Value::Real(v1.powi(v2))),
+        (Value::Real(v1), Value::Real(v2)) => Ok(Value::Real(v1.powf(v2))),
+        _ => Err("power '(**)' is only defined for numbers (integers and real).".to_string()),
+    }
 }
 
-fn eval_or(
-    lhs: Expression,
-    rhs: Expression,
-    env: &mut Environment<Expression>,
-) -> Result<ExpressionResult, String> {
-    eval_binary_boolean_op(
-        lhs,
-        rhs,
-        env,
-        |a, b| {
-            if a || b {
-                Expression::CTrue
-            } else {
-                Expression::CFalse
-            }
+pub fn neg_value(v: Value) -> Result<Value, String> {
+    match v {
+        Value::Int(value) => match value.checked_neg() {
+            Some(value) => Ok(Value::Int(value)),
+            None => Err(format!("integer overflow computing -({})", value)),
         },
-        "'or' is only defined for booleans.",
-    )
+        Value::Real(value) => Ok(Value::Real(-value)),
+        _ => Err(String::from("negation '(-)' is only defined for numbers (integers and real).")),
+    }
 }
 
-fn eval_not(
-    lhs: Expression,
-    env: &mut Environment<Expression>,
-) -> Result<ExpressionResult, String> {
-    let v = match eval(lhs, env)? {
-        ExpressionResult::Value(expr) => expr,
-        ExpressionResult::Propagate(expr) => return Ok(ExpressionResult::Propagate(expr)),
-    };
+// Converts an int, or the int in a Just or an Ok, as the type checker wants. A
+// Nothing or an Err has no int to convert
+pub fn to_real_value(v: Value) -> Result<Value, String> {
     match v {
-        Expression::CTrue => Ok(ExpressionResult::Value(Expression::CFalse)),
-        Expression::CFalse => Ok(ExpressionResult::Value(Expression::CTrue)),
+        Value::Int(value) => Ok(Value::Real(value as f64)),
+        Value::Just(value) => just_value(to_real_value((*value).clone())?),
+        Value::Ok(value) => ok_value(to_real_value((*value).clone())?),
+        Value::Nothing | Value::Err(_) => Ok(v),
+        _ => Err(String::from("only an int can be converted to a real.")),
+    }
+}
+
+// Boolean Operations
+pub fn and_values(v1: Value, v2: Value) -> Result<Value, String> {
+    boolean_values(v1, v2, |a, b| a && b, "'and' is only defined for booleans.")
+}
+
+pub fn or_values(v1: Value, v2: Value) -> Result<Value, String> {
+    boolean_values(v1, v2, |a, b| a || b, "'or' is only defined for booleans.")
+}
+
+pub fn not_value(v: Value) -> Result<Value, String> {
+    match v {
+        Value::Bool(value) => Ok(Value::Bool(!value)),
         _ => Err(String::from("'not' is only defined for booleans.")),
     }
 }
 
 // Relational Operations
-fn eval_eq(
-    lhs: Expression,
-    rhs: Expression,
-    env: &mut Environment<Expression>,
-) -> Result<ExpressionResult, String> {
-    eval_binary_rel_op(
-        lhs,
-        rhs,
-        env,
-        |a, b| {
-            if a == b {
-                Expression::CTrue
-            } else {
-                Expression::CFalse
-            }
-        },
+pub fn eq_values(v1: Value, v2: Value) -> Result<Value, String> {
+    relational_values(
+        v1,
+        v2,
+        |a, b| a == b,
         "equality '(==)' is only defined for numbers (integers and real).",
     )
 }
 
-fn eval_neq(
-    lhs: Expression,
-    rhs: Expression,
-    env: &mut Environment<Expression>,
-) -> Result<ExpressionResult, String> {
-    eval_binary_rel_op(
-        lhs,
-        rhs,
-        env,
-        |a, b| {
-            if a != b {
-                Expression::CTrue
-            } else {
-                Expression::CFalse
-            }
-        },
+pub fn neq_values(v1: Value, v2: Value) -> Result<Value, String> {
+    relational_values(
+        v1,
+        v2,
+        |a, b| a != b,
         "inequality '(!=)' is only defined for numbers (integers and real).",
     )
 }
 
-fn eval_gt(
-    lhs: Expression,
-    rhs: Expression,
-    env: &mut Environment<Expression>,
-) -> Result<ExpressionResult, String> {
-    eval_binary_rel_op(
-        lhs,
-        rhs,
-        env,
-        |a, b| {
-            if a > b {
-                Expression::CTrue
-            } else {
-                Expression::CFalse
-            }
-        },
+pub fn gt_values(v1: Value, v2: Value) -> Result<Value, String> {
+    relational_values(
+        v1,
+        v2,
+        |a, b| a > b,
         "greater than '(>)' is only defined for numbers (integers and real).",
     )
 }
 
-fn eval_lt(
-    lhs: Expression,
-    rhs: Expression,
-    env: &mut Environment<Expression>,
-) -> Result<ExpressionResult, String> {
-    eval_binary_rel_op(
-        lhs,
-        rhs,
-        env,
-        |a, b| {
-            if a < b {
-                Expression::CTrue
-            } else {
-                Expression::CFalse
-            }
-        },
+pub fn lt_values(v1: Value, v2: Value) -> Result<Value, String> {
+    relational_values(
+        v1,
+        v2,
+        |a, b| a < b,
         "less than '(<)' is only defined for numbers (integers and real).",
     )
 }
 
-fn eval_gte(
-    lhs: Expression,
-    rhs: Expression,
-    env: &mut Environment<Expression>,
-) -> Result<ExpressionResult, String> {
-    eval_binary_rel_op(
-        lhs,
-        rhs,
-        env,
-        |a, b| {
-            if a >= b {
-                Expression::CTrue
-            } else {
-                Expression::CFalse
-            }
-        },
+pub fn gte_values(v1: Value, v2: Value) -> Result<Value, String> {
+    relational_values(
+        v1,
+        v2,
+        |a, b| a >= b,
         "greater than or equal '(>=)' is only defined for numbers (integers and real).",
     )
 }
 
-fn eval_lte(
-    lhs: Expression,
-    rhs: Expression,
-    env: &mut Environment<Expression>,
-) -> Result<ExpressionResult, String> {
-    eval_binary_rel_op(
-        lhs,
-        rhs,
-        env,
-        |a, b| {
-            if a <= b {
-                Expression::CTrue
-            } else {
-                Expression::CFalse
-            }
-        },
+pub fn lte_values(v1: Value, v2: Value) -> Result<Value, String> {
+    relational_values(
+        v1,
+        v2,
+        |a, b| a <= b,
         "less than or equal '(<=)' is only defined for numbers (integers and real).",
     )
 }
 
 // Variable lookup
-pub fn eval_lookup(
-    name: String,
-    env: &mut Environment<Expression>,
-) -> Result<ExpressionResult, String> {
-    match env.lookup(&name) {
-        Some((_, value)) => Ok(ExpressionResult::Value(value.clone())),
+pub fn eval_lookup(name: &Name, env: &mut Environment<Value>) -> Result<ExpressionResult, String> {
+    match env.lookup(name) {
+        Some((_, value)) => Ok(ExpressionResult::Value(value)),
         None => Err(format!("Variable '{}' not found", name)),
     }
 }
 
 // Function call
 pub fn eval_function_call(
-    func_name: Name,
-    args: Vec<Expression>,
-    env: &mut Environment<Expression>,
+    func_name: &Name,
+    args: &[Expression],
+    resolution: &Option<Resolution>,
+    env: &mut Environment<Value>,
 ) -> Result<ExpressionResult, String> {
     show_counter_exp_eval();
     show_exp_eval(format!("In function eval_function_call:"));
-    show_exp_eval(format!("Env: {:?}", env));
+    // Programs that were not type checked run a builtin unless a function of the
+    // program hides it
+    let builtin = match resolution {
+        Some(Resolution::Builtin) => builtin_call(func_name, args),
+        Some(Resolution::Overload(_)) => None,
+        None => builtin_call(func_name, args)
+            .filter(|_| env.lookup_functions_by_name(func_name).is_empty()),
+    };
+    if let Some(builtin) = builtin {
+        return eval(&builtin, env);
+    }
     let mut actual_arg_values = Vec::new();
-    let mut actual_arg_types = Vec::new();
     for arg in args.iter() {
-        match arg {
-            Expression::Var(name) => match env.lookup_var_or_func(name) {
-                Some(FuncOrVar::Var((_, _var_exp))) => match eval_lookup(name.to_string(), env)? {
-                    ExpressionResult::Propagate(expr) => {
-                        return Ok(ExpressionResult::Propagate(expr));
-                    }
-                    ExpressionResult::Value(expr) => {
-                        actual_arg_values.push(expr);
-                    }
-                },
-                Some(FuncOrVar::Func(func)) => {
-                    actual_arg_values.push(Expression::Lambda(func));
-                }
-                None => return Err(format!("Identifier '{}' was never declared", name)),
-            },
-            Expression::Lambda(func) => {
-                actual_arg_values.push(Expression::Lambda(func.clone()));
+        match eval_argument(arg, env)? {
+            ExpressionResult::Value(value) => {
+                actual_arg_values.push(value);
+            }
+            ExpressionResult::Propagate(value) => {
+                return Ok(ExpressionResult::Propagate(value));
             }
-            _ => match eval(arg.clone(), env)? {
-                ExpressionResult::Value(expr) => {
-                    actual_arg_values.push(expr);
-                }
-                ExpressionResult::Propagate(expr) => {
-                    return Ok(ExpressionResult::Propagate(expr));
-                }
-            },
         }
     }
-    for value in &actual_arg_values {
-        actual_arg_types.push(check_expr(value.clone(), &Environment::<Type>::new())?);
-    }
 
-    let func_signature = FuncSignature {
-        name: func_name.clone(),
-        argument_types: actual_arg_types.clone(),
+    // Calls the type checker resolved dispatch on its overload; the others, such
+    // as those of programs that were not type checked, on the types of the values
+    let func_signature = match resolution {
+        Some(Resolution::Overload(func_signature)) => func_signature.clone(),
+        _ => FuncSignature {
+            name: func_name.clone(),
+            argument_types: actual_arg_values.iter().map(Value::type_of).collect(),
+        },
     };
-    match env.lookup_function(&func_signature).cloned() {
-        Some(func) => {
+    match env
+        .lookup_closure(&func_signature)
+        .map(|(func, link)| (func.clone(), link))
+    {
+        Some((func, link)) => {
             show_counter_exp_eval();
             show_exp_eval(format!("In function eval_function_call:"));
             show_exp_eval(format!(
                 "Function '{}' successfully found in environment",
                 func_signature
             ));
-
-            let mut new_env = Environment::new();
-
-            new_env.set_current_func(&func_signature);
-            // Functions from the outer environment must be propagated to new_env to ensure access to external functions within the function body.
-            // This also allows the function to reference itself, which enables recursion
-            new_env.set_global_functions(env.get_all_functions());
-
-            show_counter_exp_eval();
-            show_exp_eval(format!("In function eval_function_call:"));
-            show_exp_eval(format!("new_env after copying functions: {:?}", new_env));
-
-            for (formal_arg, value) in func.params.iter().zip(actual_arg_values.iter()) {
-                match formal_arg.argument_type {
-                    Type::TFunction(..) => {
-                        match value {
-                            Expression::Lambda(arg_func) => {
-                                let mut inner_func = arg_func.clone();
-                                inner_func.name = formal_arg.argument_name.clone();
-                                new_env.map_function(inner_func);
-                            }
-                            //This will never happen, but I need to cover all cases, otherwise it won't compile
-                            _ => {
-                                return Err(format!(
-                                    "[Runtime Error] Function {:?} expected another function as argument, but received a non functional argument",
-                                    func_signature
-                                ));
-                            }
-                        }
-                    }
-                    _ => {
-                        new_env.create_variable(
-                            formal_arg.argument_name.clone(),
-                            false,
-                            value.clone(),
-                        )?;
-                    }
-                }
-            }
-
-            show_counter_exp_eval();
-            show_exp_eval(format!("In function eval_function_call:"));
-            show_exp_eval(format!("new_env after mapping formal args: {:?}", new_env));
-
-            // Execute the body of the function.
-            match super::statement_execute::execute(*func.body.as_ref().unwrap().clone(), &new_env)
-            {
-                Ok(Computation::Continue(_)) => Err(format!("Function {:?} did not return a value", func_signature)),
-                Ok(Computation::Return(value, mut final_env)) => {
-                    show_counter_exp_eval();
-                    show_exp_eval(format!(
-                        "Env is about to receive output from function {}",
-                        func_signature
-                    ));
-                    show_exp_eval(format!(
-                        "Function {} generated {:?}",
-                        func_signature, final_env
-                    ));
-                    show_exp_eval(format!("output: {:?}", final_env.output));
-                    env.output.append(&mut final_env.output);
-                    show_exp_eval(format!("Env after receiving output: {:?}", env));
-                    Ok(ExpressionResult::Value(value))
-                }
-                Ok(Computation::PropagateError(value, _)) => Ok(ExpressionResult::Propagate(value)),
-                Err(e) => Err(e),
-            }
+            apply_function(&func, link, &func_signature, actual_arg_values, env)
         }
 
         _ => {
@@ -705,126 +566,261 @@ pub fn eval_function_call(
                 "Function '{:?}' not found in environment",
                 func_signature
             ));
-            show_exp_eval(format!("Env: {:?}", env));
-            Err(format!("Function '{:?}' not found", func_signature))
+            Err(format!("Function '{}' not found", func_signature))
         }
     }
 }
 
-// Other helpers
-fn eval_unwrap_expression(
-    exp: Expression,
-    env: &mut Environment<Expression>,
+// Evaluates an argument of a call, which may name a function or be a lambda
+fn eval_argument(
+    arg: &Expression,
+    env: &mut Environment<Value>,
 ) -> Result<ExpressionResult, String> {
-    let v = match eval(exp, env)? {
-        ExpressionResult::Value(expr) => expr,
-        ExpressionResult::Propagate(expr) => return Ok(ExpressionResult::Propagate(expr)),
+    match arg {
+        Expression::Var(name) => match env.lookup_var_or_func(name) {
+            Some(FuncOrVar::Var((_, value))) => Ok(ExpressionResult::Value(value)),
+            Some(FuncOrVar::Func(func, link)) => {
+                Ok(ExpressionResult::Value(Value::Closure(func, link)))
+            }
+            None => Err(format!("Identifier '{}' was never declared", name)),
+        },
+        Expression::Lambda(func) => Ok(ExpressionResult::Value(Value::Closure(
+            Rc::new(func.clone()),
+            env.current_link(),
+        ))),
+        _ => eval(arg, env),
+    }
+}
+
+// Runs the body of a function on the values of its arguments
+fn apply_function(
+    func: &Function,
+    link: Link,
+    func_signature: &FuncSignature,
+    actual_arg_values: Vec<Value>,
+    env: &mut Environment<Value>,
+) -> Result<ExpressionResult, String> {
+    let Some(body) = &func.body else {
+        return Err(format!("Function {} has no body", func_signature));
     };
+    // Endless recursion must fail before it overflows the native stack
+    if env.call_depth() >= MAX_CALL_DEPTH {
+        return Err(format!(
+            "Maximum recursion depth exceeded calling {}",
+            func_signature
+        ));
+    }
+
+    // The body runs in fresh scopes of the same environment, which sees the
+    // scopes enclosing the definition of the function. These declare the
+    // function itself, which enables recursion
+    env.enter_function(func_signature, link);
+    let result = bind_arguments(func, func_signature, actual_arg_values, env)
+        .and_then(|()| super::statement_execute::execute(body, env));
+    env.exit_function();
+
+    match result {
+        // Only a void function can end without `ret`, which the type checker ensures
+        Ok(Computation::Continue) if func.kind == Type::TVoid => {
+            Ok(ExpressionResult::Value(Value::Void))
+        }
+        Ok(Computation::Continue) => Err(format!(
+            "Function {:?} did not return a value",
+            func_signature
+        )),
+        Ok(Computation::Return(value)) => {
+            show_counter_exp_eval();
+            show_exp_eval(format!("Function {} returned {:?}", func_signature, value));
+            Ok(ExpressionResult::Value(value))
+        }
+        // `tryUnwrap` returns the Nothing or Err it found from the function
+        Ok(Computation::PropagateError(value)) => Ok(ExpressionResult::Value(value)),
+        Ok(Computation::Break) | Ok(Computation::ContinueLoop) => Err(format!(
+            "Function {:?} used 'break' or 'continue' outside of a loop",
+            func_signature
+        )),
+        Err(e) => Err(e),
+    }
+}
+
+// Maps the formal arguments of a function to the values it was called with
+fn bind_arguments(
+    func: &Function,
+    func_signature: &FuncSignature,
+    actual_arg_values: Vec<Value>,
+    env: &mut Environment<Value>,
+) -> Result<(), String> {
+    for (formal_arg, value) in func.params.iter().zip(actual_arg_values) {
+        match formal_arg.argument_type {
+            Type::TFunction(..) => {
+                match value {
+                    Value::Closure(arg_func, link) => {
+                        let mut inner_func = (*arg_func).clone();
+                        inner_func.name = formal_arg.argument_name.clone();
+                        env.map_closure(inner_func, link);
+                    }
+                    //This will never happen, but I need to cover all cases, otherwise it won't compile
+                    _ => {
+                        return Err(format!(
+                            "[Runtime Error] Function {:?} expected another function as argument, but received a non functional argument",
+                            func_signature
+                        ));
+                    }
+                }
+            }
+            _ => {
+                env.create_variable(formal_arg.argument_name.clone(), false, value)?;
+            }
+        }
+    }
+
+    show_counter_exp_eval();
+    show_exp_eval(format!("In function bind_arguments:"));
+    Ok(())
+}
+
+// Other helpers
+pub fn unwrap_value(v: Value) -> Result<Value, String> {
     match v {
-        Expression::CJust(e) => Ok(ExpressionResult::Value(*e)),
-        Expression::COk(e) => Ok(ExpressionResult::Value(*e)),
+        Value::Just(e) => Ok(Rc::unwrap_or_clone(e)),
+        Value::Ok(e) => Ok(Rc::unwrap_or_clone(e)),
         _ => Err(String::from("Program panicked trying to unwrap.")),
     }
 }
 
-fn eval_propagate_expression(
-    exp: Expression,
-    env: &mut Environment<Expression>,
-) -> Result<ExpressionResult, String> {
-    let v = match eval(exp, env)? {
-        ExpressionResult::Value(expr) => expr,
-        ExpressionResult::Propagate(expr) => return Ok(ExpressionResult::Propagate(expr)),
-    };
+pub fn propagate_value(v: Value) -> Result<ExpressionResult, String> {
     match v {
-        Expression::CJust(e) => Ok(ExpressionResult::Value(*e)),
-        Expression::COk(e) => Ok(ExpressionResult::Value(*e)),
-        Expression::CErr(e) => Ok(ExpressionResult::Propagate(*e)),
-        Expression::CNothing => Ok(ExpressionResult::Propagate(Expression::CString(
-            "Couldn't unwrap Nothing".to_string(),
-        ))),
+        Value::Just(e) => Ok(ExpressionResult::Value(Rc::unwrap_or_clone(e))),
+        Value::Ok(e) => Ok(ExpressionResult::Value(Rc::unwrap_or_clone(e))),
+        failure @ (Value::Err(_) | Value::Nothing) => Ok(ExpressionResult::Propagate(failure)),
         _ => Err(String::from("'propagate' expects a Just or Ok.")),
     }
 }
 
-fn eval_isnothing_expression(
-    exp: Expression,
-    env: &mut Environment<Expression>,
-) -> Result<ExpressionResult, String> {
-    let v = match eval(exp, env)? {
-        ExpressionResult::Value(expr) => expr,
-        ExpressionResult::Propagate(expr) => return Ok(ExpressionResult::Propagate(expr)),
-    };
+pub fn unwrap_or_values(v: Value, default: Value) -> Result<Value, String> {
     match v {
-        Expression::CNothing => Ok(ExpressionResult::Value(Expression::CTrue)),
-        _ => Ok(ExpressionResult::Value(Expression::CFalse)),
+        Value::Just(e) | Value::Ok(e) => Ok(Rc::unwrap_or_clone(e)),
+        Value::Nothing | Value::Err(_) => Ok(default),
+        _ => Err(String::from("'unwrap_or' expects a Maybe or a Result.")),
     }
 }
-fn eval_iserror_expression(
-    exp: Expression,
-    env: &mut Environment<Expression>,
-) -> Result<ExpressionResult, String> {
-    let v = match eval(exp, env)? {
-        ExpressionResult::Value(expr) => expr,
-        ExpressionResult::Propagate(expr) => return Ok(ExpressionResult::Propagate(expr)),
-    };
+
+pub fn ok_or_values(v: Value, error: Value) -> Result<Value, String> {
     match v {
-        Expression::CErr(_) => Ok(ExpressionResult::Value(Expression::CTrue)),
-        _ => Ok(ExpressionResult::Value(Expression::CFalse)),
+        Value::Just(e) => Ok(Value::Ok(e)),
+        Value::Nothing => Ok(Value::Err(Rc::new(error))),
+        _ => Err(String::from("'ok_or' expects a Maybe.")),
     }
 }
-fn eval_just(
-    exp: Expression,
-    env: &mut Environment<Expression>,
-) -> Result<ExpressionResult, String> {
-    let v = match eval(exp, env)? {
-        ExpressionResult::Value(expr) => expr,
-        ExpressionResult::Propagate(expr) => return Ok(ExpressionResult::Propagate(expr)),
-    };
-    Ok(ExpressionResult::Value(Expression::CJust(Box::new(v))))
+
+pub fn is_nothing_value(v: Value) -> Result<Value, String> {
+    Ok(Value::Bool(v == Value::Nothing))
 }
-fn eval_ok(exp: Expression, env: &mut Environment<Expression>) -> Result<ExpressionResult, String> {
-    let v = match eval(exp, env)? {
-        ExpressionResult::Value(expr) => expr,
-        ExpressionResult::Propagate(expr) => return Ok(ExpressionResult::Propagate(expr)),
-    };
-    Ok(ExpressionResult::Value(Expression::COk(Box::new(v))))
+
+pub fn is_error_value(v: Value) -> Result<Value, String> {
+    let is_error = matches!(v, Value::Err(_));
+    Ok(Value::Bool(is_error))
+}
+
+pub fn just_value(v: Value) -> Result<Value, String> {
+    Ok(Value::Just(Rc::new(v)))
+}
+
+pub fn ok_value(v: Value) -> Result<Value, String> {
+    Ok(Value::Ok(Rc::new(v)))
 }
-fn eval_err(
-    exp: Expression,
-    env: &mut Environment<Expression>,
+
+pub fn err_value(v: Value) -> Result<Value, String> {
+    Ok(Value::Err(Rc::new(v)))
+}
+
+pub fn range_values(start: Value, end: Value) -> Result<Value, String> {
+    match (start, end) {
+        (Value::Int(start), Value::Int(end)) => Ok(Value::Range(start, end)),
+        _ => Err(String::from("range expects two integer bounds")),
+    }
+}
+
+fn eval_propagate_expression(
+    exp: &Expression,
+    env: &mut Environment<Value>,
 ) -> Result<ExpressionResult, String> {
-    let v = match eval(exp, env)? {
-        ExpressionResult::Value(expr) => expr,
-        ExpressionResult::Propagate(expr) => return Ok(ExpressionResult::Propagate(expr)),
-    };
-    Ok(ExpressionResult::Value(Expression::CErr(Box::new(v))))
+    match eval(exp, env)? {
+        ExpressionResult::Value(value) => propagate_value(value),
+        ExpressionResult::Propagate(value) => Ok(ExpressionResult::Propagate(value)),
+    }
 }
 
-fn is_constant(exp: Expression) -> bool {
-    match exp {
-        Expression::CTrue => true,
-        Expression::CFalse => true,
-        Expression::CVoid => true,
-        Expression::CInt(_) => true,
-        Expression::CReal(_) => true,
-        Expression::CString(_) => true,
-        Expression::CNothing => true,
-        _ => false,
+// Calls the function on the value of a Just or an Ok, which `map` wraps back
+// and `and_then` does not, since the function already gives a Maybe or a Result
+fn eval_combinator(
+    exp: &Expression,
+    func: &Expression,
+    flatten: bool,
+    env: &mut Environment<Value>,
+) -> Result<ExpressionResult, String> {
+    let value = match eval(exp, env)? {
+        ExpressionResult::Value(value) => value,
+        ExpressionResult::Propagate(value) => return Ok(ExpressionResult::Propagate(value)),
+    };
+    let (func, link) = match eval_argument(func, env)? {
+        ExpressionResult::Value(Value::Closure(func, link)) => (func, link),
+        ExpressionResult::Value(value) => {
+            return Err(format!("Expected a function, but found {}", value));
+        }
+        ExpressionResult::Propagate(value) => return Ok(ExpressionResult::Propagate(value)),
+    };
+    let inner = match &value {
+        Value::Just(e) | Value::Ok(e) => e.as_ref().clone(),
+        Value::Nothing | Value::Err(_) => return Ok(ExpressionResult::Value(value)),
+        _ => return Err(String::from("Expected a Maybe or a Result.")),
+    };
+    let func_signature = FuncSignature::from_func(&func);
+    match apply_function(&func, link, &func_signature, vec![inner], env)? {
+        ExpressionResult::Value(result) if !flatten => match value {
+            Value::Ok(_) => ok_value(result),
+            _ => just_value(result),
+        }
+        .map(ExpressionResult::Value),
+        result => Ok(result),
     }
 }
 
 fn eval_list_value(
-    sub_expressions: Vec<Expression>,
-    env: &mut Environment<Expression>,
+    sub_expressions: &[Expression],
+    env: &mut Environment<Value>,
 ) -> Result<ExpressionResult, String> {
-    let mut values = Vec::new();
+    let mut values = Vec::with_capacity(sub_expressions.len());
     for exp in sub_expressions {
         match eval(exp, env)? {
-            ExpressionResult::Value(expr) => values.push(expr),
-            ExpressionResult::Propagate(expr) => return Ok(ExpressionResult::Propagate(expr)),
+            ExpressionResult::Value(value) => values.push(value),
+            ExpressionResult::Propagate(value) => return Ok(ExpressionResult::Propagate(value)),
+        }
+    }
+    Ok(ExpressionResult::Value(Value::List(Rc::new(values))))
+}
+
+fn eval_constructor(
+    name: &Name,
+    args: &[Box<Expression>],
+    env: &mut Environment<Value>,
+) -> Result<ExpressionResult, String> {
+    let Some((type_name, constructors)) = env.lookup_constructor(name) else {
+        return Err(format!("Constructor '{}' was never declared", name));
+    };
+    let adt_type = Rc::new(Type::TAlgebraicData(type_name, constructors.clone()));
+    let mut values = Vec::with_capacity(args.len());
+    for arg in args {
+        match eval(arg, env)? {
+            ExpressionResult::Value(value) => values.push(value),
+            ExpressionResult::Propagate(value) => return Ok(ExpressionResult::Propagate(value)),
         }
     }
-    Ok(ExpressionResult::Value(Expression::ListValue(values)))
+    Ok(ExpressionResult::Value(Value::Adt(
+        adt_type,
+        name.clone(),
+        Rc::new(values),
+    )))
 }
 
 fn show_exp_eval(texto: String) {
@@ -1,5 +1,406 @@
 pub mod expression_eval;
 pub mod statement_execute;
+pub mod value;
 
 pub use expression_eval::eval;
 pub use statement_execute::{execute, run};
+pub use value::Value;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::environment::environment::Environment;
+    use crate::ir::ast::{Expression, Statement, Type};
+    use crate::test_utils::{checked_program, lines};
+    use crate::type_checker::check_stmt;
+
+    // Type checks and runs the source, printing the given variables at the end
+    fn check_and_run(source: &str, printed: &[&str]) -> Result<Vec<String>, String> {
+        let program = checked_program(source, printed)?;
+        let mut env: Environment<Value> = Environment::new();
+        run(&program, &mut env).map(|()| env.output)
+    }
+
+    #[test]
+    fn test_nested_functions_capture_outer_variables() {
+        let source = "def outer(n: int) -> int: \
+                      def add(x: int) -> int: ret x + n; end; \
+                      if n > 5: \
+                      def twice(x: int) -> int: ret add(add(x)); end; \
+                      ret twice(0); \
+                      end; \
+                      ret add(1); end; \
+                      var r = outer(10); var s = outer(2);";
+        assert_eq!(check_and_run(source, &["r", "s"]), lines(&["20", "3"]));
+        // Including the variables the enclosing function declares before them
+        let source = "def outer() -> int: \
+                      var a = 5; def f() -> int: ret a; end; \
+                      a = a + 1; ret f(); end; \
+                      var r = outer();";
+        assert_eq!(check_and_run(source, &["r"]), lines(&["6"]));
+    }
+
+    #[test]
+    fn test_nested_functions_are_only_visible_in_their_block() {
+        let source = "def outer(n: int) -> int: \
+                      def add(x: int) -> int: ret x + n; end; ret add(1); end; \
+                      var r = add(1);";
+        assert_eq!(
+            check_and_run(source, &[]),
+            Err("Function add(int) was called but never declared".to_string())
+        );
+    }
+
+    #[test]
+    fn test_functions_see_the_functions_where_they_are_defined() {
+        let source = "def g() -> int: ret 1; end; \
+                      def f() -> int: ret g(); end; \
+                      def h() -> int: def g() -> int: ret 2; end; ret f() * 10 + g(); end; \
+                      var r = h();";
+        assert_eq!(check_and_run(source, &["r"]), lines(&["12"]));
+    }
+
+    #[test]
+    fn test_functions_passed_as_arguments_keep_their_scopes() {
+        let source = "def apply(f: (int) -> int, x: int) -> int: ret f(x); end; \
+                      def outer(n: int) -> int: \
+                      def add(x: int) -> int: ret x + n; end; ret apply(add, 1); end; \
+                      var r = outer(10);";
+        assert_eq!(check_and_run(source, &["r"]), lines(&["11"]));
+    }
+
+    #[test]
+    fn test_captured_variables_cannot_be_assigned() {
+        let source = "def outer(n: int) -> int: var total = 0; \
+                      if n > 0: def add() -> int: total = total + n; ret total; end; \
+                      var x = add(); end; ret total; end;";
+        assert_eq!(
+            check_and_run(source, &[]),
+            Err(
+                "[Type Error] cannot reassign '\"total\"' variable, since it was declared as a constant value."
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_constants_are_not_assigned_after_type_checking() {
+        let error = |source: &str| check_and_run(source, &[]).unwrap_err();
+        assert_eq!(
+            error("val limit = 3; if limit > 2: while True: limit = 1; end; end;"),
+            "[Type Error] cannot assign to val 'limit', declared as 'val limit = 3' in the program."
+        );
+        assert_eq!(
+            error("def f(n: int) -> int: n = n + 1; ret n; end;"),
+            "[Type Error] cannot assign to parameter 'n' of function 'f(int)'."
+        );
+        assert_eq!(
+            error("for x in [1, 2]: x = 3; end;"),
+            "[Type Error] cannot assign to loop variable 'x' of 'for x in [1, 2]' in the program."
+        );
+    }
+
+    #[test]
+    fn test_variables_are_local_to_the_block_declaring_them() {
+        let error = |source: &str| check_and_run(source, &[]).unwrap_err();
+        assert_eq!(
+            error("var n = 1; if n > 0: val x = 1; end else: val x = 2; end; val y = x;"),
+            "[Name Error] 'x' is not defined here, since it was declared in a block that has ended."
+        );
+        assert_eq!(
+            error("var i = 0; while i < 3: var last = i; i = i + 1; end; last = 0;"),
+            "[Type Error] variable 'last' cannot be assigned here, since it was declared in a block that has ended."
+        );
+        assert_eq!(
+            error("for i in [1]: end; var q = i;"),
+            "[Name Error] 'i' is not defined here, since it was declared in a block that has ended."
+        );
+        // A loop variable shadows an outer variable only until the loop ends
+        let source = "var i = \"outer\"; var n = 0; for i in [1, 2]: n = n + i; end; i = i + \"!\";";
+        assert_eq!(check_and_run(source, &["n", "i"]), lines(&["3", "outer!"]));
+        // Once the block ends, its names can be declared again
+        let source = "var total = 0; \
+                      for i in [1, 2, 3]: val double = i * 2; total = total + double; end; \
+                      val double = total;";
+        assert_eq!(check_and_run(source, &["double"]), lines(&["12"]));
+    }
+
+    #[test]
+    fn test_bodies_that_are_not_blocks_have_their_own_scope() {
+        // The body of the `if` declares `x` without being a Statement::Block
+        let program = Statement::Block(vec![
+            Statement::IfThenElse(
+                Box::new(Expression::CTrue),
+                Box::new(Statement::VarDeclaration(
+                    "x".to_string(),
+                    Box::new(Expression::CInt(1)),
+                )),
+                None,
+            ),
+            Statement::Print(Box::new(Expression::Var("x".to_string()))),
+        ]);
+        assert_eq!(
+            check_stmt(&mut program.clone(), &Environment::<Type>::new()).unwrap_err(),
+            "[Name Error] 'x' is not defined here, since it was declared in a block that has ended."
+        );
+        let mut env: Environment<Value> = Environment::new();
+        assert!(run(&program, &mut env).is_err());
+    }
+
+    #[test]
+    fn test_try_unwrap_returns_the_failure_from_its_function() {
+        let source = "def half(n: int) -> Maybe[int]: \
+                      if n % 2 == 0: ret Just(n / 2); end; ret Nothing; end; \
+                      def quarter(n: int) -> Maybe[int]: val h = tryUnwrap(half(n)); ret half(h); end; \
+                      def digit(n: int) -> Result[int, string]: \
+                      if n < 10: ret Ok(n); end; ret Err(\"too big\"); end; \
+                      def sum(a: int, b: int) -> Result[int, string]: \
+                      ret Ok(tryUnwrap(digit(a)) + tryUnwrap(digit(b))); end; \
+                      val a = unwrap_or(quarter(12), -1); val b = unwrap_or(quarter(6), -1); \
+                      val c = unwrap_or(sum(2, 3), -1); val d = isError(sum(2, 30));";
+        assert_eq!(
+            check_and_run(source, &["a", "b", "c", "d"]),
+            lines(&["3", "-1", "5", "True"])
+        );
+    }
+
+    #[test]
+    fn test_try_unwrap_is_checked_against_the_return_type() {
+        let error = |source: &str| check_and_run(source, &[]).unwrap_err();
+        assert_eq!(
+            error("val x = tryUnwrap(Just(1));"),
+            "[Type Error] 'tryUnwrap' can only be used inside a function."
+        );
+        assert_eq!(
+            error("def f(m: Maybe[int]) -> int: ret tryUnwrap(m); end;"),
+            "[Type Error] 'tryUnwrap' cannot return the failure of a value of type Maybe<int> from function 'f(Maybe<int>)', which returns int."
+        );
+        assert_eq!(
+            error("def f(r: Result[int, int]) -> Result[int, string]: ret Ok(tryUnwrap(r)); end;"),
+            "[Type Error] 'tryUnwrap' cannot return the failure of a value of type Result<int, int> from function 'f(Result<int, int>)', which returns Result<int, string>."
+        );
+    }
+
+    #[test]
+    fn test_maybe_and_result_combinators() {
+        let source = "def double(n: int) -> int: ret n * 2; end; \
+                      def half(n: int) -> Maybe[int]: \
+                      if n % 2 == 0: ret Just(n / 2); end; ret Nothing; end; \
+                      var m = Just(6); \
+                      val a = unwrap_or(map(m, double), 0); \
+                      val b = unwrap_or(and_then(m, half), 0); \
+                      val c = unwrap_or(and_then(and_then(m, half), half), 0); \
+                      val d = unwrap_or(map(ok_or(half(3), \"odd\"), double), 0); \
+                      val e = isError(ok_or(half(3), \"odd\")); \
+                      val f = unwrap_or(map(Ok(1), lambda (n: int) -> float: ret n + 0.5 end), 0.0);";
+        assert_eq!(
+            check_and_run(source, &["a", "b", "c", "d", "e", "f"]),
+            lines(&["12", "3", "0", "0", "True", "1.5"])
+        );
+        let error = |source: &str| check_and_run(source, &[]).unwrap_err();
+        assert_eq!(
+            error("def f(s: string) -> int: ret 1; end; val x = map(Just(1), f);"),
+            "[Type Error] 'map' expects a function taking a value of type int, but found fn(string) -> int."
+        );
+        assert_eq!(
+            error("val x = unwrap_or(Just(1), \"none\");"),
+            "[Type Error] the default value of 'unwrap_or' must be of type int, but it is of type string."
+        );
+    }
+
+    #[test]
+    fn test_ints_given_for_reals_are_converted() {
+        let source = "def half(x: float) -> float: ret x / 2; end; \
+                      quarter = lambda (x: float) -> float: ret half(x) / 2 end; \
+                      val a = half(3); val b = quarter(1); \
+                      val c = unwrap_or(map(Just(5), half), 0.0); \
+                      val d = unwrap_or(and_then(Ok(3), lambda (x: float) -> Result[float, string]: ret Ok(x / 2) end), 0.0); \
+                      def none(n: int) -> Maybe[int]: ret Nothing; end; \
+                      def bad(n: int) -> Result[int, string]: ret Err(\"bad\"); end; \
+                      val e = unwrap_or(map(none(1), half), 0.5); \
+                      val f = isError(map(bad(1), half));";
+        assert_eq!(
+            check_and_run(source, &["a", "b", "c", "d", "e", "f"]),
+            lines(&["1.5", "0.25", "2.5", "1.5", "0.5", "True"])
+        );
+    }
+
+    #[test]
+    fn test_overloaded_functions_given_by_name_resolve_to_the_overload_taken() {
+        let source = "def apply(h: (int) -> int, x: int) -> int: ret h(x); end; \
+                      def f(x: int) -> int: ret x + 1; end; \
+                      def f(x: float) -> float: ret x / 2; end; \
+                      val r = apply(f, 1); \
+                      val s = unwrap_or(map(Just(3), f), 0); \
+                      val t = unwrap_or(map(Just(3.0), f), 0.0);";
+        // The overloads are looked up in a hash map, so run it a few times
+        for _ in 0..8 {
+            assert_eq!(
+                check_and_run(source, &["r", "s", "t"]),
+                lines(&["2", "4", "1.5"])
+            );
+        }
+    }
+
+    #[test]
+    fn test_builtin_names_are_not_reserved() {
+        let source = "val map = 3; val unwrap_or = map + 1; \
+                      def ok_or(n: int) -> int: ret n * 10; end; \
+                      val a = ok_or(unwrap_or); \
+                      var total = 0; for i in range(0, map): total = total + i; end;";
+        assert_eq!(
+            check_and_run(source, &["a", "total"]),
+            lines(&["40", "3"])
+        );
+        // A function of the program hides the builtin of the same name
+        let source = "def unwrap_or(a: int, b: int) -> int: ret a - b; end; val r = unwrap_or(5, 2);";
+        assert_eq!(check_and_run(source, &["r"]), lines(&["3"]));
+        let source = "def range(n: int) -> int: ret n * 2; end; val r = range(4);";
+        assert_eq!(check_and_run(source, &["r"]), lines(&["8"]));
+    }
+
+    #[test]
+    fn test_runtime_errors_are_caught() {
+        let source = "def ratio(n: int) -> int: ret 10 / n; end; \
+                      var count = 0; \
+                      var caught = \"none\"; \
+                      for i in [2, 0, 5]: \
+                      try: count = count + ratio(i); end \
+                      catch e: caught = e; count = count + 100; end; \
+                      end; \
+                      var message = \"none\"; \
+                      try: val r = unwrap(Nothing); message = \"unwrapped\"; end \
+                      catch e: message = e; end;";
+        assert_eq!(
+            check_and_run(source, &["count", "caught", "message"]),
+            lines(&["107", "division by zero", "Program panicked trying to unwrap."])
+        );
+        let source = "var low = 0 - 2147483647; var caught = \"none\"; \
+                      try: low = low - 2; end catch e: caught = e; end;";
+        assert_eq!(
+            check_and_run(source, &["low", "caught"]),
+            lines(&["-2147483647", "integer overflow computing -2147483647 - 2"])
+        );
+        // Failures of the handler are not caught
+        let source = "var x = 0; try: x = 1 % x; end catch e: x = 2 % x; end;";
+        assert_eq!(
+            check_and_run(source, &[]),
+            Err("modulo by zero".to_string())
+        );
+    }
+
+    #[test]
+    fn test_caught_errors_are_constants_of_the_handler() {
+        let error = |source: &str| check_and_run(source, &[]).unwrap_err();
+        assert_eq!(
+            error("try: val x = 1; end catch e: e = \"other\"; end;"),
+            "[Type Error] cannot assign to error 'e' of 'catch e' in the program."
+        );
+        assert_eq!(
+            error("try: val x = 1; end catch e: val y = 2; end; val z = e;"),
+            "[Name Error] 'e' is not defined here, since it was declared in a block that has ended."
+        );
+    }
+
+    #[test]
+    fn test_functions_only_see_the_variables_declared_before_they_run() {
+        // The function may be called before the variable is declared
+        let source = "def g() -> int: \
+                      var r = f(); var k = 1; def f() -> int: ret k; end; \
+                      ret r; end;";
+        assert_eq!(
+            check_and_run(source, &[]),
+            Err("[Name Error] 'k' is not defined here, since function 'f()' may run before 'k' is declared.".to_string())
+        );
+        // Variables declared after the definition are not visible either
+        let source = "val k = \"global\"; \
+                      def g() -> string: def f() -> string: ret k; end; val k = \"local\"; ret f(); end; \
+                      val r = g();";
+        assert_eq!(check_and_run(source, &["r"]), lines(&["global"]));
+    }
+
+    #[test]
+    fn test_functions_read_and_declare_global_variables() {
+        let source = "var count = 0; val step = 5; \
+                      def inc() -> int: global count; count = count + step; ret count; end; \
+                      def twice() -> int: val first = inc(); ret inc(); end; \
+                      val a = inc(); val b = twice();";
+        assert_eq!(
+            check_and_run(source, &["count", "a", "b"]),
+            lines(&["15", "5", "15"])
+        );
+    }
+
+    #[test]
+    fn test_chained_comparisons_evaluate_each_operand_once() {
+        let source = "var calls = 0; \
+                      def middle() -> int: global calls; calls = calls + 1; ret 5; end; \
+                      val inside = 1 < middle() <= 5; val outside = 1 < middle() < 2 == 1;";
+        assert_eq!(
+            check_and_run(source, &["inside", "outside", "calls"]),
+            lines(&["True", "False", "2"])
+        );
+    }
+
+    #[test]
+    fn test_functions_called_before_their_definition_only_read_earlier_globals() {
+        let source = "var r = f(); val step = 2; def f() -> int: ret step; end;";
+        assert_eq!(
+            check_and_run(source, &[]),
+            Err("[Name Error] 'step' is not defined here, since function 'f()' may run before 'step' is declared.".to_string())
+        );
+        // So do those called early through another function
+        let source = "def g() -> int: ret f(); end; var r = g(); \
+                      val step = 2; def f() -> int: ret step; end;";
+        assert!(check_and_run(source, &[]).is_err());
+        // A function that only runs after its definition reads the globals declared before it
+        let source = "val step = 2; def f() -> int: ret step; end; val later = 3; var r = f();";
+        assert_eq!(check_and_run(source, &["r"]), lines(&["2"]));
+        let source = "val base = 1; def f() -> int: ret later; end; val later = 3;";
+        assert_eq!(
+            check_and_run(source, &[]),
+            Err("[Name Error] 'later' is not defined.".to_string())
+        );
+    }
+
+    #[test]
+    fn test_function_variables_hide_global_variables() {
+        let source = "var a = 1; val b = 2; \
+                      def f() -> int: var a = 10; val b = 20; ret a + b; end; \
+                      val r = f();";
+        assert_eq!(check_and_run(source, &["r", "a", "b"]), lines(&["30", "1", "2"]));
+        // Unlike the variables of the function itself
+        let source = "def f(n: int) -> int: var a = 1; if n > 0: var a = 2; end; ret n; end;";
+        assert_eq!(
+            check_and_run(source, &[]),
+            Err("[Type Error] variable 'a' already declared".to_string())
+        );
+    }
+
+    #[test]
+    fn test_global_variables_are_only_assigned_when_declared_global() {
+        let source = "var count = 0; def inc() -> int: count = count + 1; ret count; end;";
+        assert_eq!(
+            check_and_run(source, &[]),
+            Err("[Type Error] cannot assign global variable 'count' in function 'inc()' without declaring it with 'global count'.".to_string())
+        );
+
+        let outside = "var count = 0; global count;";
+        assert_eq!(
+            check_and_run(outside, &[]),
+            Err("[Type Error] 'global count' can only be used inside a function.".to_string())
+        );
+
+        let constant = "val count = 0; def inc() -> int: global count; ret 1; end;";
+        assert_eq!(
+            check_and_run(constant, &[]),
+            Err("[Type Error] cannot declare 'count' global, since it was declared as a constant value.".to_string())
+        );
+
+        let local = "var count = 0; def inc(count: int) -> int: global count; ret 1; end;";
+        assert_eq!(
+            check_and_run(local, &[]),
+            Err("[Name Error] 'count' is not a top-level variable of the program.".to_string())
+        );
+    }
+}
@@ -1,99 +1,80 @@
 use std::fmt::format;
+use std::rc::Rc;
 
 use super::expression_eval::{ExpressionResult, eval};
-use crate::environment::environment::Environment;
+use super::value::Value;
+use crate::environment::environment::{Environment, run_before_definition};
 use crate::interpreter::expression_eval::eval_function_call;
-use crate::ir::ast::{Expression, FuncSignature, Statement};
+use crate::ir::ast::{Expression, Name, Statement};
 use crate::{show, show_counter};
 
+// How the execution of a statement ended. The environment is updated in place
 pub enum Computation {
-    Continue(Environment<Expression>),
-    Return(Expression, Environment<Expression>),
-    PropagateError(Expression, Environment<Expression>),
+    Continue,
+    Return(Value),
+    PropagateError(Value),
+    // A `break` or `continue` leaving the statements up to the innermost loop
+    Break,
+    ContinueLoop,
 }
 
-pub fn _execute_with_env_(
-    stmt: Statement,
-    env: &mut Environment<Expression>,
-) -> Result<Environment<Expression>, String> {
-    match execute(stmt, &env.clone()) {
-        Ok(Computation::Continue(new_env)) => Ok(new_env),
-        Ok(Computation::Return(_, new_env)) => Ok(new_env), // For backward compatibility
-        Ok(Computation::PropagateError(_, new_env)) => Ok(new_env), // For backward compatibility
-        Err(e) => Err(e),
-    }
+pub fn run(stmt: &Statement, env: &mut Environment<Value>) -> Result<(), String> {
+    execute(stmt, env).map(|_| ())
 }
 
-pub fn run(
-    stmt: Statement,
-    env: &Environment<Expression>,
-) -> Result<Environment<Expression>, String> {
-    match execute(stmt, env) {
-        Ok(Computation::Continue(new_env)) => Ok(new_env),
-        Ok(Computation::Return(_, new_env)) => Ok(new_env),
-        Ok(Computation::PropagateError(_, new_env)) => Ok(new_env),
-        Err(e) => Err(e),
-    }
-}
-
-pub fn execute(stmt: Statement, env: &Environment<Expression>) -> Result<Computation, String> {
-    let mut new_env = env.clone();
-
-    match stmt.clone() {
+pub fn execute(stmt: &Statement, env: &mut Environment<Value>) -> Result<Computation, String> {
+    match stmt {
         Statement::VarDeclaration(name, exp) => {
             show_counter_statement_exec();
             show_statement_exec(format!("Exec VarDeclaration:"));
             show_statement_exec(format!("Statement: {:?}", stmt));
-            show_statement_exec(format!("Env: {:?}", new_env));
-            let value = match eval(*exp, &mut new_env)? {
-                ExpressionResult::Value(expr) => expr,
-                ExpressionResult::Propagate(expr) => {
-                    return Ok(Computation::PropagateError(expr, new_env));
+            let value = match eval(exp, env)? {
+                ExpressionResult::Value(value) => value,
+                ExpressionResult::Propagate(value) => {
+                    return Ok(Computation::PropagateError(value));
                 }
             };
-            new_env.create_variable(name, true, value)?;
-            Ok(Computation::Continue(new_env))
+            env.create_variable(name.clone(), true, value)?;
+            Ok(Computation::Continue)
         }
 
         Statement::ValDeclaration(name, exp) => {
             show_counter_statement_exec();
             show_statement_exec(format!("Exec ValDeclaration:"));
             show_statement_exec(format!("Statement: {:?}", stmt));
-            show_statement_exec(format!("Env: {:?}", new_env));
-            let value = match eval(*exp, &mut new_env)? {
-                ExpressionResult::Value(expr) => expr,
-                ExpressionResult::Propagate(expr) => {
-                    return Ok(Computation::PropagateError(expr, new_env));
+            let value = match eval(exp, env)? {
+                ExpressionResult::Value(value) => value,
+                ExpressionResult::Propagate(value) => {
+                    return Ok(Computation::PropagateError(value));
                 }
             };
-            new_env.create_variable(name, false, value)?;
-            Ok(Computation::Continue(new_env))
+            env.create_variable(name.clone(), false, value)?;
+            Ok(Computation::Continue)
         }
 
         Statement::Assignment(name, exp) => {
             show_counter_statement_exec();
             show_statement_exec(format!("Exec Assignement:"));
             show_statement_exec(format!("Statement: {:?}", stmt));
-            show_statement_exec(format!("Env: {:?}", new_env));
-            match *exp {
-                Expression::Lambda(mut func) => {
-                    func.name = name;
-                    new_env.map_function(func);
-                    return Ok(Computation::Continue(new_env));
+            match exp.as_ref() {
+                Expression::Lambda(func) => {
+                    let mut func = func.clone();
+                    func.name = name.clone();
+                    env.map_function(func);
+                    return Ok(Computation::Continue);
                 }
                 _ => {
-                    let value = match eval(*exp, &mut new_env)? {
-                        ExpressionResult::Value(expr) => expr,
+                    let value = match eval(exp, env)? {
+                        ExpressionResult::Value(value) => value,
                         ExpressionResult::Propagate(expr) => {
-                            return Ok(Computation::PropagateError(expr, new_env));
+                            return Ok(Computation::PropagateError(expr));
                         }
                     };
                     show_counter_statement_exec();
                     show_statement_exec(format!("Finishing Exec Assignement:"));
                     show_statement_exec(format!("Statement: {:?}", stmt));
-                    show_statement_exec(format!("Env: {:?}", new_env));
-                    new_env.change_variable_value(name, value)?;
-                    return Ok(Computation::Continue(new_env));
+                    env.change_variable_value(name.clone(), value)?;
+                    return Ok(Computation::Continue);
                 }
             }
         }
@@ -102,74 +83,83 @@ pub fn execute(stmt: Statement, env: &Environment<Expression>) -> Result<Computa
             show_counter_statement_exec();
             show_statement_exec(format!("Exec IfThenElse:"));
             show_statement_exec(format!("Statement: {:?}", stmt));
-            show_statement_exec(format!("Env: {:?}", new_env));
-            let value = match eval(*cond, &mut new_env)? {
-                ExpressionResult::Value(expr) => expr,
-                ExpressionResult::Propagate(expr) => {
-                    return Ok(Computation::PropagateError(expr, new_env));
+            let value = match eval(cond, env)? {
+                ExpressionResult::Value(value) => value,
+                ExpressionResult::Propagate(value) => {
+                    return Ok(Computation::PropagateError(value));
                 }
             };
 
             match value {
-                Expression::CTrue => match *stmt_then {
-                    Statement::Block(stmts) => execute_block(stmts, &mut new_env),
-                    _ => execute(*stmt_then, &mut new_env),
-                },
-                Expression::CFalse => match stmt_else {
-                    Some(else_stmt) => match *else_stmt {
-                        Statement::Block(stmts) => execute_block(stmts, &mut new_env),
-                        _ => execute(*else_stmt, &mut new_env),
-                    },
-                    None => Ok(Computation::Continue(new_env)),
+                Value::Bool(true) => execute_body(stmt_then, env),
+                Value::Bool(false) => match stmt_else.as_deref() {
+                    Some(else_stmt) => execute_body(else_stmt, env),
+                    None => Ok(Computation::Continue),
                 },
                 _ => Err("Condition must evaluate to a boolean".to_string()),
             }
         }
 
+        Statement::When(exp, cases, default) => {
+            show_counter_statement_exec();
+            show_statement_exec(format!("Exec When:"));
+            show_statement_exec(format!("Statement: {:?}", stmt));
+            let value = match eval(exp, env)? {
+                ExpressionResult::Value(value) => value,
+                ExpressionResult::Propagate(value) => {
+                    return Ok(Computation::PropagateError(value));
+                }
+            };
+
+            let mut body = default.as_deref();
+            for (case_value, case_body) in cases {
+                let case_value = match eval(case_value, env)? {
+                    ExpressionResult::Value(value) => value,
+                    ExpressionResult::Propagate(value) => {
+                        return Ok(Computation::PropagateError(value));
+                    }
+                };
+                if case_value == value {
+                    body = Some(case_body);
+                    break;
+                }
+            }
+
+            match body {
+                Some(body) => execute_body(body, env),
+                None => Ok(Computation::Continue),
+            }
+        }
+
         Statement::Block(stmts) => {
             show_counter_statement_exec();
             show_statement_exec(format!("Exec Block:"));
             show_statement_exec(format!("Statement: {:?}", stmt));
-            show_statement_exec(format!("Env: {:?}", new_env));
-            //new_env.push();
-            let result = execute_block(stmts, &mut new_env);
-            //new_env.pop();
-            result
+            execute_block(stmts, env)
         }
 
         Statement::While(cond, stmt) => {
             show_counter_statement_exec();
             show_statement_exec(format!("Exec While:"));
             show_statement_exec(format!("Statement: {:?}", stmt));
-            show_statement_exec(format!("Env: {:?}", new_env));
-            let mut value = match eval(*cond.clone(), &mut new_env)? {
-                ExpressionResult::Value(expr) => expr,
-                ExpressionResult::Propagate(expr) => {
-                    return Ok(Computation::PropagateError(expr, new_env));
-                }
-            };
-
             loop {
+                let value = match eval(cond, env)? {
+                    ExpressionResult::Value(value) => value,
+                    ExpressionResult::Propagate(value) => {
+                        return Ok(Computation::PropagateError(value));
+                    }
+                };
                 match value {
-                    Expression::CTrue => {
-                        match execute(*stmt.clone(), &mut new_env)? {
-                            Computation::Continue(env) => new_env = env,
-                            Computation::Return(expr, env) => {
-                                return Ok(Computation::Return(expr, env));
-                            }
-                            Computation::PropagateError(expr, env) => {
-                                return Ok(Computation::PropagateError(expr, env));
-                            }
+                    Value::Bool(true) => match execute_body(stmt, env)? {
+                        Computation::Continue | Computation::ContinueLoop => {}
+                        Computation::Break => return Ok(Computation::Continue),
+                        Computation::Return(expr) => return Ok(Computation::Return(expr)),
+                        Computation::PropagateError(expr) => {
+                            return Ok(Computation::PropagateError(expr));
                         }
-                        value = match eval(*cond.clone(), &mut new_env)? {
-                            ExpressionResult::Value(expr) => expr,
-                            ExpressionResult::Propagate(expr) => {
-                                return Ok(Computation::PropagateError(expr, new_env));
-                            }
-                        };
-                    }
-                    Expression::CFalse => return Ok(Computation::Continue(new_env)),
-                    _ => unreachable!(),
+                    },
+                    Value::Bool(false) => return Ok(Computation::Continue),
+                    _ => return Err("Condition must evaluate to a boolean".to_string()),
                 }
             }
         }
@@ -178,31 +168,29 @@ pub fn execute(stmt: Statement, env: &Environment<Expression>) -> Result<Computa
             show_counter_statement_exec();
             show_statement_exec(format!("Exec For:"));
             show_statement_exec(format!("Statement: {:?}", stmt));
-            show_statement_exec(format!("Env: {:?}", new_env));
-            let values = match eval(*list.clone(), &mut new_env)? {
-                ExpressionResult::Value(expr) => expr,
-                ExpressionResult::Propagate(expr) => {
-                    return Ok(Computation::PropagateError(expr, new_env));
+            let values = match eval(list, env)? {
+                ExpressionResult::Value(value) => value,
+                ExpressionResult::Propagate(value) => {
+                    return Ok(Computation::PropagateError(value));
                 }
             };
 
-            match values {
-                Expression::ListValue(expressions) => {
-                    for exp in expressions {
-                        new_env.create_variable(var.clone(), false, exp)?;
-                        match execute(*stmt.clone(), &mut new_env)? {
-                            Computation::Continue(env) => new_env = env,
-                            Computation::Return(expr, env) => {
-                                return Ok(Computation::Return(expr, env));
-                            }
-                            Computation::PropagateError(expr, env) => {
-                                return Ok(Computation::PropagateError(expr, env));
-                            }
-                        }
-                    }
-                    return Ok(Computation::Continue(new_env));
-                }
-                _ => unreachable!(),
+            // The loop variable lives in a scope of its own, which ends with the loop
+            env.push();
+            let result = execute_iterations(var, values, stmt, env);
+            env.pop();
+            result
+        }
+
+        Statement::TryCatch(body, name, handler) => {
+            show_counter_statement_exec();
+            show_statement_exec(format!("Exec TryCatch: {}", name));
+            show_statement_exec(format!("Statement: {:?}", stmt));
+            // Blocks and function calls leave their scopes even when they fail, so
+            // the handler runs in the environment where the `try` started
+            match execute_body(body, env) {
+                Err(message) => execute_handler(name, message, handler, env),
+                computation => computation,
             }
         }
 
@@ -210,155 +198,205 @@ pub fn execute(stmt: Statement, env: &Environment<Expression>) -> Result<Computa
             show_counter_statement_exec();
             show_statement_exec(format!("Exec Sequence:"));
             show_statement_exec(format!("Statement: {:?}", stmt));
-            show_statement_exec(format!("Env: {:?}", new_env));
-            match execute(*s1, &mut new_env)? {
-                Computation::Continue(env) => new_env = env,
-                Computation::Return(expr, env) => return Ok(Computation::Return(expr, env)),
-                Computation::PropagateError(expr, env) => {
-                    return Ok(Computation::PropagateError(expr, env));
-                }
+            match execute(s1, env)? {
+                Computation::Continue => execute(s2, env),
+                jump => Ok(jump),
             }
-            execute(*s2, &mut new_env)
         }
 
         Statement::FuncDef(func) => {
             show_counter_statement_exec();
             show_statement_exec(format!("Exec FuncDef:"));
             show_statement_exec(format!("Statement: {:?}", stmt));
-            show_statement_exec(format!("Env: {:?}", new_env));
-            new_env.map_function(func.clone());
-            Ok(Computation::Continue(new_env))
+            env.define_function(func.clone());
+            Ok(Computation::Continue)
         }
 
         Statement::Return(exp) => {
             show_counter_statement_exec();
-            show_statement_exec(format!(
-                "Exec Return from function: {}",
-                new_env.current_func
-            ));
+            show_statement_exec(format!("Exec Return from function: {}", env.current_func));
             show_statement_exec(format!("Statement: {:?}", stmt));
-            show_statement_exec(format!("Env: {:?}", new_env));
-            let exp_value = match eval(*exp, &mut new_env)? {
-                ExpressionResult::Value(expr) => expr,
-                ExpressionResult::Propagate(expr) => {
-                    return Ok(Computation::PropagateError(expr, new_env));
+            let exp_value = match eval(exp, env)? {
+                ExpressionResult::Value(value) => value,
+                ExpressionResult::Propagate(value) => {
+                    return Ok(Computation::PropagateError(value));
                 }
             };
             show_counter_statement_exec();
             show_statement_exec(format!(
                 "Finishing Return from function: {}",
-                new_env.current_func
+                env.current_func
             ));
             show_statement_exec(format!("Return result: {:?}", exp_value));
-            Ok(Computation::Return(exp_value, new_env))
+            Ok(Computation::Return(exp_value))
         }
 
         Statement::TypeDeclaration(name, constructors) => {
             show_counter_statement_exec();
             show_statement_exec(format!("Exec TypeDeclaration:"));
             show_statement_exec(format!("Statement: {:?}", stmt));
-            show_statement_exec(format!("Env: {:?}", new_env));
-            new_env.map_adt(name, constructors);
-            Ok(Computation::Continue(new_env))
+            env.map_adt(name.clone(), constructors.clone());
+            Ok(Computation::Continue)
         }
 
         Statement::Print(exp) => {
             show_counter_statement_exec();
             show_statement_exec(format!("Exec Print:"));
             show_statement_exec(format!("Statement: {:?}", stmt));
-            show_statement_exec(format!("Env: {:?}", new_env));
-            let value = match eval(*exp.clone(), &mut new_env)? {
-                ExpressionResult::Value(expr) => expr,
-                ExpressionResult::Propagate(expr) => {
-                    return Ok(Computation::PropagateError(expr, new_env));
+            let value = match eval(exp, env)? {
+                ExpressionResult::Value(value) => value,
+                ExpressionResult::Propagate(value) => {
+                    return Ok(Computation::PropagateError(value));
                 }
             };
             match value {
-                Expression::CString(string) => new_env.insert_output_line(&string),
-                Expression::CInt(integer) => {
-                    let string = integer.to_string();
-                    new_env.insert_output_line(&string);
-                }
-                Expression::CReal(real) => {
-                    let string = real.to_string();
-                    new_env.insert_output_line(&string);
-                }
-                Expression::CTrue => {
-                    new_env.insert_output_line("True");
-                }
-                Expression::CFalse => {
-                    new_env.insert_output_line("False");
+                Value::Str(_) | Value::Int(_) | Value::Real(_) | Value::Bool(_) => {
+                    env.insert_output_line(&value.to_string());
                 }
                 _ => {
                     return Err("Type not supported by print statement".to_string());
                 }
             }
-            return Ok(Computation::Continue(new_env));
+            return Ok(Computation::Continue);
         }
 
-        Statement::SingleFuncCall(name, args) => {
-            let mut new_env = env.clone();
-            match eval_function_call(name, args, &mut new_env) {
-                Ok(ExpressionResult::Value(_)) => {
-                    return Ok(Computation::Continue(new_env));
-                }
-                Ok(ExpressionResult::Propagate(exp)) => {
-                    return Ok(Computation::PropagateError(exp, new_env));
-                }
-                Err(e) => Err(e),
+        Statement::SingleFuncCall(name, args, resolution) => {
+            match eval_function_call(name, args, resolution, env)? {
+                ExpressionResult::Value(_) => Ok(Computation::Continue),
+                ExpressionResult::Propagate(exp) => Ok(Computation::PropagateError(exp)),
             }
         }
 
+        Statement::Break => Ok(Computation::Break),
+
+        Statement::Continue => Ok(Computation::ContinueLoop),
+
+        Statement::Global(name) => {
+            env.declare_global(name.clone());
+            Ok(Computation::Continue)
+        }
+
+        Statement::Comment(_) => Ok(Computation::Continue),
+
         _ => Err(String::from("not implemented yet")),
     }
 }
 
-//Alterei radicalmente execute_block
+// The values a `for` loop binds its variable to: the elements of a list, the
+// integers of a range, the characters of a string, or the value inside a Just
+pub fn iteration_values(values: Value) -> Result<Box<dyn Iterator<Item = Value>>, String> {
+    match values {
+        Value::List(values) => Ok(Box::new(Rc::unwrap_or_clone(values).into_iter())),
+        Value::Range(start, end) => Ok(Box::new((start..end).map(Value::Int))),
+        Value::Str(text) => {
+            let characters: Vec<Value> = text
+                .chars()
+                .map(|c| Value::str(c.encode_utf8(&mut [0; 4])))
+                .collect();
+            Ok(Box::new(characters.into_iter()))
+        }
+        Value::Just(value) => Ok(Box::new(std::iter::once(Rc::unwrap_or_clone(value)))),
+        Value::Nothing => Ok(Box::new(std::iter::empty())),
+        other => Err(format!("Can not iterate over {} in a for loop", other)),
+    }
+}
+
+// Runs the statements of a block in a new scope, which is popped however the block ends
 pub fn execute_block(
-    stmts: Vec<Statement>,
-    env: &Environment<Expression>,
+    stmts: &[Statement],
+    env: &mut Environment<Value>,
 ) -> Result<Computation, String> {
-    let mut current_env = env.clone();
-
     show_counter_statement_exec();
     show_statement_exec(format!("In function execute_block:"));
     show_statement_exec(format!("All statements: {:?}", stmts));
-    show_statement_exec(format!("Env: {:?}", current_env));
 
-    current_env.push();
+    env.push();
+    declare_functions(stmts, env);
+    let result = execute_statements(stmts, env);
+
+    show_counter_statement_exec();
+    show_statement_exec(format!("Exiting function execute_block:"));
+    show_statement_exec(format!("Env is about to be popped"));
+    env.pop();
+    result
+}
 
-    for stmt in stmts.clone() {
+// Each iteration rebinds the loop variable in the scope of the loop
+fn execute_iterations(
+    var: &Name,
+    values: Value,
+    body: &Statement,
+    env: &mut Environment<Value>,
+) -> Result<Computation, String> {
+    for value in iteration_values(values)? {
+        env.map_variable(var.clone(), false, value);
+        match execute_body(body, env)? {
+            Computation::Continue | Computation::ContinueLoop => {}
+            Computation::Break => return Ok(Computation::Continue),
+            Computation::Return(expr) => return Ok(Computation::Return(expr)),
+            Computation::PropagateError(expr) => {
+                return Ok(Computation::PropagateError(expr));
+            }
+        }
+    }
+    Ok(Computation::Continue)
+}
+
+// The handler runs in a scope of its own, where the message of the failure is
+// a string constant
+fn execute_handler(
+    name: &Name,
+    message: String,
+    handler: &Statement,
+    env: &mut Environment<Value>,
+) -> Result<Computation, String> {
+    env.push();
+    env.map_variable(name.clone(), false, Value::str(&message));
+    let result = execute_body(handler, env);
+    env.pop();
+    result
+}
+
+// The body of an `if`, `when`, `while` or `for` runs in a scope of its own, as
+// in the type checker, even if it is not a Statement::Block
+fn execute_body(stmt: &Statement, env: &mut Environment<Value>) -> Result<Computation, String> {
+    match stmt {
+        Statement::Block(stmts) => execute_block(stmts, env),
+        stmt => execute_block(std::slice::from_ref(stmt), env),
+    }
+}
+
+// Maps the functions a block defines before running it, as the type checker
+// does, so that they can be called before their definition
+fn declare_functions(stmts: &[Statement], env: &mut Environment<Value>) {
+    let early = run_before_definition(stmts);
+    for stmt in stmts {
+        if let Statement::FuncDef(func) = stmt {
+            env.hoist_function(func.clone(), early.contains(&func.name));
+        }
+    }
+}
+
+fn execute_statements(
+    stmts: &[Statement],
+    env: &mut Environment<Value>,
+) -> Result<Computation, String> {
+    for stmt in stmts {
         show_counter_statement_exec();
         show_statement_exec(format!("In function execute_block:"));
         show_statement_exec(format!("Single Statement: {:?}", stmt));
-        show_statement_exec(format!("Env: {:?}", current_env));
-        match execute(stmt, &current_env)? {
-            Computation::Continue(new_env) => current_env = new_env,
-            Computation::Return(expr, mut new_env) => {
-                show_counter_statement_exec();
-                show_statement_exec(format!("In function execute_block:"));
-                show_statement_exec(format!(
-                    "Returning from function {} ...",
-                    current_env.current_func
-                ));
-                show_statement_exec(format!("Return result: {:?}", expr));
-                show_statement_exec(format!("All statements: {:?}", stmts));
-                show_statement_exec(format!("Env is about to be popped"));
-                new_env.pop();
-                return Ok(Computation::Return(expr, new_env));
-            }
-            Computation::PropagateError(expr, env) => {
-                return Ok(Computation::PropagateError(expr, current_env));
+        let output_len = env.output.len();
+        match execute(stmt, env)? {
+            Computation::Continue => {}
+            Computation::PropagateError(expr) => {
+                // Propagating discards what the statement printed
+                env.output.truncate(output_len);
+                return Ok(Computation::PropagateError(expr));
             }
+            jump => return Ok(jump),
         }
     }
-    show_counter_statement_exec();
-    show_statement_exec(format!("Exiting function execute_block:"));
-    show_statement_exec(format!("All statements: {:?}", stmts));
-    show_statement_exec(format!("Env: {:?}", current_env));
-    show_statement_exec(format!("Env is about to be popped"));
-    current_env.pop();
-    Ok(Computation::Continue(current_env))
+    Ok(Computation::Continue)
 }
 
 fn show_statement_exec(texto: String) {
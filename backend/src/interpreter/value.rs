@@ -0,0 +1,140 @@
+use std::fmt;
+use std::rc::Rc;
+
+use crate::environment::environment::Link;
+use crate::ir::ast::{Function, Name, Type};
+use crate::type_checker::expression_type_checker::func_to_type;
+
+// Represents the values computed by the interpreter. Compound values are
+// reference counted, so copying a value into a variable or an argument never
+// copies its contents
+#[derive(Debug, PartialEq, Clone)]
+pub enum Value {
+    Int(i32),
+    Real(f64),
+    Str(Rc<str>),
+    Bool(bool),
+    Void,
+    List(Rc<Vec<Value>>),
+    // Integers from the first bound (inclusive) to the second (exclusive)
+    Range(i32, i32),
+    Nothing,
+    Just(Rc<Value>),
+    Ok(Rc<Value>),
+    Err(Rc<Value>),
+    // A function passed as an argument, with the link to its definition
+    Closure(Rc<Function>, Link),
+    // Type of the algebraic data type, name of the constructor and its arguments
+    Adt(Rc<Type>, Name, Rc<Vec<Value>>),
+}
+
+impl Value {
+    pub fn str(text: &str) -> Value {
+        Value::Str(Rc::from(text))
+    }
+
+    // The type used to select an overload of a function called with this value
+    pub fn type_of(&self) -> Type {
+        match self {
+            Value::Int(_) => Type::TInteger,
+            Value::Real(_) => Type::TReal,
+            Value::Str(_) => Type::TString,
+            Value::Bool(_) => Type::TBool,
+            Value::Void => Type::TVoid,
+            Value::List(values) => match values.first() {
+                Some(value) => Type::TList(Box::new(value.type_of())),
+                None => Type::TList(Box::new(Type::TAny)),
+            },
+            Value::Range(_, _) => Type::TList(Box::new(Type::TInteger)),
+            Value::Nothing => Type::TMaybe(Box::new(Type::TAny)),
+            Value::Just(value) => Type::TMaybe(Box::new(value.type_of())),
+            Value::Ok(value) => Type::TResult(Box::new(value.type_of()), Box::new(Type::TAny)),
+            Value::Err(value) => Type::TResult(Box::new(Type::TAny), Box::new(value.type_of())),
+            Value::Closure(func, _) => func_to_type(func),
+            Value::Adt(adt_type, _, _) => (**adt_type).clone(),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Int(value) => write!(f, "{}", value),
+            Value::Real(value) => write!(f, "{}", value),
+            Value::Str(value) => write!(f, "{}", value),
+            Value::Bool(true) => write!(f, "True"),
+            Value::Bool(false) => write!(f, "False"),
+            Value::Void => write!(f, "void"),
+            Value::List(values) => {
+                let values = values
+                    .iter()
+                    .map(|v| v.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                write!(f, "[{}]", values)
+            }
+            Value::Range(start, end) => write!(f, "range({}, {})", start, end),
+            Value::Nothing => write!(f, "Nothing"),
+            Value::Just(value) => write!(f, "Just({})", value),
+            Value::Ok(value) => write!(f, "Ok({})", value),
+            Value::Err(value) => write!(f, "Err({})", value),
+            Value::Closure(func, _) => write!(f, "<function {}>", func.name),
+            Value::Adt(_, constructor, args) if args.is_empty() => write!(f, "{}", constructor),
+            Value::Adt(_, constructor, args) => {
+                let args = args
+                    .iter()
+                    .map(|v| v.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                write!(f, "{}({})", constructor, args)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_values_share_their_contents() {
+        let list = Value::List(Rc::new(vec![Value::Int(1), Value::str("a")]));
+        let copy = list.clone();
+        match (&list, &copy) {
+            (Value::List(l1), Value::List(l2)) => assert!(Rc::ptr_eq(l1, l2)),
+            _ => unreachable!(),
+        }
+        assert_eq!(list, copy);
+    }
+
+    #[test]
+    fn test_type_of_values() {
+        assert_eq!(Value::Int(1).type_of(), Type::TInteger);
+        assert_eq!(
+            Value::List(Rc::new(vec![])).type_of(),
+            Type::TList(Box::new(Type::TAny))
+        );
+        assert_eq!(
+            Value::Range(0, 3).type_of(),
+            Type::TList(Box::new(Type::TInteger))
+        );
+        assert_eq!(
+            Value::Just(Rc::new(Value::str("a"))).type_of(),
+            Type::TMaybe(Box::new(Type::TString))
+        );
+        assert_eq!(
+            Value::Err(Rc::new(Value::Int(1))).type_of(),
+            Type::TResult(Box::new(Type::TAny), Box::new(Type::TInteger))
+        );
+    }
+
+    #[test]
+    fn test_display_values() {
+        let list = Value::List(Rc::new(vec![Value::Int(1), Value::Bool(true)]));
+        assert_eq!(list.to_string(), "[1, True]");
+        assert_eq!(
+            Value::Just(Rc::new(Value::Real(1.5))).to_string(),
+            "Just(1.5)"
+        );
+    }
+}
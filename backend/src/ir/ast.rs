@@ -1,3 +1,4 @@
+use std::collections::HashSet;
 use std::fmt;
 
 // Type alias for variable and function names
@@ -82,6 +83,16 @@ impl fmt::Display for FuncSignature {
     }
 }
 
+// What the type checker resolves a call to, for the interpreter and the VM to
+// run it without looking the name up again
+#[derive(Debug, Clone, PartialEq)]
+pub enum Resolution {
+    // The builtin of the name, which no function of the program hides
+    Builtin,
+    // An overload of a function of the program
+    Overload(FuncSignature),
+}
+
 // Represents the types that can be used in the AST
 #[derive(Clone, Debug, Eq, PartialEq, Hash)]
 pub enum Type {
@@ -154,6 +165,17 @@ impl ValueConstructor {
     }
 }
 
+// Represents the operator of a comparison in a chain of comparisons
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Comparison {
+    EQ,
+    NEQ,
+    GT,
+    LT,
+    GTE,
+    LTE,
+}
+
 // Represents expressions in the AST
 #[derive(Debug, PartialEq, Clone)]
 pub enum Expression {
@@ -164,18 +186,32 @@ pub enum Expression {
     CReal(f64),
     CString(String),
     CVoid,
+    // The value that a declaration block gives to a variable declared without
+    // one. It evaluates like the wrapped constant, but static analysis regards
+    // the variable as not assigned yet
+    Default(Box<Expression>),
 
     // Variable reference
     Var(Name),
+    // A function given by name as an argument, with the overload the type
+    // checker resolves it to
+    FuncRef(FuncSignature),
 
-    // Function call
-    FuncCall(Name, Vec<Expression>),
+    // Function call, with what the type checker resolves it to. The interpreter
+    // dispatches on it instead of the types of the values
+    FuncCall(Name, Vec<Expression>, Option<Resolution>),
 
     // Arithmetic expressions over numbers
     Add(Box<Expression>, Box<Expression>),
     Sub(Box<Expression>, Box<Expression>),
     Mul(Box<Expression>, Box<Expression>),
     Div(Box<Expression>, Box<Expression>),
+    Mod(Box<Expression>, Box<Expression>),
+    Pow(Box<Expression>, Box<Expression>),
+    Neg(Box<Expression>),
+    // Converts an int to a real, or the int in a Just or an Ok to a real. The
+    // type checker inserts it where an int is given for a real parameter
+    ToReal(Box<Expression>),
 
     // Boolean expressions over booleans
     And(Box<Expression>, Box<Expression>),
@@ -189,6 +225,9 @@ pub enum Expression {
     LT(Box<Expression>, Box<Expression>),
     GTE(Box<Expression>, Box<Expression>),
     LTE(Box<Expression>, Box<Expression>),
+    // A chain of comparisons such as `a < b <= c`, which holds when each of
+    // them does. Every operand is evaluated once, from left to right
+    Chain(Box<Expression>, Vec<(Comparison, Expression)>),
 
     // Error-related expressions
     COk(Box<Expression>),
@@ -200,9 +239,20 @@ pub enum Expression {
     IsNothing(Box<Expression>),
     Propagate(Box<Expression>),
 
+    // Combinators over a Maybe or a Result: `map` and `and_then` call the
+    // function of their second operand on the value of a Just or an Ok
+    Map(Box<Expression>, Box<Expression>),
+    AndThen(Box<Expression>, Box<Expression>),
+    UnwrapOr(Box<Expression>, Box<Expression>),
+    OkOr(Box<Expression>, Box<Expression>),
+
     // List value
     ListValue(Vec<Expression>),
 
+    // Integers from the first bound (inclusive) to the second (exclusive). It is
+    // typed as a list of ints, but its values are never stored as a list
+    Range(Box<Expression>, Box<Expression>),
+
     //Lambda expressions
     Lambda(Function),
 
@@ -216,9 +266,20 @@ pub enum Statement {
     VarDeclaration(Name, Box<Expression>),
     ValDeclaration(Name, Box<Expression>),
     Assignment(Name, Box<Expression>),
+    // An `elif` is an IfThenElse whose else branch is another IfThenElse, not wrapped in a Block
     IfThenElse(Box<Expression>, Box<Statement>, Option<Box<Statement>>),
+    // Runs the body of the first case whose value equals the int or string
+    // expression, or the default body when no case matches
+    When(
+        Box<Expression>,
+        Vec<(Expression, Statement)>,
+        Option<Box<Statement>>,
+    ),
     While(Box<Expression>, Box<Statement>),
     For(Name, Box<Expression>, Box<Statement>),
+    // Runs the first body, and the second one if the first fails at runtime,
+    // with the message of the failure in the variable of the given name
+    TryCatch(Box<Statement>, Name, Box<Statement>),
     Block(Vec<Statement>),
     Sequence(Box<Statement>, Box<Statement>),
     Assert(Box<Expression>, Box<Expression>),
@@ -230,8 +291,153 @@ pub enum Statement {
     ModTestDef(Name, Box<Statement>),
     AssertFails(String),
     FuncDef(Function),
-    SingleFuncCall(Name, Vec<Expression>),
+    SingleFuncCall(Name, Vec<Expression>, Option<Resolution>),
     Return(Box<Expression>),
+    // Leave the innermost loop, or skip to its next iteration
+    Break,
+    Continue,
+    // Lets a function assign the top-level variable with the given name
+    Global(Name),
     TypeDeclaration(Name, Vec<ValueConstructor>),
     Print(Box<Expression>),
+    // Comment of a textual program, with its delimiters; it has no effect
+    Comment(String),
+}
+
+impl Expression {
+    // Adds the names of the variables and functions that the expression refers
+    // to, including those in the bodies of its lambdas
+    pub fn collect_names(&self, names: &mut HashSet<Name>) {
+        match self {
+            Expression::Var(name) => {
+                names.insert(name.clone());
+            }
+            Expression::FuncCall(name, args, _) => {
+                names.insert(name.clone());
+                args.iter().for_each(|arg| arg.collect_names(names));
+            }
+            Expression::ListValue(items) => items.iter().for_each(|item| item.collect_names(names)),
+            Expression::Constructor(_, args) => {
+                args.iter().for_each(|arg| arg.collect_names(names))
+            }
+            Expression::Lambda(func) => func.collect_names(names),
+            Expression::FuncRef(func_signature) => {
+                names.insert(func_signature.name.clone());
+            }
+            Expression::Default(e)
+            | Expression::Neg(e)
+            | Expression::ToReal(e)
+            | Expression::Not(e)
+            | Expression::COk(e)
+            | Expression::CErr(e)
+            | Expression::CJust(e)
+            | Expression::Unwrap(e)
+            | Expression::IsError(e)
+            | Expression::IsNothing(e)
+            | Expression::Propagate(e) => e.collect_names(names),
+            Expression::Add(l, r)
+            | Expression::Sub(l, r)
+            | Expression::Mul(l, r)
+            | Expression::Div(l, r)
+            | Expression::Mod(l, r)
+            | Expression::Pow(l, r)
+            | Expression::And(l, r)
+            | Expression::Or(l, r)
+            | Expression::EQ(l, r)
+            | Expression::NEQ(l, r)
+            | Expression::GT(l, r)
+            | Expression::LT(l, r)
+            | Expression::GTE(l, r)
+            | Expression::LTE(l, r)
+            | Expression::Map(l, r)
+            | Expression::AndThen(l, r)
+            | Expression::UnwrapOr(l, r)
+            | Expression::OkOr(l, r)
+            | Expression::Range(l, r) => {
+                l.collect_names(names);
+                r.collect_names(names);
+            }
+            Expression::Chain(first, links) => {
+                first.collect_names(names);
+                for (_, operand) in links {
+                    operand.collect_names(names);
+                }
+            }
+            Expression::CTrue
+            | Expression::CFalse
+            | Expression::CInt(_)
+            | Expression::CReal(_)
+            | Expression::CString(_)
+            | Expression::CVoid
+            | Expression::CNothing => {}
+        }
+    }
+}
+
+impl Function {
+    pub fn collect_names(&self, names: &mut HashSet<Name>) {
+        if let Some(body) = &self.body {
+            body.collect_names(names);
+        }
+    }
+}
+
+impl Statement {
+    // Adds the names of the variables and functions that the statement refers
+    // to, including those in the bodies of the functions it defines
+    pub fn collect_names(&self, names: &mut HashSet<Name>) {
+        match self {
+            Statement::VarDeclaration(_, e)
+            | Statement::ValDeclaration(_, e)
+            | Statement::Assignment(_, e)
+            | Statement::AssertTrue(e, _)
+            | Statement::AssertFalse(e, _)
+            | Statement::Return(e)
+            | Statement::Print(e) => e.collect_names(names),
+            Statement::Assert(l, r)
+            | Statement::AssertEQ(l, r, _)
+            | Statement::AssertNEQ(l, r, _) => {
+                l.collect_names(names);
+                r.collect_names(names);
+            }
+            Statement::IfThenElse(cond, then, other) => {
+                cond.collect_names(names);
+                then.collect_names(names);
+                if let Some(other) = other {
+                    other.collect_names(names);
+                }
+            }
+            Statement::When(exp, cases, default) => {
+                exp.collect_names(names);
+                for (value, body) in cases {
+                    value.collect_names(names);
+                    body.collect_names(names);
+                }
+                if let Some(default) = default {
+                    default.collect_names(names);
+                }
+            }
+            Statement::While(cond, body) | Statement::For(_, cond, body) => {
+                cond.collect_names(names);
+                body.collect_names(names);
+            }
+            Statement::TryCatch(body, _, handler) | Statement::Sequence(body, handler) => {
+                body.collect_names(names);
+                handler.collect_names(names);
+            }
+            Statement::Block(stmts) => stmts.iter().for_each(|stmt| stmt.collect_names(names)),
+            Statement::ModTestDef(_, body) => body.collect_names(names),
+            Statement::FuncDef(func) | Statement::TestDef(func) => func.collect_names(names),
+            Statement::SingleFuncCall(name, args, _) => {
+                names.insert(name.clone());
+                args.iter().for_each(|arg| arg.collect_names(names));
+            }
+            Statement::AssertFails(_)
+            | Statement::Break
+            | Statement::Continue
+            | Statement::Global(_)
+            | Statement::TypeDeclaration(..)
+            | Statement::Comment(_) => {}
+        }
+    }
 }
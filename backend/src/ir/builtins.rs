@@ -0,0 +1,46 @@
+//! Builtin functions. Their names are not reserved: a call runs the builtin
+//! only when the program declares no function of the same name, so they are
+//! free to name variables and functions too.
+
+use crate::ir::ast::Expression;
+
+pub const MAP_BUILTIN: &str = "map";
+pub const AND_THEN_BUILTIN: &str = "and_then";
+pub const UNWRAP_OR_BUILTIN: &str = "unwrap_or";
+pub const OK_OR_BUILTIN: &str = "ok_or";
+pub const RANGE_BUILTIN: &str = "range";
+
+type BinaryBuild = fn(Box<Expression>, Box<Expression>) -> Expression;
+
+// The builtins that take two arguments, with the expression each call builds
+const BINARY_BUILTINS: &[(&str, BinaryBuild)] = &[
+    (MAP_BUILTIN, Expression::Map),
+    (AND_THEN_BUILTIN, Expression::AndThen),
+    (UNWRAP_OR_BUILTIN, Expression::UnwrapOr),
+    (OK_OR_BUILTIN, Expression::OkOr),
+    (RANGE_BUILTIN, Expression::Range),
+];
+
+/// The expression a call of a builtin stands for, or None when no builtin of
+/// that name takes the given arguments.
+pub fn builtin_call(name: &str, args: &[Expression]) -> Option<Expression> {
+    let (_, build) = BINARY_BUILTINS
+        .iter()
+        .find(|(builtin, _)| *builtin == name)?;
+    match args {
+        [lhs, rhs] => Some(build(Box::new(lhs.clone()), Box::new(rhs.clone()))),
+        _ => None,
+    }
+}
+
+/// The arguments of the call that builtin_call turned into the given expression.
+pub fn builtin_operands(builtin: Expression) -> Vec<Expression> {
+    match builtin {
+        Expression::Map(lhs, rhs)
+        | Expression::AndThen(lhs, rhs)
+        | Expression::UnwrapOr(lhs, rhs)
+        | Expression::OkOr(lhs, rhs)
+        | Expression::Range(lhs, rhs) => vec![*lhs, *rhs],
+        _ => Vec::new(),
+    }
+}
@@ -0,0 +1,23 @@
+use std::sync::Mutex;
+
+pub use crate::handlers::hard_interpreter::{show, show_counter};
+
+// Declare a variável global static mutável com Mutex para sincronização
+lazy_static::lazy_static! {
+    pub static ref COUNTER: Mutex<u64> = Mutex::new(0);
+    // Whether the steps of the type checker and the interpreter are logged to the
+    // text files, which is only done when the LOG_STEPS variable is set
+    pub static ref LOG_STEPS: bool = std::env::var_os("LOG_STEPS").is_some();
+}
+
+pub mod environment;
+pub mod handlers;
+pub mod interpreter;
+pub mod ir;
+pub mod models;
+pub mod parser;
+pub mod routes;
+#[cfg(test)]
+pub mod test_utils;
+pub mod type_checker;
+pub mod vm;
@@ -1,28 +1,7 @@
 use actix_files::Files;
 use actix_web::{App, HttpServer};
 
-use crate::handlers::hard_interpreter::{execute_with_json, reset_txt_files, show, show_counter};
-use crate::parser::{
-    keyword, parse_expression, parse_formal_argument, parse_lambda, parse_return_statement,
-    parse_statement,
-};
-use std::fs::File;
-use std::io::Write;
-use std::sync::Mutex;
-
-// Declare a variável global static mutável com Mutex para sincronização
-lazy_static::lazy_static! {
-    pub static ref COUNTER: Mutex<u64> = Mutex::new(0);
-}
-
-mod environment;
-mod handlers;
-mod interpreter;
-mod ir;
-mod models;
-mod parser;
-mod routes;
-mod type_checker;
+use backend::routes;
 
 // Allows the main function to be an async function
 #[actix_web::main]
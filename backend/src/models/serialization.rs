@@ -10,6 +10,9 @@ pub struct Workspace {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Workspace2 {
     pub blocks: Blocks2,
+    // Runs the program on the bytecode VM instead of the tree-walking interpreter
+    #[serde(default)]
+    pub bytecode: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -19,7 +19,7 @@ pub fn handle_math_operations(executor: &mut dyn BlockExecutor, block: &Block) -
         "sub" => num1 - num2,
         "mult" => num1 * num2,
         "divi" => num1 / num2,
-        _ => unreachable!(),
+        _ => return None,
     };
 
     Some(Value::Number(result))
@@ -44,7 +44,7 @@ pub fn handle_math_comparisons(executor: &mut dyn BlockExecutor, block: &Block)
         "equal" => (num1 - num2).abs() < std::f64::EPSILON,
         "less_equal" => num1 <= num2,
         "greater_equal" => num1 >= num2,
-        _ => unreachable!(),
+        _ => return None,
     };
 
     Some(Value::Boolean(result))
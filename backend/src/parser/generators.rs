@@ -0,0 +1,296 @@
+//! Random generators of well-formed AST trees for property testing.
+//!
+//! Only the fragment of the AST that has a textual syntax is generated
+//! (e.g. no `Print` statements or `Constructor` expressions), and the trees have
+//! exactly the shape produced by the parser (bodies are always `Statement::Block`s,
+//! lambda bodies hold a single `ret`).
+
+use proptest::collection::vec;
+use proptest::option;
+use proptest::prelude::*;
+
+use crate::ir::ast::{
+    Comparison, Expression, FormalArgument, Function, Name, Statement, Type, ValueConstructor,
+};
+use crate::parser::keywords::KEYWORDS;
+use crate::parser::parser_common::{
+    ANY_TYPE, ASSERT_KEYWORD, BOOLEAN_TYPE, DATA_KEYWORD, DEF_KEYWORD, END_KEYWORD, INT_TYPE,
+    MAYBE_TYPE, REAL_TYPE, RESULT_TYPE, RET_KEYWORD, STRING_TYPE, UNIT_TYPE,
+};
+
+// Words that the parser recognizes as keywords or type names in some context
+fn is_reserved(word: &str) -> bool {
+    let reserved = [
+        ANY_TYPE,
+        ASSERT_KEYWORD,
+        BOOLEAN_TYPE,
+        DATA_KEYWORD,
+        DEF_KEYWORD,
+        END_KEYWORD,
+        INT_TYPE,
+        MAYBE_TYPE,
+        REAL_TYPE,
+        RESULT_TYPE,
+        RET_KEYWORD,
+        STRING_TYPE,
+        UNIT_TYPE,
+    ];
+    KEYWORDS.contains(&word) || reserved.contains(&word)
+}
+
+pub fn identifier() -> impl Strategy<Value = Name> {
+    "[a-z_][a-z0-9_]{0,6}".prop_filter("identifier must not be a keyword", |name| {
+        !is_reserved(name)
+    })
+}
+
+pub fn constructor_name() -> impl Strategy<Value = Name> {
+    "[A-Z][a-zA-Z0-9]{0,6}".prop_filter("constructor must not be a keyword", |name| {
+        !is_reserved(name)
+    })
+}
+
+// Any character accepted by is_string_char, except quotes and control characters
+pub fn string_literal() -> impl Strategy<Value = String> {
+    "[a-zA-Z0-9 _.,;:!?+*/=<>()\\[\\]-]{0,10}"
+}
+
+// Line comments stop at the newline and block comments must not contain `*/`
+pub fn comment() -> impl Strategy<Value = String> {
+    prop_oneof![
+        "[a-zA-Z0-9 #;:*/\"]{0,12}".prop_map(|text| format!("#{}", text)),
+        "[a-zA-Z0-9 #;:/\"\n]{0,12}".prop_map(|text| format!("/*{}*/", text)),
+    ]
+}
+
+// Reals with a few decimal places, which are always printed as `digits.digits`
+pub fn real_literal() -> impl Strategy<Value = f64> {
+    (-1_000_000i64..1_000_000, 1i32..4)
+        .prop_map(|(mantissa, places)| mantissa as f64 / 10f64.powi(places))
+}
+
+/// Types that can annotate variables, parameters and return values.
+pub fn value_type() -> impl Strategy<Value = Type> {
+    let leaf = prop_oneof![
+        Just(Type::TInteger),
+        Just(Type::TBool),
+        Just(Type::TReal),
+        Just(Type::TString),
+        Just(Type::TVoid),
+        Just(Type::TAny),
+    ];
+    leaf.prop_recursive(3, 16, 3, |inner| {
+        prop_oneof![
+            inner.clone().prop_map(|t| Type::TList(Box::new(t))),
+            vec(inner.clone(), 1..3).prop_map(Type::TTuple),
+            inner.clone().prop_map(|t| Type::TMaybe(Box::new(t))),
+            (inner.clone(), inner.clone())
+                .prop_map(|(ok, err)| Type::TResult(Box::new(ok), Box::new(err))),
+            (inner.clone(), vec(inner, 0..3))
+                .prop_map(|(ret, params)| Type::TFunction(Box::new(ret), params)),
+        ]
+    })
+}
+
+/// Any type accepted by `parse_type`, including algebraic data declarations.
+pub fn any_type() -> impl Strategy<Value = Type> {
+    let constructor = (constructor_name(), vec(value_type(), 0..3))
+        .prop_map(|(name, types)| ValueConstructor::new(name, types));
+    prop_oneof![
+        4 => value_type(),
+        1 => (constructor_name(), vec(constructor, 1..4))
+            .prop_map(|(name, constructors)| Type::TAlgebraicData(name, constructors)),
+    ]
+}
+
+pub fn formal_argument() -> impl Strategy<Value = FormalArgument> {
+    (identifier(), value_type()).prop_map(|(name, t)| FormalArgument::new(name, t))
+}
+
+pub fn expression() -> impl Strategy<Value = Expression> {
+    expression_with_depth(4)
+}
+
+fn expression_with_depth(depth: u32) -> impl Strategy<Value = Expression> {
+    let leaf = prop_oneof![
+        Just(Expression::CTrue),
+        Just(Expression::CFalse),
+        any::<i32>().prop_map(Expression::CInt),
+        real_literal().prop_map(Expression::CReal),
+        string_literal().prop_map(Expression::CString),
+        identifier().prop_map(Expression::Var),
+        Just(Expression::CNothing),
+    ];
+    leaf.prop_recursive(depth, 32, 3, |inner| {
+        let binary = (inner.clone(), inner.clone(), 0..14usize).prop_map(|(l, r, op)| {
+            let (l, r) = (Box::new(l), Box::new(r));
+            match op {
+                0 => Expression::Add(l, r),
+                1 => Expression::Sub(l, r),
+                2 => Expression::Mul(l, r),
+                3 => Expression::Div(l, r),
+                4 => Expression::And(l, r),
+                5 => Expression::Or(l, r),
+                6 => Expression::EQ(l, r),
+                7 => Expression::NEQ(l, r),
+                8 => Expression::GT(l, r),
+                9 => Expression::LT(l, r),
+                10 => Expression::GTE(l, r),
+                11 => Expression::LTE(l, r),
+                12 => Expression::Mod(l, r),
+                _ => Expression::Pow(l, r),
+            }
+        });
+        // The parser only builds a chain out of two or more comparisons
+        let comparison = prop_oneof![
+            Just(Comparison::EQ),
+            Just(Comparison::NEQ),
+            Just(Comparison::GT),
+            Just(Comparison::LT),
+            Just(Comparison::GTE),
+            Just(Comparison::LTE),
+        ];
+        let chain = (inner.clone(), vec((comparison, inner.clone()), 2..4))
+            .prop_map(|(first, links)| Expression::Chain(Box::new(first), links));
+        let lambda = (vec(formal_argument(), 0..3), value_type(), inner.clone()).prop_map(
+            |(params, kind, ret)| {
+                Expression::Lambda(Function {
+                    name: "".to_string(),
+                    kind,
+                    params,
+                    body: Some(Box::new(Statement::Block(vec![Statement::Return(
+                        Box::new(ret),
+                    )]))),
+                })
+            },
+        );
+        let builtin = (inner.clone(), 0..7usize).prop_map(|(e, op)| {
+            let e = Box::new(e);
+            match op {
+                0 => Expression::CJust(e),
+                1 => Expression::COk(e),
+                2 => Expression::CErr(e),
+                3 => Expression::Unwrap(e),
+                4 => Expression::Propagate(e),
+                5 => Expression::IsNothing(e),
+                _ => Expression::IsError(e),
+            }
+        });
+        prop_oneof![
+            4 => binary,
+            1 => chain,
+            1 => builtin,
+            1 => inner.clone().prop_map(|e| Expression::Not(Box::new(e))),
+            1 => inner.clone().prop_map(|e| Expression::Neg(Box::new(e))),
+            1 => (identifier(), vec(inner.clone(), 0..3))
+                .prop_map(|(name, args)| Expression::FuncCall(name, args, None)),
+            1 => vec(inner.clone(), 0..3).prop_map(Expression::ListValue),
+            1 => lambda,
+        ]
+    })
+}
+
+pub fn statement() -> impl Strategy<Value = Statement> {
+    let exp = || expression_with_depth(2);
+    let leaf = prop_oneof![
+        (identifier(), exp()).prop_map(|(name, e)| Statement::VarDeclaration(name, Box::new(e))),
+        (identifier(), exp()).prop_map(|(name, e)| Statement::ValDeclaration(name, Box::new(e))),
+        (identifier(), exp()).prop_map(|(name, e)| Statement::Assignment(name, Box::new(e))),
+        (exp(), exp()).prop_map(|(l, r)| Statement::Assert(Box::new(l), Box::new(r))),
+        exp().prop_map(|e| Statement::Return(Box::new(e))),
+        Just(Statement::Break),
+        Just(Statement::Continue),
+        identifier().prop_map(Statement::Global),
+    ];
+    leaf.prop_recursive(3, 24, 3, move |inner| {
+        let block = vec(
+            prop_oneof![4 => inner, 1 => comment().prop_map(Statement::Comment)],
+            0..3,
+        )
+        .prop_map(Statement::Block);
+        prop_oneof![
+            (exp(), block.clone(), option::of(block.clone())).prop_map(|(cond, then, other)| {
+                Statement::IfThenElse(Box::new(cond), Box::new(then), other.map(Box::new))
+            }),
+            // An `elif` chain
+            (
+                exp(),
+                block.clone(),
+                exp(),
+                block.clone(),
+                option::of(block.clone())
+            )
+                .prop_map(|(cond, then, elif_cond, elif_then, other)| {
+                    let elif = Statement::IfThenElse(
+                        Box::new(elif_cond),
+                        Box::new(elif_then),
+                        other.map(Box::new),
+                    );
+                    Statement::IfThenElse(Box::new(cond), Box::new(then), Some(Box::new(elif)))
+                }),
+            (
+                exp(),
+                vec((exp(), block.clone()), 0..3),
+                option::of(block.clone())
+            )
+                .prop_map(|(exp, cases, default)| {
+                    Statement::When(Box::new(exp), cases, default.map(Box::new))
+                }),
+            (exp(), block.clone())
+                .prop_map(|(cond, body)| Statement::While(Box::new(cond), Box::new(body))),
+            (identifier(), exp(), block.clone()).prop_map(|(var, iterable, body)| {
+                Statement::For(var, Box::new(iterable), Box::new(body))
+            }),
+            (block.clone(), identifier(), block.clone()).prop_map(|(body, name, handler)| {
+                Statement::TryCatch(Box::new(body), name, Box::new(handler))
+            }),
+            (
+                identifier(),
+                vec(formal_argument(), 0..3),
+                value_type(),
+                block
+            )
+                .prop_map(|(name, params, kind, body)| {
+                    Statement::FuncDef(Function {
+                        name,
+                        kind,
+                        params,
+                        body: Some(Box::new(body)),
+                    })
+                }),
+        ]
+    })
+}
+
+/// Top-level statements of a program, with comments between them.
+pub fn program() -> impl Strategy<Value = Vec<Statement>> {
+    vec(
+        prop_oneof![4 => statement(), 1 => comment().prop_map(Statement::Comment)],
+        0..4,
+    )
+}
+
+/// Arbitrary sequences of tokens of the textual grammar, mostly ill-formed,
+/// used to check that the parsers fail gracefully instead of panicking.
+pub fn token_soup() -> impl Strategy<Value = String> {
+    let token = prop_oneof![
+        proptest::sample::select(vec![
+            "if", "else", "while", "for", "in", "def", "var", "val", "ret", "lambda", "assert",
+            "end", "data", "and", "or", "not", "True", "False", "int", "float", "Maybe", "Result",
+            "(", ")", "[", "]", ",", ":", ";", "=", "->", "|", "+", "-", "*", "/", "%", "**", "<",
+            "<=", ">", ">=", "==", "!=", "\"", "-1", "2.5", "#", "/*", "*/", "\n", "Just",
+            "Nothing", "unwrap",
+        ])
+        .prop_map(|t| t.to_string()),
+        identifier(),
+        any::<i64>().prop_map(|n| n.to_string()),
+        string_literal().prop_map(|s| format!("\"{}\"", s)),
+    ];
+    let whitespace = proptest::sample::select(vec!["", " ", "\n", "\t"]);
+    vec((token, whitespace), 0..40).prop_map(|tokens| {
+        tokens
+            .into_iter()
+            .map(|(token, space)| format!("{}{}", token, space))
+            .collect()
+    })
+}
@@ -1,25 +1,47 @@
+use crate::parser::operators::{AND_OPERATOR, NOT_OPERATOR, OR_OPERATOR};
+use crate::parser::parser_common::{
+    ASSERT_KEYWORD, BREAK_KEYWORD, CASE_KEYWORD, CATCH_KEYWORD, CONTINUE_KEYWORD, DATA_KEYWORD,
+    DEF_KEYWORD, ELIF_KEYWORD, ELSE_KEYWORD, END_KEYWORD, ERR_KEYWORD, FALSE_KEYWORD, FOR_KEYWORD,
+    GLOBAL_KEYWORD, IF_KEYWORD, IN_KEYWORD, IS_ERROR_KEYWORD, IS_NOTHING_KEYWORD, JUST_KEYWORD,
+    LAMBDA_KEYWORD, NOTHING_KEYWORD, OK_KEYWORD, RET_KEYWORD, TRUE_KEYWORD, TRY_KEYWORD,
+    TRY_UNWRAP_KEYWORD, UNWRAP_KEYWORD, VAL_KEYWORD, VAR_KEYWORD, WHEN_KEYWORD, WHILE_KEYWORD,
+};
+
+/// Words that the lexer never reads as identifiers. Type names (e.g. `int`) are
+/// not reserved: they are only recognized where a type is expected.
 pub const KEYWORDS: &[&str] = &[
-    "if",
-    "in",
-    "else",
-    "def",
-    "while",
-    "for",
-    "val",
-    "var",
-    "return",
-    "lambda",
-    "Ok",
-    "Err",
-    "Just",
-    "Nothing",
-    "unwrap",
-    "tryUnwrap",
-    "isNothing",
-    "isError",
-    "and",
-    "or",
-    "not",
-    "True",
-    "False",
+    IF_KEYWORD,
+    IN_KEYWORD,
+    ELSE_KEYWORD,
+    ELIF_KEYWORD,
+    WHEN_KEYWORD,
+    CASE_KEYWORD,
+    DEF_KEYWORD,
+    WHILE_KEYWORD,
+    FOR_KEYWORD,
+    VAL_KEYWORD,
+    VAR_KEYWORD,
+    RET_KEYWORD,
+    BREAK_KEYWORD,
+    CONTINUE_KEYWORD,
+    GLOBAL_KEYWORD,
+    TRY_KEYWORD,
+    CATCH_KEYWORD,
+    LAMBDA_KEYWORD,
+    ASSERT_KEYWORD,
+    DATA_KEYWORD,
+    END_KEYWORD,
+    OK_KEYWORD,
+    ERR_KEYWORD,
+    JUST_KEYWORD,
+    NOTHING_KEYWORD,
+    UNWRAP_KEYWORD,
+    TRY_UNWRAP_KEYWORD,
+    IS_NOTHING_KEYWORD,
+    IS_ERROR_KEYWORD,
+    AND_OPERATOR,
+    OR_OPERATOR,
+    NOT_OPERATOR,
+    TRUE_KEYWORD,
+    FALSE_KEYWORD,
 ];
@@ -0,0 +1,273 @@
+//! Splits the source text into tokens for the statement, expression and type parsers.
+//!
+//! Every token remembers its span in the source, so the parsers can report
+//! errors (and return the unconsumed input) in terms of the original text.
+//! Comments are kept as tokens: the parsers skip them, except where a statement
+//! may start, where they are kept in the AST for the pretty printer.
+
+use nom::InputLength;
+use nom::error::{VerboseError, VerboseErrorKind};
+
+use crate::parser::keywords::KEYWORDS;
+use crate::parser::parser_common::{
+    BLOCK_COMMENT_END, BLOCK_COMMENT_START, LINE_COMMENT_START, is_string_char,
+};
+
+// Symbols of more than one character; everything else is a single character
+const LONG_SYMBOLS: &[&str] = &["**", "->", "<=", ">=", "==", "!="];
+const SYMBOL_CHARS: &str = "+-*/%<>=()[],:;|";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    Identifier,
+    /// A word listed in `KEYWORDS`, which can not be used as an identifier
+    Keyword,
+    Int,
+    Real,
+    /// A string literal, including its quotes
+    Str,
+    Symbol,
+    /// A comment, including its delimiters
+    Comment,
+    /// A character that does not start any token
+    Unknown,
+    /// The end of the input, always the last token
+    Eof,
+}
+
+/// Byte offsets of a token in the source text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token<'a> {
+    pub kind: TokenKind,
+    pub text: &'a str,
+    pub span: Span,
+}
+
+/// Splits `input` into tokens, ending with a `TokenKind::Eof` token.
+///
+/// The only errors are unterminated strings and block comments; characters that
+/// do not start a token become `TokenKind::Unknown` tokens, left for the parsers
+/// to report.
+pub fn tokenize(input: &str) -> Result<Vec<Token<'_>>, VerboseError<&str>> {
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    loop {
+        start += skip_whitespace(&input[start..]);
+        let rest = &input[start..];
+        let Some(first) = rest.chars().next() else {
+            break;
+        };
+        let (kind, len) = if rest.starts_with(LINE_COMMENT_START) {
+            (TokenKind::Comment, rest.find('\n').unwrap_or(rest.len()))
+        } else if let Some(body) = rest.strip_prefix(BLOCK_COMMENT_START) {
+            match body.find(BLOCK_COMMENT_END) {
+                Some(end) => (
+                    TokenKind::Comment,
+                    BLOCK_COMMENT_START.len() + end + BLOCK_COMMENT_END.len(),
+                ),
+                None => return Err(error(body, "`*/` closing the comment")),
+            }
+        } else if first == '"' {
+            let body = &rest[1..];
+            let end = body.find(|c| !is_string_char(c)).unwrap_or(body.len());
+            if !body[end..].starts_with('"') {
+                return Err(error(&body[end..], "closing `\"`"));
+            }
+            (TokenKind::Str, end + 2)
+        } else if first.is_ascii_digit() {
+            number(rest)
+        } else if first.is_ascii_alphabetic() || first == '_' {
+            let len = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            if KEYWORDS.contains(&&rest[..len]) {
+                (TokenKind::Keyword, len)
+            } else {
+                (TokenKind::Identifier, len)
+            }
+        } else if let Some(symbol) = LONG_SYMBOLS.iter().find(|s| rest.starts_with(*s)) {
+            (TokenKind::Symbol, symbol.len())
+        } else if SYMBOL_CHARS.contains(first) {
+            (TokenKind::Symbol, 1)
+        } else {
+            (TokenKind::Unknown, first.len_utf8())
+        };
+        tokens.push(Token {
+            kind,
+            text: &rest[..len],
+            span: Span {
+                start,
+                end: start + len,
+            },
+        });
+        start += len;
+    }
+    tokens.push(Token {
+        kind: TokenKind::Eof,
+        text: "",
+        span: Span {
+            start: input.len(),
+            end: input.len(),
+        },
+    });
+    Ok(tokens)
+}
+
+fn skip_whitespace(input: &str) -> usize {
+    input.len() - input.trim_start().len()
+}
+
+// `digits` or `digits.digits`: a dot that is not followed by a digit ends the number
+fn number(input: &str) -> (TokenKind, usize) {
+    let digits =
+        |text: &str| text.len() - text.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let integer = digits(input);
+    let rest = &input[integer..];
+    if rest.starts_with('.') && rest[1..].starts_with(|c: char| c.is_ascii_digit()) {
+        (TokenKind::Real, integer + 1 + digits(&rest[1..]))
+    } else {
+        (TokenKind::Int, integer)
+    }
+}
+
+fn error<'a>(position: &'a str, label: &'static str) -> VerboseError<&'a str> {
+    VerboseError {
+        errors: vec![(position, VerboseErrorKind::Context(label))],
+    }
+}
+
+/// The tokens that are left to parse, which is the input type of the nom parsers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tokens<'a> {
+    tokens: &'a [Token<'a>],
+}
+
+impl<'a> Tokens<'a> {
+    /// Wraps the result of `tokenize`, which must end with the `Eof` token.
+    pub fn new(tokens: &'a [Token<'a>]) -> Tokens<'a> {
+        debug_assert!(tokens.last().is_some_and(|t| t.kind == TokenKind::Eof));
+        Tokens { tokens }
+    }
+
+    /// The first token, which may be a comment.
+    pub fn first(&self) -> &'a Token<'a> {
+        &self.tokens[0]
+    }
+
+    /// Splits off the first token; the `Eof` token is never consumed.
+    pub fn split_first(self) -> (Tokens<'a>, &'a Token<'a>) {
+        let first = self.first();
+        if first.kind == TokenKind::Eof {
+            (self, first)
+        } else {
+            (
+                Tokens {
+                    tokens: &self.tokens[1..],
+                },
+                first,
+            )
+        }
+    }
+
+    /// Splits off the first token that is not a comment.
+    pub fn next_token(self) -> (Tokens<'a>, &'a Token<'a>) {
+        let mut input = self;
+        loop {
+            let (rest, token) = input.split_first();
+            if token.kind != TokenKind::Comment {
+                return (rest, token);
+            }
+            input = rest;
+        }
+    }
+
+    /// Byte offset in the source text of the first token left.
+    pub fn offset(&self) -> usize {
+        self.first().span.start
+    }
+}
+
+impl InputLength for Tokens<'_> {
+    fn input_len(&self) -> usize {
+        self.tokens.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(input: &str) -> Vec<(TokenKind, &str)> {
+        tokenize(input)
+            .unwrap()
+            .into_iter()
+            .map(|t| (t.kind, t.text))
+            .collect()
+    }
+
+    #[test]
+    fn test_tokenize_statement() {
+        assert_eq!(
+            kinds("var x1 = 2.5 ** -3; # done"),
+            vec![
+                (TokenKind::Keyword, "var"),
+                (TokenKind::Identifier, "x1"),
+                (TokenKind::Symbol, "="),
+                (TokenKind::Real, "2.5"),
+                (TokenKind::Symbol, "**"),
+                (TokenKind::Symbol, "-"),
+                (TokenKind::Int, "3"),
+                (TokenKind::Symbol, ";"),
+                (TokenKind::Comment, "# done"),
+                (TokenKind::Eof, ""),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_reserves_keywords_only_as_whole_words() {
+        assert_eq!(
+            kinds("ret return ending"),
+            vec![
+                (TokenKind::Keyword, "ret"),
+                (TokenKind::Identifier, "return"),
+                (TokenKind::Identifier, "ending"),
+                (TokenKind::Eof, ""),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_strings_and_unknown_characters() {
+        assert_eq!(
+            kinds("\"# /* not a comment\" @ 1."),
+            vec![
+                (TokenKind::Str, "\"# /* not a comment\""),
+                (TokenKind::Unknown, "@"),
+                (TokenKind::Int, "1"),
+                (TokenKind::Unknown, "."),
+                (TokenKind::Eof, ""),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_spans() {
+        let tokens = tokenize("a /* b */\n  <= 10").unwrap();
+        let spans: Vec<(usize, usize)> =
+            tokens.iter().map(|t| (t.span.start, t.span.end)).collect();
+        assert_eq!(spans, vec![(0, 1), (2, 9), (12, 14), (15, 17), (17, 17)]);
+    }
+
+    #[test]
+    fn test_tokenize_unterminated() {
+        assert!(tokenize("\"abc").is_err());
+        assert!(tokenize("x /* comment").is_err());
+    }
+}
@@ -1,38 +1,29 @@
+#[cfg(test)]
+pub mod generators;
 pub mod keywords;
+pub mod lexer;
+pub mod operators;
 pub mod parser;
 pub mod parser_common;
+pub mod parser_error;
 pub mod parser_expr;
 pub mod parser_stmt;
 pub mod parser_type;
-use nom::{
-    IResult,
-    character::complete::{char, multispace0},
-    combinator::{map, opt},
-    multi::separated_list0,
-    sequence::tuple,
-};
+pub mod pretty_printer;
 
 use crate::ir::ast::Statement;
-use crate::parser::parser_common::SEMICOLON_CHAR;
+use crate::parser::parser_common::{ParseResult, from_text};
 
 pub use parser_common::keyword;
 pub use parser_expr::{parse_expression, parse_lambda};
-pub use parser_stmt::{parse_formal_argument, parse_return_statement, parse_statement};
+pub use parser_stmt::{
+    parse_formal_argument, parse_return_statement, parse_statement, parse_statement_list,
+};
 pub use parser_type::parse_type;
+pub use pretty_printer::{print_expression, print_program, print_statement, print_type};
 
-pub fn parse(input: &str) -> IResult<&str, Vec<Statement>> {
-    map(
-        tuple((
-            multispace0,
-            separated_list0(
-                tuple((multispace0, char(SEMICOLON_CHAR), multispace0)),
-                parse_statement,
-            ),
-            opt(tuple((multispace0, char(SEMICOLON_CHAR)))), // optional trailing semicolon
-            multispace0,
-        )),
-        |(_, statements, _, _)| statements,
-    )(input)
+pub fn parse(input: &str) -> ParseResult<'_, Vec<Statement>> {
+    from_text(input, parser_stmt::statement_list)
 }
 
 pub use parser::parse_chained_blocks;
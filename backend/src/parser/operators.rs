@@ -0,0 +1,178 @@
+//! Precedence table of the expression operators.
+//!
+//! `parse_expression` is a Pratt parser driven by this table, and the pretty
+//! printer reads the same table to decide where parentheses are needed, so the
+//! two can not drift apart.
+
+use crate::ir::ast::{Comparison, Expression};
+
+// Binding power of each expression form, from loosest to tightest
+pub const OR_PRECEDENCE: u8 = 1;
+pub const AND_PRECEDENCE: u8 = 2;
+pub const NOT_PRECEDENCE: u8 = 3;
+pub const RELATIONAL_PRECEDENCE: u8 = 4;
+pub const ADDITIVE_PRECEDENCE: u8 = 5;
+pub const MULTIPLICATIVE_PRECEDENCE: u8 = 6;
+pub const NEGATION_PRECEDENCE: u8 = 7;
+pub const POWER_PRECEDENCE: u8 = 8;
+pub const ATOM_PRECEDENCE: u8 = 9;
+
+pub const OR_OPERATOR: &str = "or";
+pub const AND_OPERATOR: &str = "and";
+pub const NOT_OPERATOR: &str = "not";
+pub const NEGATION_OPERATOR: &str = "-";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Associativity {
+    /// `a - b - c` is `(a - b) - c`
+    Left,
+    /// `a ** b ** c` is `a ** (b ** c)`
+    Right,
+    /// `a < b < c` is `a < b and b < c`
+    Chained,
+}
+
+pub struct BinaryOperator {
+    pub symbol: &'static str,
+    /// Whether the symbol is a keyword (e.g. `and`) rather than a symbol token
+    pub is_keyword: bool,
+    pub precedence: u8,
+    pub associativity: Associativity,
+    /// Minimum binding power of the right operand
+    pub right_precedence: u8,
+    pub build: fn(Box<Expression>, Box<Expression>) -> Expression,
+}
+
+impl BinaryOperator {
+    /// Minimum binding power of the left operand
+    pub fn left_precedence(&self) -> u8 {
+        match self.associativity {
+            Associativity::Left => self.precedence,
+            Associativity::Right | Associativity::Chained => self.precedence + 1,
+        }
+    }
+}
+
+const fn operator(
+    symbol: &'static str,
+    precedence: u8,
+    associativity: Associativity,
+    build: fn(Box<Expression>, Box<Expression>) -> Expression,
+) -> BinaryOperator {
+    let right_precedence = match associativity {
+        Associativity::Right => precedence,
+        Associativity::Left | Associativity::Chained => precedence + 1,
+    };
+    BinaryOperator {
+        symbol,
+        is_keyword: false,
+        precedence,
+        associativity,
+        right_precedence,
+        build,
+    }
+}
+
+/// Every binary operator of the expression grammar.
+#[rustfmt::skip]
+pub const BINARY_OPERATORS: &[BinaryOperator] = &[
+    BinaryOperator {
+        is_keyword: true,
+        ..operator(OR_OPERATOR, OR_PRECEDENCE, Associativity::Left, Expression::Or)
+    },
+    BinaryOperator {
+        is_keyword: true,
+        ..operator(AND_OPERATOR, AND_PRECEDENCE, Associativity::Left, Expression::And)
+    },
+    operator("==", RELATIONAL_PRECEDENCE, Associativity::Chained, Expression::EQ),
+    operator("!=", RELATIONAL_PRECEDENCE, Associativity::Chained, Expression::NEQ),
+    operator("<=", RELATIONAL_PRECEDENCE, Associativity::Chained, Expression::LTE),
+    operator("<", RELATIONAL_PRECEDENCE, Associativity::Chained, Expression::LT),
+    operator(">=", RELATIONAL_PRECEDENCE, Associativity::Chained, Expression::GTE),
+    operator(">", RELATIONAL_PRECEDENCE, Associativity::Chained, Expression::GT),
+    operator("+", ADDITIVE_PRECEDENCE, Associativity::Left, Expression::Add),
+    operator("-", ADDITIVE_PRECEDENCE, Associativity::Left, Expression::Sub),
+    // The exponent may be negated, as in `2 ** -1`
+    BinaryOperator {
+        right_precedence: NEGATION_PRECEDENCE,
+        ..operator("**", POWER_PRECEDENCE, Associativity::Right, Expression::Pow)
+    },
+    operator("*", MULTIPLICATIVE_PRECEDENCE, Associativity::Left, Expression::Mul),
+    operator("/", MULTIPLICATIVE_PRECEDENCE, Associativity::Left, Expression::Div),
+    operator("%", MULTIPLICATIVE_PRECEDENCE, Associativity::Left, Expression::Mod),
+];
+
+fn find_operator(symbol: &str) -> &'static BinaryOperator {
+    BINARY_OPERATORS
+        .iter()
+        .find(|op| op.symbol == symbol)
+        .expect("every binary expression has an entry in BINARY_OPERATORS")
+}
+
+/// Symbol of each comparison of a chain such as `a < b <= c`.
+const COMPARISONS: &[(Comparison, &str)] = &[
+    (Comparison::EQ, "=="),
+    (Comparison::NEQ, "!="),
+    (Comparison::LTE, "<="),
+    (Comparison::LT, "<"),
+    (Comparison::GTE, ">="),
+    (Comparison::GT, ">"),
+];
+
+/// Comparison of a chain written with a chained operator.
+pub fn comparison(op: &BinaryOperator) -> Comparison {
+    COMPARISONS
+        .iter()
+        .find(|(_, symbol)| *symbol == op.symbol)
+        .map(|(comparison, _)| *comparison)
+        .expect("every chained operator has an entry in COMPARISONS")
+}
+
+/// Operator of a comparison of a chain.
+pub fn comparison_operator(comparison: Comparison) -> &'static BinaryOperator {
+    let (_, symbol) = COMPARISONS
+        .iter()
+        .find(|(c, _)| *c == comparison)
+        .expect("every comparison has an entry in COMPARISONS");
+    find_operator(symbol)
+}
+
+/// Splits a binary expression into its operator and operands.
+pub fn binary_operator(
+    exp: &Expression,
+) -> Option<(&'static BinaryOperator, &Expression, &Expression)> {
+    let (symbol, left, right) = match exp {
+        Expression::Or(l, r) => (OR_OPERATOR, l, r),
+        Expression::And(l, r) => (AND_OPERATOR, l, r),
+        Expression::EQ(l, r) => ("==", l, r),
+        Expression::NEQ(l, r) => ("!=", l, r),
+        Expression::LTE(l, r) => ("<=", l, r),
+        Expression::LT(l, r) => ("<", l, r),
+        Expression::GTE(l, r) => (">=", l, r),
+        Expression::GT(l, r) => (">", l, r),
+        Expression::Add(l, r) => ("+", l, r),
+        Expression::Sub(l, r) => ("-", l, r),
+        Expression::Pow(l, r) => ("**", l, r),
+        Expression::Mul(l, r) => ("*", l, r),
+        Expression::Div(l, r) => ("/", l, r),
+        Expression::Mod(l, r) => ("%", l, r),
+        _ => return None,
+    };
+    Some((find_operator(symbol), left.as_ref(), right.as_ref()))
+}
+
+/// Binding power of an expression, i.e. the loosest operator at its root.
+pub fn precedence(exp: &Expression) -> u8 {
+    match exp {
+        Expression::Not(_) => NOT_PRECEDENCE,
+        Expression::Neg(_) => NEGATION_PRECEDENCE,
+        // Negative literals are written with a leading `-`, so they bind like a negation
+        Expression::CInt(value) if *value < 0 => NEGATION_PRECEDENCE,
+        Expression::CReal(value) if value.is_sign_negative() => NEGATION_PRECEDENCE,
+        Expression::Chain(..) => RELATIONAL_PRECEDENCE,
+        _ => match binary_operator(exp) {
+            Some((op, _, _)) => op.precedence,
+            None => ATOM_PRECEDENCE,
+        },
+    }
+}
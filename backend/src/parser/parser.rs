@@ -1,42 +1,218 @@
 use crate::ir::ast::{Expression, FormalArgument, Function, Statement, Type};
 use crate::models::{Block2, Blocks2, Input2, NextBlock2, Workspace2};
-use crate::parser::parse_type;
-use crate::parser::parser_common::separator;
-use crate::parser::parser_common::{COMMA_CHAR, COMMA_SYMBOL, identifier};
-use crate::parser::parser_expr::parse_actual_arguments;
+use crate::parser::parser_common::ParseResult;
+use crate::parser::parser_common::{from_text, identifier};
+use crate::parser::parser_error::{describe_parse_error, describe_unparsed, error_position};
 use crate::parser::parser_expr::parse_expression;
+use crate::parser::parser_expr::parse_expression_list;
 use crate::parser::parser_stmt::parse_formal_argument;
+use crate::parser::parser_type::parse_type_list;
 use actix_web::{HttpResponse, Responder, post, web};
 use nom::{Err, Finish};
 use nom::{
     IResult,
     branch::alt,
     bytes::complete::{tag, take_while},
-    character::complete::{alpha1, char, digit1, multispace0, multispace1},
+    character::complete::{alpha1, char, digit1, multispace1},
     combinator::{map, map_res, not, opt, peek, recognize, value, verify},
     error::Error,
-    multi::{fold_many0, many0, separated_list0},
-    sequence::{delimited, pair, preceded, terminated, tuple},
+    multi::{fold_many0, many0},
+    sequence::{pair, preceded, terminated, tuple},
 };
 use serde_json;
+use std::fmt;
 use std::fmt::format;
 use std::io::Write;
 use std::str::FromStr;
 use std::{fs::File, process::Output};
 
-pub fn parse_chained_blocks(block: &Block2) -> Result<Statement, String> {
+/// A parse error found while converting a Blockly workspace, located by the id
+/// of the block that holds the malformed text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockParseError {
+    pub block_id: String,
+    pub message: String,
+    /// The input that nom left unconsumed, when the error comes from a text field
+    pub remainder: Option<String>,
+}
+
+impl BlockParseError {
+    fn new(block: &Block2, message: String) -> BlockParseError {
+        BlockParseError {
+            block_id: block.id.clone(),
+            message,
+            remainder: None,
+        }
+    }
+
+    fn with_remainder(block: &Block2, message: String, remainder: &str) -> BlockParseError {
+        BlockParseError {
+            block_id: block.id.clone(),
+            message,
+            remainder: Some(remainder.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for BlockParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[Block {}] {}", self.block_id, self.message)?;
+        match &self.remainder {
+            Some(rest) if !rest.is_empty() => write!(f, " (unparsed input: `{}`)", rest),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Converts a chain of blocks into a `Statement::Block`. The conversion does not
+/// stop at the first malformed block: every error of the chain, including those
+/// inside nested bodies, is reported in the order the blocks appear.
+pub fn parse_chained_blocks(block: &Block2) -> Result<Statement, Vec<BlockParseError>> {
+    let mut errors: Vec<BlockParseError> = Vec::new();
+    let statement = parse_chain(block, &mut errors);
+    if errors.is_empty() {
+        Ok(statement)
+    } else {
+        Err(errors)
+    }
+}
+
+// Malformed blocks are left out of the returned statement and recorded in `errors`
+fn parse_chain(block: &Block2, errors: &mut Vec<BlockParseError>) -> Statement {
     let mut current_block = Some(block);
     let mut statements_vector: Vec<Statement> = Vec::new();
 
     while let Some(block_iterator) = current_block {
-        let statement: Statement = parse_single_block(block_iterator)?;
-        statements_vector.push(statement);
+        // A block is reported before the errors found in its nested bodies
+        let first_nested_error = errors.len();
+        match parse_single_block(block_iterator, errors) {
+            Ok(statement) => statements_vector.push(statement),
+            Err(error) => errors.insert(first_nested_error, error),
+        }
         current_block = block_iterator.next.as_ref().map(|next| next.block.as_ref());
     }
-    return Ok(Statement::Block(statements_vector));
+    return Statement::Block(statements_vector);
+}
+
+// Runs `parser` over the whole text of a field, explaining where and why it failed
+fn parse_complete<'a, O>(
+    block: &Block2,
+    text: &'a str,
+    context: &str,
+    mut parser: impl FnMut(&'a str) -> ParseResult<'a, O>,
+) -> Result<O, BlockParseError> {
+    let message = |reason: String| format!("Parsing error on {} `{}`: {}", context, text, reason);
+    match parser(text) {
+        Ok(("", value)) => Ok(value),
+        Ok((rest, _)) => Err(BlockParseError::with_remainder(
+            block,
+            message(describe_unparsed(text, rest)),
+            rest,
+        )),
+        Err(Err::Error(e)) | Err(Err::Failure(e)) => {
+            let reason = message(describe_parse_error(text, &e));
+            match error_position(&e) {
+                Some(rest) => Err(BlockParseError::with_remainder(block, reason, rest)),
+                None => Err(BlockParseError::new(block, reason)),
+            }
+        }
+        Err(Err::Incomplete(_)) => Err(BlockParseError::new(
+            block,
+            message("incomplete input".to_string()),
+        )),
+    }
+}
+
+fn parse_complete_expression(
+    block: &Block2,
+    text: &str,
+    context: &str,
+) -> Result<Expression, BlockParseError> {
+    parse_complete(block, text, context, parse_expression)
+}
+
+fn parse_complete_identifier(
+    block: &Block2,
+    text: &str,
+    context: &str,
+) -> Result<String, BlockParseError> {
+    parse_complete(block, text, context, |text| from_text(text, identifier))
+}
+
+// Parses a nested chain of blocks (e.g. the body of an if), recording its errors
+fn parse_body(
+    block: &Block2,
+    input_name: &str,
+    errors: &mut Vec<BlockParseError>,
+) -> Option<Statement> {
+    block
+        .inputs
+        .as_ref()
+        .and_then(|i| i.get(input_name))
+        .and_then(|input| input.block.as_ref())
+        .map(|body| parse_chain(body, errors))
+}
+
+// The text typed in the expression shadow block of an input
+fn shadow_text<'a>(block: &'a Block2, input_name: &str) -> Option<&'a String> {
+    block
+        .inputs
+        .as_ref()
+        .and_then(|i| i.get(input_name))
+        .and_then(|input| input.shadow.as_ref())
+        .and_then(|shadow_block| shadow_block.fields.as_ref())
+        .and_then(|fields| fields.get("TEXT"))
+}
+
+// Parses the chain of case blocks of a when block. A malformed case is
+// recorded in `errors` and left out.
+fn parse_cases(block: &Block2, errors: &mut Vec<BlockParseError>) -> Vec<(Expression, Statement)> {
+    let mut cases = Vec::new();
+    let mut current_block = block
+        .inputs
+        .as_ref()
+        .and_then(|i| i.get("CASES"))
+        .and_then(|input| input.block.as_deref());
+
+    while let Some(case_block) = current_block {
+        let first_nested_error = errors.len();
+        match parse_case(case_block, errors) {
+            Ok(case) => cases.push(case),
+            Err(error) => errors.insert(first_nested_error, error),
+        }
+        current_block = case_block.next.as_ref().map(|next| next.block.as_ref());
+    }
+    cases
 }
 
-fn parse_single_block(block: &Block2) -> Result<Statement, String> {
+fn parse_case(
+    block: &Block2,
+    errors: &mut Vec<BlockParseError>,
+) -> Result<(Expression, Statement), BlockParseError> {
+    if block.r#type != "case_block" {
+        return Err(BlockParseError::new(
+            block,
+            "Only case blocks can be placed in the cases of a when block".to_string(),
+        ));
+    }
+    match shadow_text(block, "VALUE") {
+        Some(value) if !value.is_empty() => {
+            let value_exp = parse_complete_expression(block, value, "case value");
+            let body = parse_body(block, "CASE_BODY", errors);
+            Ok((value_exp?, body.unwrap_or(Statement::Block(vec![]))))
+        }
+        _ => Err(BlockParseError::new(
+            block,
+            "Case value is empty".to_string(),
+        )),
+    }
+}
+
+fn parse_single_block(
+    block: &Block2,
+    errors: &mut Vec<BlockParseError>,
+) -> Result<Statement, BlockParseError> {
+    let error = |message: &str| BlockParseError::new(block, message.to_string());
     match block.r#type.as_str() {
         "print_block" => {
             if let Some(expression_string) = block
@@ -52,14 +228,11 @@ fn parse_single_block(block: &Block2) -> Result<Statement, String> {
                         "".to_string(),
                     ))));
                 }
-                let (rest, assignment_exp) = parse_expression(expression_string)
-                    .map_err(|_e| format!("Parsing error on expression: {}", expression_string))?;
-                if !rest.is_empty() {
-                    return Err(format!(
-                        "Parsing error on print statement expression: {}",
-                        expression_string
-                    ));
-                }
+                let assignment_exp = parse_complete_expression(
+                    block,
+                    expression_string,
+                    "print statement expression",
+                )?;
                 // Retorna OK com o Statement usando variable_string e assignment_exp
                 return Ok(Statement::Print(Box::new(assignment_exp)));
             } else {
@@ -76,15 +249,10 @@ fn parse_single_block(block: &Block2) -> Result<Statement, String> {
                 .and_then(|fields| fields.get("VARIABLE"))
             {
                 if variable_name.is_empty() {
-                    return Err("Variable name cannot be empty".to_string());
+                    return Err(error("Variable name cannot be empty"));
                 }
 
-                let (rest, variable_string) =
-                    delimited(multispace0, identifier, multispace0)(variable_name)
-                        .map_err(|_e| format!("Parsing error on variable: {}", variable_name))?;
-                if !rest.is_empty() {
-                    return Err(format!("Parsing error on variable: {}", variable_name));
-                }
+                let variable_string = parse_complete_identifier(block, variable_name, "variable")?;
 
                 if let Some(type_name) = block.fields.as_ref().and_then(|fields| fields.get("TYPE"))
                 {
@@ -93,17 +261,17 @@ fn parse_single_block(block: &Block2) -> Result<Statement, String> {
                         "FLOAT" => Expression::CReal(0.0),
                         "STRING" => Expression::CString(String::new()),
                         "BOOL" => Expression::CTrue,
-                        _ => return Err("non-valid type".to_string()),
+                        _ => return Err(error("non-valid type")),
                     };
                     return Ok(Statement::VarDeclaration(
-                        *variable_string,
-                        Box::new(initial_expr),
+                        variable_string,
+                        Box::new(Expression::Default(Box::new(initial_expr))),
                     ));
                 } else {
-                    return Err("Variable type field missing".to_string());
+                    return Err(error("Variable type field missing"));
                 }
             } else {
-                return Err("Variable name field missing".to_string());
+                return Err(error("Variable name field missing"));
             }
         }
 
@@ -114,15 +282,11 @@ fn parse_single_block(block: &Block2) -> Result<Statement, String> {
                 .and_then(|fields| fields.get("VARIABLE"))
             {
                 if variable_name.is_empty() {
-                    return Err("Variable name cannot be empty".to_string());
+                    return Err(error("Variable name cannot be empty"));
                 }
 
-                let (rest, variable_string) =
-                    delimited(multispace0, identifier, multispace0)(variable_name)
-                        .map_err(|_e| format!("Parsing error on variable: {}", variable_name))?;
-                if !rest.is_empty() {
-                    return Err(format!("Parsing error on variable: {}", variable_name));
-                }
+                // Both fields are checked, so that a typo in each is reported at once
+                let variable_string = parse_complete_identifier(block, variable_name, "variable");
 
                 if let Some(expression_string) = block
                     .inputs
@@ -132,38 +296,37 @@ fn parse_single_block(block: &Block2) -> Result<Statement, String> {
                     .and_then(|shadow_block| shadow_block.fields.as_ref())
                     .and_then(|fields| fields.get("TEXT"))
                 {
-                    //println!("Expression string: {}", expression_string);
                     if expression_string.is_empty() {
-                        return Err("Variable assignment requires non-empty expression".to_string());
+                        return Err(error("Variable assignment requires non-empty expression"));
                     }
 
-                    let (rest, assignment_exp) =
-                        parse_expression(expression_string).map_err(|_e| {
-                            format!("Parsing error on expression: {}", expression_string)
-                        })?;
-                    if !rest.is_empty() {
-                        //println!("assignment_exp: {:?}", assignment_exp);
-                        //println!("rest: {}", rest);
-                        return Err(format!(
-                            "Parsing error on expression: {}",
-                            expression_string
-                        ));
-                    }
+                    let assignment_exp =
+                        parse_complete_expression(block, expression_string, "expression");
+                    let (variable_string, assignment_exp) = match (variable_string, assignment_exp)
+                    {
+                        (Ok(name), Ok(exp)) => (name, exp),
+                        // The returned error goes before those recorded meanwhile
+                        (Err(name_error), Err(exp_error)) => {
+                            errors.push(exp_error);
+                            return Err(name_error);
+                        }
+                        (Err(e), _) | (_, Err(e)) => return Err(e),
+                    };
 
                     // Retorna OK com o Statement usando variable_string e assignment_exp
                     return Ok(Statement::Assignment(
-                        *variable_string,
+                        variable_string,
                         Box::new(assignment_exp),
                     ));
                 } else {
-                    return Err("Variable assignment requires non-empty expression".to_string());
+                    return Err(error("Variable assignment requires non-empty expression"));
                 }
             } else {
-                return Err("Variable name field missing".to_string());
+                return Err(error("Variable name field missing"));
             }
         }
 
-        "if_else_block" => {
+        "if_else_block" | "if_elif_else_block" => {
             if let Some(condition) = block
                 .inputs
                 .as_ref()
@@ -173,48 +336,73 @@ fn parse_single_block(block: &Block2) -> Result<Statement, String> {
                 .and_then(|fields| fields.get("TEXT"))
             {
                 if condition.is_empty() {
-                    return Err("If condition is empty".to_string());
-                }
-                let (rest, condition_exp) = parse_expression(condition)
-                    .map_err(|e| format!("Parsing error on condition: {}", condition))?;
-                if !rest.is_empty() {
-                    return Err(format!("Parsing error on condition: {}", condition));
+                    return Err(error("If condition is empty"));
                 }
-                if let Some(if_body) = block
-                    .inputs
-                    .as_ref()
-                    .and_then(|i| i.get("IF_BODY"))
-                    .and_then(|input| input.block.as_ref())
+                // The bodies are parsed even if the condition is malformed, to report their errors too
+                let condition_exp = parse_complete_expression(block, condition, "condition");
+                let then_block = parse_body(block, "IF_BODY", errors);
+                // The `elif` branches are numbered inputs, from ELIF_CONDITION0 and ELIF_BODY0 on
+                let mut elifs = Vec::new();
+                while let Some(elif_condition) =
+                    shadow_text(block, &format!("ELIF_CONDITION{}", elifs.len()))
                 {
-                    let then_block = parse_chained_blocks(if_body)?;
-
-                    if let Some(else_body) = block
-                        .inputs
-                        .as_ref()
-                        .and_then(|i| i.get("ELSE_BODY"))
-                        .and_then(|input| input.block.as_ref())
-                    {
-                        let else_block = parse_chained_blocks(else_body)?;
-                        return Ok(Statement::IfThenElse(
-                            Box::new(condition_exp),
-                            Box::new(then_block),
-                            Some(Box::new(else_block)),
-                        ));
+                    let elif_condition = if elif_condition.is_empty() {
+                        Err(error("Elif condition is empty"))
                     } else {
-                        return Ok(Statement::IfThenElse(
-                            Box::new(condition_exp),
-                            Box::new(then_block),
-                            None,
-                        ));
-                    }
+                        parse_complete_expression(block, elif_condition, "elif condition")
+                    };
+                    let elif_block =
+                        parse_body(block, &format!("ELIF_BODY{}", elifs.len()), errors);
+                    elifs.push((elif_condition, elif_block));
+                }
+                let mut else_block = parse_body(block, "ELSE_BODY", errors);
+                let condition_exp = condition_exp?;
+                let elifs = elifs
+                    .into_iter()
+                    .map(|(condition, body)| condition.map(|condition| (condition, body)))
+                    .collect::<Result<Vec<_>, _>>()?;
+                // Each elif becomes an if nested in the else branch of the previous one
+                for (elif_condition, elif_block) in elifs.into_iter().rev() {
+                    else_block = Some(Statement::IfThenElse(
+                        Box::new(elif_condition),
+                        Box::new(elif_block.unwrap_or(Statement::Block(vec![]))),
+                        else_block.map(Box::new),
+                    ));
+                }
+                if let Some(then_block) = then_block {
+                    return Ok(Statement::IfThenElse(
+                        Box::new(condition_exp),
+                        Box::new(then_block),
+                        else_block.map(Box::new),
+                    ));
                 } else {
-                    return Err("Non-existent if-body".to_string());
+                    return Err(error("Non-existent if-body"));
+                }
+            } else {
+                return Err(error("If condition is empty"));
+            }
+        }
+
+        "when_block" => {
+            if let Some(value) = shadow_text(block, "VALUE") {
+                if value.is_empty() {
+                    return Err(error("When value is empty"));
                 }
+                let value_exp = parse_complete_expression(block, value, "when value");
+                let cases = parse_cases(block, errors);
+                let default_block = parse_body(block, "DEFAULT_BODY", errors);
+                return Ok(Statement::When(
+                    Box::new(value_exp?),
+                    cases,
+                    default_block.map(Box::new),
+                ));
             } else {
-                return Err("If condition is empty".to_string());
+                return Err(error("When value is empty"));
             }
         }
 
+        "case_block" => Err(error("A case block must be placed inside a when block")),
+
         "while_block" => {
             if let Some(condition) = block
                 .inputs
@@ -225,35 +413,30 @@ fn parse_single_block(block: &Block2) -> Result<Statement, String> {
                 .and_then(|fields| fields.get("TEXT"))
             {
                 if condition.is_empty() {
-                    return Err("While condition is empty".to_string());
-                }
-                let (rest, condition_exp) = parse_expression(condition)
-                    .map_err(|e| format!("Parsing error on condition: {}", condition))?;
-                if !rest.is_empty() {
-                    return Err(format!("Parsing error on condition: {}", condition));
+                    return Err(error("While condition is empty"));
                 }
-                if let Some(while_body) = block
-                    .inputs
-                    .as_ref()
-                    .and_then(|i| i.get("WHILE_BODY"))
-                    .and_then(|input| input.block.as_ref())
-                {
-                    let while_block = parse_chained_blocks(while_body)?;
+                let condition_exp = parse_complete_expression(block, condition, "condition");
+                let while_block = parse_body(block, "WHILE_BODY", errors);
+                let condition_exp = condition_exp?;
+                if let Some(while_block) = while_block {
                     return Ok(Statement::While(
                         Box::new(condition_exp),
                         Box::new(while_block),
                     ));
                 } else {
-                    return Err("Non-existent while-body".to_string());
+                    return Err(error("Non-existent while-body"));
                 }
             } else {
-                return Err("While condition is empty".to_string());
+                return Err(error("While condition is empty"));
             }
         }
         "function_declaration_block" => {
             let mut func: Function = Function::new();
-            let mut func_body: Option<Statement> = None;
-            let mut final_return_statement = Statement::Return(Box::new(Expression::CVoid));
+            // The body is parsed first, so that its errors are reported even if the header is malformed
+            let func_body = parse_body(block, "FUNCTION_BODY", errors);
+            // A function without a return expression ends with its body, which the
+            // type checker only allows for void functions
+            let mut final_return_statement = None;
             if let Some(return_type) = block
                 .fields
                 .as_ref()
@@ -265,10 +448,15 @@ fn parse_single_block(block: &Block2) -> Result<Statement, String> {
                     "FLOAT" => Type::TReal,
                     "STRING" => Type::TString,
                     "BOOL" => Type::TBool,
-                    _ => return Err(format!("Unknown return type: {}", return_type)),
+                    _ => {
+                        return Err(BlockParseError::new(
+                            block,
+                            format!("Unknown return type: {}", return_type),
+                        ));
+                    }
                 }
             } else {
-                return Err("Function return type cannot be empty".to_string());
+                return Err(error("Function return type cannot be empty"));
             }
             if let Some(func_name) = block
                 .fields
@@ -276,17 +464,11 @@ fn parse_single_block(block: &Block2) -> Result<Statement, String> {
                 .and_then(|fields| fields.get("FUNCTION_NAME"))
             {
                 if func_name.is_empty() {
-                    return Err("Function was not named".to_string());
-                }
-                let (rest, func_string) =
-                    delimited(multispace0, identifier, multispace0)(func_name)
-                        .map_err(|_e| format!("Parsing error on function: {}", func_name))?;
-                if !rest.is_empty() {
-                    return Err(format!("Parsing error on function: {}", func_name));
+                    return Err(error("Function was not named"));
                 }
-                func.name = *func_string;
+                func.name = parse_complete_identifier(block, func_name, "function")?;
             } else {
-                return Err("Function was not named".to_string());
+                return Err(error("Function was not named"));
             }
             if let Some(formal_argument_block) = block
                 .inputs
@@ -310,7 +492,10 @@ fn parse_single_block(block: &Block2) -> Result<Statement, String> {
                                 "STRING" => Type::TString,
                                 "BOOL" => Type::TBool,
                                 _ => {
-                                    return Err(format!("Unknown argument type: {}", arg_type_str));
+                                    return Err(BlockParseError::new(
+                                        current_block,
+                                        format!("Unknown argument type: {}", arg_type_str),
+                                    ));
                                 }
                             };
                             let formal_argument = FormalArgument {
@@ -330,27 +515,28 @@ fn parse_single_block(block: &Block2) -> Result<Statement, String> {
                             })
                         {
                             if arg_func_name.is_empty() {
-                                return Err("Argument name cannot be empty".to_string());
+                                return Err(error("Argument name cannot be empty"));
                             }
-                            let (rest1, arg_func_params_type): (&str, Vec<Type>) =
-                                delimited(
-                                    multispace0,
-                                    separated_list0(separator(COMMA_SYMBOL), parse_type),
-                                    multispace0,
-                                )(arg_func_params_type)
-                                .map_err(|e| {
-                                    format!("Erro ao parsear argumentos funcionais: {:?}", e)
+                            let (rest, arg_func_params_type): (&str, Vec<Type>) =
+                                parse_type_list(arg_func_params_type).map_err(|e| {
+                                    BlockParseError::new(
+                                        current_block,
+                                        format!("Erro ao parsear argumentos funcionais: {:?}", e),
+                                    )
                                 })?;
-                            let (rest2, arg_func_name) =
-                                delimited(multispace0, identifier, multispace0)(&arg_func_name)
-                                    .map_err(|_e| {
-                                        format!("Parsing error on variable: {}", arg_func_name)
-                                    })?;
-                            if !(rest1.is_empty() && rest2.is_empty()) {
-                                //println!("rest1: {}, rest2: {}", rest1, rest2));
-                                return Err(format!(
-                                    "Parsing Error on argument {} of function {}",
-                                    arg_func_name, func.name
+                            let arg_func_name = parse_complete_identifier(
+                                current_block,
+                                arg_func_name,
+                                "variable",
+                            )?;
+                            if !rest.is_empty() {
+                                return Err(BlockParseError::with_remainder(
+                                    current_block,
+                                    format!(
+                                        "Parsing Error on argument {} of function {}",
+                                        arg_func_name, func.name
+                                    ),
+                                    rest,
                                 ));
                             }
                             let arg_func_return_type = match arg_func_return_type.as_str() {
@@ -359,9 +545,9 @@ fn parse_single_block(block: &Block2) -> Result<Statement, String> {
                                 "STRING" => Type::TString,
                                 "BOOL" => Type::TBool,
                                 _ => {
-                                    return Err(format!(
-                                        "Unknown argument type: {}",
-                                        arg_func_return_type
+                                    return Err(BlockParseError::new(
+                                        current_block,
+                                        format!("Unknown argument type: {}", arg_func_return_type),
                                     ));
                                 }
                             };
@@ -388,15 +574,6 @@ fn parse_single_block(block: &Block2) -> Result<Statement, String> {
                 }
             } else {
             }
-            if let Some(func_block) = block
-                .inputs
-                .as_ref()
-                .and_then(|input| input.get("FUNCTION_BODY"))
-                .and_then(|input| input.block.as_ref())
-            {
-                func_body = Some(parse_chained_blocks(func_block)?); //func_block agora é do tipo Some(Statement::Block())
-            } else {
-            }
             if let Some(return_str) = block
                 .inputs
                 .as_ref()
@@ -406,32 +583,30 @@ fn parse_single_block(block: &Block2) -> Result<Statement, String> {
                 .and_then(|fields| fields.get("TEXT"))
             {
                 if !return_str.is_empty() {
-                    let (rest, return_exp) = parse_expression(return_str).map_err(|error| {
-                        format!(
-                            "Parsing error on return statement {}: {:?}",
-                            return_str, error
-                        )
-                    })?;
-                    if !rest.is_empty() {
-                        return Err(format!("Parsing error on return_str: {}", return_str));
-                    }
-                    final_return_statement = Statement::Return(Box::new(return_exp));
+                    let return_exp =
+                        parse_complete_expression(block, return_str, "return statement")?;
+                    final_return_statement = Some(Statement::Return(Box::new(return_exp)));
                 } else {
                 }
             } else {
             }
             func.body = match func_body {
                 Some(Statement::Block(mut statement_vec)) => {
-                    statement_vec.push(final_return_statement);
+                    statement_vec.extend(final_return_statement);
                     Some(Box::new(Statement::Block(statement_vec)))
                 }
-                None => Some(Box::new(Statement::Block(vec![final_return_statement]))),
+                None => Some(Box::new(Statement::Block(
+                    final_return_statement.into_iter().collect(),
+                ))),
                 Some(_) => None,
             };
             if func.body.is_none() {
-                return Err(format!(
-                    "Parse Error on function {}: function body needs to be a Statement::Block",
-                    func.name
+                return Err(BlockParseError::new(
+                    block,
+                    format!(
+                        "Parse Error on function {}: function body needs to be a Statement::Block",
+                        func.name
+                    ),
                 ));
             }
             return Ok(Statement::FuncDef(func));
@@ -447,7 +622,7 @@ fn parse_single_block(block: &Block2) -> Result<Statement, String> {
                 .and_then(|fields| fields.get("TEXT"))
             {
                 if func_name.is_empty() {
-                    return Err("Function name is empty".to_string());
+                    return Err(error("Function name is empty"));
                 } else {
                     if let Some(actual_args) = block
                         .inputs
@@ -457,25 +632,345 @@ fn parse_single_block(block: &Block2) -> Result<Statement, String> {
                         .and_then(|shadow_block| shadow_block.fields.as_ref())
                         .and_then(|fields| fields.get("TEXT"))
                     {
-                        let (input, args) = parse_actual_arguments(&format!("({})", actual_args))
-                            .map_err(|e| {
-                            format!("Erro ao fazer parse dos argumentos: {}", e)
-                        })?;
-                        return Ok(Statement::SingleFuncCall(func_name.to_string(), args));
+                        let args = parse_complete(
+                            block,
+                            actual_args,
+                            "arguments",
+                            parse_expression_list,
+                        )?;
+                        return Ok(Statement::SingleFuncCall(func_name.to_string(), args, None));
                     } else {
-                        return Err(format!(
-                            "Parse Error on single call of function {}",
-                            func_name
+                        return Err(BlockParseError::new(
+                            block,
+                            format!("Parse Error on single call of function {}", func_name),
                         ));
                     }
                 }
             } else {
-                return Err(format!("Parse Error on single function call"));
+                return Err(error("Parse Error on single function call"));
             }
         }
+
+        "break_block" => Ok(Statement::Break),
+
+        "continue_block" => Ok(Statement::Continue),
+
+        "global_block" => match block.fields.as_ref().and_then(|f| f.get("VARIABLE")) {
+            Some(name) if !name.is_empty() => Ok(Statement::Global(parse_complete_identifier(
+                block, name, "variable",
+            )?)),
+            _ => Err(error("Variable name cannot be empty")),
+        },
+
+        "try_catch_block" => {
+            let name = match block.fields.as_ref().and_then(|f| f.get("ERROR")) {
+                Some(name) if !name.is_empty() => parse_complete_identifier(block, name, "error"),
+                _ => Err(error("Error name cannot be empty")),
+            };
+            // Both bodies are parsed even if the name is malformed, to report their errors too
+            let try_block = parse_body(block, "TRY_BODY", errors);
+            let catch_block = parse_body(block, "CATCH_BODY", errors);
+            Ok(Statement::TryCatch(
+                Box::new(try_block.unwrap_or(Statement::Block(vec![]))),
+                name?,
+                Box::new(catch_block.unwrap_or(Statement::Block(vec![]))),
+            ))
+        }
+
         _ => {
             //output.push(format!("Unknown block type: {}", block.r#type));
-            return Err("Non-existent block".to_string());
+            return Err(error("Non-existent block"));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn print_block(id: &str, text: &str) -> serde_json::Value {
+        json!({
+            "type": "print_block",
+            "id": id,
+            "inputs": {
+                "EXPRESSION": {"shadow": {"type": "expression_block", "id": format!("{}-text", id), "fields": {"TEXT": text}}}
+            }
+        })
+    }
+
+    fn chain(mut blocks: Vec<serde_json::Value>) -> Block2 {
+        let mut next: Option<serde_json::Value> = None;
+        while let Some(mut block) = blocks.pop() {
+            if let Some(next_block) = next {
+                block["next"] = json!({"block": next_block});
+            }
+            next = Some(block);
         }
+        serde_json::from_value(next.unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_parse_chained_blocks_ok() {
+        let block = chain(vec![print_block("a", "1 + 2"), print_block("b", "\"hi\"")]);
+        assert_eq!(
+            parse_chained_blocks(&block),
+            Ok(Statement::Block(vec![
+                Statement::Print(Box::new(Expression::Add(
+                    Box::new(Expression::CInt(1)),
+                    Box::new(Expression::CInt(2)),
+                ))),
+                Statement::Print(Box::new(Expression::CString("hi".to_string()))),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_parse_chained_blocks_reports_every_error() {
+        let block = chain(vec![
+            print_block("a", "1 +"),
+            print_block("b", "2"),
+            print_block("c", "x y"),
+        ]);
+        let errors = parse_chained_blocks(&block).unwrap_err();
+        assert_eq!(
+            errors,
+            vec![
+                BlockParseError {
+                    block_id: "a".to_string(),
+                    message: "Parsing error on print statement expression `1 +`: \
+                              expected expression after `+` at column 4"
+                        .to_string(),
+                    remainder: Some("".to_string()),
+                },
+                BlockParseError {
+                    block_id: "c".to_string(),
+                    message: "Parsing error on print statement expression `x y`: \
+                              unexpected `y` after `x` at column 3"
+                        .to_string(),
+                    remainder: Some("y".to_string()),
+                },
+            ]
+        );
+        assert_eq!(
+            errors[1].to_string(),
+            "[Block c] Parsing error on print statement expression `x y`: \
+             unexpected `y` after `x` at column 3 (unparsed input: `y`)"
+        );
+        assert_eq!(
+            errors[0].to_string(),
+            "[Block a] Parsing error on print statement expression `1 +`: \
+             expected expression after `+` at column 4"
+        );
+    }
+
+    #[test]
+    fn test_parse_chained_blocks_reports_nested_errors_after_their_parent() {
+        let if_block = json!({
+            "type": "if_else_block",
+            "id": "if",
+            "inputs": {
+                "CONDITION": {"shadow": {"type": "expression_block", "id": "cond", "fields": {"TEXT": "x >"}}},
+                "IF_BODY": {"block": print_block("then", "(1")},
+                "ELSE_BODY": {"block": print_block("else", "2")}
+            }
+        });
+        let block = chain(vec![if_block, print_block("after", "3 3")]);
+        let ids: Vec<String> = parse_chained_blocks(&block)
+            .unwrap_err()
+            .into_iter()
+            .map(|e| e.block_id)
+            .collect();
+        assert_eq!(ids, vec!["if", "then", "after"]);
+    }
+
+    #[test]
+    fn test_parse_assignment_reports_both_malformed_fields_in_order() {
+        let block: Block2 = serde_json::from_value(json!({
+            "type": "assignment_block",
+            "id": "assign",
+            "fields": {"VARIABLE": "1x"},
+            "inputs": {
+                "EXPRESSION": {"shadow": {"type": "expression_block", "id": "exp", "fields": {"TEXT": "2 +"}}}
+            }
+        }))
+        .unwrap();
+        let messages: Vec<String> = parse_chained_blocks(&block)
+            .unwrap_err()
+            .into_iter()
+            .map(|e| e.message)
+            .collect();
+        assert_eq!(messages.len(), 2);
+        assert!(messages[0].starts_with("Parsing error on variable `1x`"));
+        assert!(messages[1].starts_with("Parsing error on expression `2 +`"));
+    }
+
+    fn text(id: &str, text: &str) -> serde_json::Value {
+        json!({"shadow": {"type": "expression_block", "id": id, "fields": {"TEXT": text}}})
+    }
+
+    #[test]
+    fn test_parse_elif_and_when_blocks() {
+        let if_block = json!({
+            "type": "if_else_block",
+            "id": "if",
+            "inputs": {
+                "CONDITION": text("c0", "x == 1"),
+                "IF_BODY": {"block": print_block("one", "1")},
+                "ELIF_CONDITION0": text("c1", "x == 2"),
+                "ELIF_BODY0": {"block": print_block("two", "2")},
+                "ELIF_CONDITION1": text("c2", "x == 3"),
+                "ELSE_BODY": {"block": print_block("other", "0")}
+            }
+        });
+        let when_block = json!({
+            "type": "when_block",
+            "id": "when",
+            "inputs": {
+                "VALUE": text("v", "x"),
+                "CASES": {"block": {
+                    "type": "case_block",
+                    "id": "case",
+                    "inputs": {
+                        "VALUE": text("v1", "1"),
+                        "CASE_BODY": {"block": print_block("a", "1")}
+                    },
+                    "next": {"block": {"type": "print_block", "id": "stray"}}
+                }}
+            }
+        });
+        let print = |n| Statement::Block(vec![Statement::Print(Box::new(Expression::CInt(n)))]);
+        let x_is = |n| {
+            Box::new(Expression::EQ(
+                Box::new(Expression::Var("x".to_string())),
+                Box::new(Expression::CInt(n)),
+            ))
+        };
+        let if_block: Block2 = serde_json::from_value(if_block).unwrap();
+        assert_eq!(
+            parse_chained_blocks(&if_block),
+            Ok(Statement::Block(vec![Statement::IfThenElse(
+                x_is(1),
+                Box::new(print(1)),
+                Some(Box::new(Statement::IfThenElse(
+                    x_is(2),
+                    Box::new(print(2)),
+                    Some(Box::new(Statement::IfThenElse(
+                        x_is(3),
+                        Box::new(Statement::Block(vec![])),
+                        Some(Box::new(print(0))),
+                    ))),
+                ))),
+            )]))
+        );
+
+        let when_block: Block2 = serde_json::from_value(when_block).unwrap();
+        let errors = parse_chained_blocks(&when_block).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].block_id, "stray");
+    }
+
+    #[test]
+    fn test_parse_break_and_continue_blocks() {
+        let while_block = json!({
+            "type": "while_block",
+            "id": "loop",
+            "inputs": {
+                "CONDITION": {"shadow": {"type": "expression_block", "id": "cond", "fields": {"TEXT": "True"}}},
+                "WHILE_BODY": {"block": {
+                    "type": "continue_block",
+                    "id": "skip",
+                    "next": {"block": {"type": "break_block", "id": "stop"}}
+                }}
+            }
+        });
+        let block: Block2 = serde_json::from_value(while_block).unwrap();
+        assert_eq!(
+            parse_chained_blocks(&block),
+            Ok(Statement::Block(vec![Statement::While(
+                Box::new(Expression::CTrue),
+                Box::new(Statement::Block(vec![
+                    Statement::Continue,
+                    Statement::Break
+                ])),
+            )]))
+        );
+    }
+
+    #[test]
+    fn test_function_blocks_return_only_their_return_expression() {
+        let function = |return_type: &str, return_expression: &str| {
+            let block = json!({
+                "type": "function_declaration_block",
+                "id": "f",
+                "fields": {"FUNCTION_NAME": "f", "RETURN_TYPE": return_type},
+                "inputs": {
+                    "FUNCTION_BODY": {"block": print_block("p", "1")},
+                    "RETURN_EXPRESSION": {"shadow": {"type": "expression_block", "id": "r", "fields": {"TEXT": return_expression}}}
+                }
+            });
+            match parse_chained_blocks(&serde_json::from_value(block).unwrap()) {
+                Ok(Statement::Block(mut stmts)) => match stmts.pop() {
+                    Some(Statement::FuncDef(func)) => func.body.map(|body| *body),
+                    _ => None,
+                },
+                _ => None,
+            }
+        };
+        let print = Statement::Print(Box::new(Expression::CInt(1)));
+        assert_eq!(
+            function("VOID", ""),
+            Some(Statement::Block(vec![print.clone()]))
+        );
+        assert_eq!(
+            function("INT", "2"),
+            Some(Statement::Block(vec![
+                print,
+                Statement::Return(Box::new(Expression::CInt(2))),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_parse_try_catch_block() {
+        let try_catch = json!({
+            "type": "try_catch_block",
+            "id": "try",
+            "fields": {"ERROR": "problem"},
+            "inputs": {
+                "TRY_BODY": {"block": print_block("a", "1 / 0")},
+                "CATCH_BODY": {"block": print_block("b", "problem")}
+            }
+        });
+        let block: Block2 = serde_json::from_value(try_catch).unwrap();
+        assert_eq!(
+            parse_chained_blocks(&block),
+            Ok(Statement::Block(vec![Statement::TryCatch(
+                Box::new(Statement::Block(vec![Statement::Print(Box::new(
+                    Expression::Div(Box::new(Expression::CInt(1)), Box::new(Expression::CInt(0)))
+                ))])),
+                "problem".to_string(),
+                Box::new(Statement::Block(vec![Statement::Print(Box::new(
+                    Expression::Var("problem".to_string())
+                ))])),
+            )]))
+        );
+    }
+
+    #[test]
+    fn test_declaration_blocks_give_a_default_value() {
+        let declaration = json!({
+            "type": "declaration_block",
+            "id": "decl",
+            "fields": {"TYPE": "INT", "VARIABLE": "total"}
+        });
+        let block: Block2 = serde_json::from_value(declaration).unwrap();
+        assert_eq!(
+            parse_chained_blocks(&block),
+            Ok(Statement::Block(vec![Statement::VarDeclaration(
+                "total".to_string(),
+                Box::new(Expression::Default(Box::new(Expression::CInt(0)))),
+            )]))
+        );
     }
 }
@@ -1,14 +1,17 @@
 use nom::{
     IResult,
-    branch::alt,
-    bytes::complete::tag,
-    character::complete::{alpha1, multispace0, multispace1},
-    combinator::{map, not, peek, recognize},
-    multi::many0,
-    sequence::{delimited, preceded, terminated, tuple},
+    combinator::cut,
+    error::{ContextError, ErrorKind, ParseError, VerboseError, context},
 };
 
-use crate::parser::keywords::KEYWORDS;
+use crate::parser::lexer::{Token, TokenKind, Tokens, tokenize};
+
+/// Result of the textual parsers. Errors keep the context labels and the
+/// expected characters, so that `parser_error` can explain what went wrong.
+pub type ParseResult<'a, O> = IResult<&'a str, O, VerboseError<&'a str>>;
+
+/// Result of the parsers over the tokens produced by the lexer.
+pub type TokenResult<'a, O> = IResult<Tokens<'a>, O, VerboseError<Tokens<'a>>>;
 
 // Type name constants
 //pub const INT_TYPE: &str = "Int";
@@ -35,6 +38,9 @@ pub const END_KEYWORD: &str = "end";
 // Statement keyword constants
 pub const IF_KEYWORD: &str = "if";
 pub const ELSE_KEYWORD: &str = "else";
+pub const ELIF_KEYWORD: &str = "elif";
+pub const WHEN_KEYWORD: &str = "when";
+pub const CASE_KEYWORD: &str = "case";
 pub const WHILE_KEYWORD: &str = "while";
 pub const FOR_KEYWORD: &str = "for";
 pub const IN_KEYWORD: &str = "in";
@@ -43,79 +49,140 @@ pub const VAR_KEYWORD: &str = "var";
 pub const VAL_KEYWORD: &str = "val";
 pub const DEF_KEYWORD: &str = "def";
 pub const RET_KEYWORD: &str = "ret";
+pub const BREAK_KEYWORD: &str = "break";
+pub const CONTINUE_KEYWORD: &str = "continue";
+pub const GLOBAL_KEYWORD: &str = "global";
+pub const TRY_KEYWORD: &str = "try";
+pub const CATCH_KEYWORD: &str = "catch";
 pub const LAMBDA_KEYWORD: &str = "lambda";
 
+// Literal keyword constants
+pub const TRUE_KEYWORD: &str = "True";
+pub const FALSE_KEYWORD: &str = "False";
+
+// Maybe and Result keyword constants, which are written like function calls
+pub const OK_KEYWORD: &str = "Ok";
+pub const ERR_KEYWORD: &str = "Err";
+pub const JUST_KEYWORD: &str = "Just";
+pub const NOTHING_KEYWORD: &str = "Nothing";
+pub const UNWRAP_KEYWORD: &str = "unwrap";
+pub const TRY_UNWRAP_KEYWORD: &str = "tryUnwrap";
+pub const IS_NOTHING_KEYWORD: &str = "isNothing";
+pub const IS_ERROR_KEYWORD: &str = "isError";
+
 // Operator and symbol constants
 pub const FUNCTION_ARROW: &str = "->";
 pub const PIPE_SYMBOL: &str = "|";
 pub const COLON_SYMBOL: &str = ":";
 pub const COMMA_SYMBOL: &str = ",";
 pub const SEMICOLON_SYMBOL: &str = ";";
+pub const EQUALS_SYMBOL: &str = "=";
+pub const LEFT_BRACKET_SYMBOL: &str = "[";
+pub const RIGHT_BRACKET_SYMBOL: &str = "]";
+pub const LEFT_PAREN_SYMBOL: &str = "(";
+pub const RIGHT_PAREN_SYMBOL: &str = ")";
 
-// Bracket and parentheses constants
-pub const LEFT_BRACKET: char = '[';
-pub const RIGHT_BRACKET: char = ']';
-pub const LEFT_PAREN: char = '(';
-pub const RIGHT_PAREN: char = ')';
-
-// Other character constants
-pub const COMMA_CHAR: char = ',';
-pub const COLON_CHAR: char = ':';
-pub const PIPE_CHAR: char = '|';
-pub const SEMICOLON_CHAR: char = ';';
-pub const EQUALS_CHAR: char = '=';
+// Comment delimiter constants
+pub const LINE_COMMENT_START: &str = "#";
+pub const BLOCK_COMMENT_START: &str = "/*";
+pub const BLOCK_COMMENT_END: &str = "*/";
 
 /// Accepts any character except '"' and control characters (like \n, \t)
 pub fn is_string_char(c: char) -> bool {
     c != '"' && !c.is_control()
 }
 
-pub fn separator<'a>(sep: &'static str) -> impl FnMut(&'a str) -> IResult<&'a str, &'a str> {
-    delimited(multispace0, tag(sep), multispace0)
+/// Runs a parser over the tokens of `input`. The unconsumed input and the
+/// positions of the errors are reported as the suffixes of `input` where the
+/// corresponding tokens start, so `parser_error` can locate them in the text.
+pub fn from_text<'a, O, F>(input: &'a str, mut parser: F) -> ParseResult<'a, O>
+where
+    F: for<'t> FnMut(Tokens<'t>) -> TokenResult<'t, O>,
+{
+    let tokens = tokenize(input).map_err(nom::Err::Failure)?;
+    match parser(Tokens::new(&tokens)) {
+        Ok((rest, value)) => Ok((&input[rest.offset()..], value)),
+        Err(e) => Err(e.map(|e| VerboseError {
+            errors: e
+                .errors
+                .into_iter()
+                .map(|(tokens, kind)| (&input[tokens.offset()..], kind))
+                .collect(),
+        })),
+    }
 }
 
-/// Parses a reserved keyword (e.g., "if") surrounded by optional spaces
-/// Fails if followed by an identifier character
-pub fn keyword<'a>(kw: &'static str) -> impl FnMut(&'a str) -> IResult<&'a str, &'a str> {
-    move |input: &'a str| {
-        let result = map(
-            tuple((
-                terminated(
-                    preceded(multispace0, tag(kw)),
-                    not(peek(identifier_start_or_continue)),
-                ),
-                multispace0,
-            )),
-            |(kw, _)| kw,
-        )(input);
-
-        result
-    }
+/// Commits to `parser` once the preceding input is known to start a construct
+/// (e.g. after `if`): its errors become failures labeled with what was expected,
+/// instead of letting an enclosing `alt` backtrack and report something unrelated.
+pub fn expect<I, O, E, F>(label: &'static str, parser: F) -> impl FnMut(I) -> IResult<I, O, E>
+where
+    I: Clone,
+    E: ParseError<I> + ContextError<I>,
+    F: FnMut(I) -> IResult<I, O, E>,
+{
+    cut(context(label, parser))
 }
 
-/// Parsers for identifiers.
-pub fn identifier(input: &str) -> IResult<&str, Box<String>> {
-    let (input, _) = multispace0(input)?;
+// Consumes the next token that is not a comment, if it satisfies `accept`
+fn token<'a>(input: Tokens<'a>, accept: impl Fn(&Token) -> bool) -> TokenResult<'a, &'a Token<'a>> {
+    let (rest, token) = input.next_token();
+    if accept(token) {
+        Ok((rest, token))
+    } else {
+        Err(nom::Err::Error(VerboseError::from_error_kind(
+            input,
+            ErrorKind::Tag,
+        )))
+    }
+}
 
-    let (input, first_char) = identifier_start(input)?;
-    let (input, rest) = identifier_continue(input)?;
+/// Parses a reserved keyword (e.g., "if").
+pub fn keyword<'a>(kw: &'static str) -> impl FnMut(Tokens<'a>) -> TokenResult<'a, &'a str> {
+    move |input| {
+        token(input, |t| t.kind == TokenKind::Keyword && t.text == kw)
+            .map(|(rest, t)| (rest, t.text))
+    }
+}
 
-    let ident = format!("{}{}", first_char, rest);
+/// Parses a symbol (e.g., "->").
+pub fn symbol<'a>(sym: &'static str) -> impl FnMut(Tokens<'a>) -> TokenResult<'a, &'a str> {
+    move |input| {
+        token(input, |t| t.kind == TokenKind::Symbol && t.text == sym)
+            .map(|(rest, t)| (rest, t.text))
+    }
+}
 
-    Ok((input, Box::from(ident.clone())))
+/// Parses an identifier with the given name, for words that are only meaningful
+/// in some contexts, like the type names.
+pub fn name<'a>(name: &'static str) -> impl FnMut(Tokens<'a>) -> TokenResult<'a, &'a str> {
+    move |input| {
+        token(input, |t| t.kind == TokenKind::Identifier && t.text == name)
+            .map(|(rest, t)| (rest, t.text))
+    }
 }
 
-/// First character of an identifier: [a-zA-Z_]
-fn identifier_start(input: &str) -> IResult<&str, &str> {
-    alt((alpha1, tag("_")))(input)
+/// Parses an identifier, which is never a keyword.
+pub fn identifier(input: Tokens) -> TokenResult<String> {
+    let (rest, t) = token(input, |t| t.kind == TokenKind::Identifier)?;
+    Ok((rest, t.text.to_string()))
 }
 
-/// Remaining characters: [a-zA-Z0-9_]*
-fn identifier_continue(input: &str) -> IResult<&str, &str> {
-    recognize(many0(identifier_start_or_continue))(input)
+/// Parses a literal of the given kind, returning its text.
+pub fn literal<'a>(kind: TokenKind) -> impl FnMut(Tokens<'a>) -> TokenResult<'a, &'a str> {
+    move |input| token(input, |t| t.kind == kind).map(|(rest, t)| (rest, t.text))
 }
 
-/// A single identifier character: alphanumeric or underscore
-fn identifier_start_or_continue(input: &str) -> IResult<&str, &str> {
-    recognize(alt((alpha1, tag("_"), nom::character::complete::digit1)))(input)
+/// Parses a comment that comes right away, with its delimiters: either `#` up
+/// to the end of the line, or `/* ... */`, which may span several lines.
+pub fn comment(input: Tokens<'_>) -> TokenResult<'_, &str> {
+    let (rest, t) = input.split_first();
+    if t.kind == TokenKind::Comment {
+        Ok((rest, t.text))
+    } else {
+        Err(nom::Err::Error(VerboseError::from_error_kind(
+            input,
+            ErrorKind::Tag,
+        )))
+    }
 }
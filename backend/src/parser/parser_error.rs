@@ -0,0 +1,204 @@
+use nom::error::{VerboseError, VerboseErrorKind};
+
+// Characters that always form a token on their own
+const DELIMITERS: &str = "()[]\",;:";
+
+/// Explains a parse error of `input` in terms of the token that was expected and
+/// where, e.g. "expected expression after `>` at column 4".
+pub fn describe_parse_error(input: &str, error: &VerboseError<&str>) -> String {
+    match main_entry(error) {
+        Some((rest, VerboseErrorKind::Context(label))) => {
+            format!("expected {}{}", label, location(input, rest))
+        }
+        Some((rest, VerboseErrorKind::Char(c))) => {
+            format!("expected `{}`{}", c, location(input, rest))
+        }
+        Some((rest, VerboseErrorKind::Nom(_))) => describe_unparsed(input, rest),
+        None => "invalid syntax".to_string(),
+    }
+}
+
+/// Explains why a parser stopped before the end of `input`, leaving `rest` unconsumed.
+pub fn describe_unparsed(input: &str, rest: &str) -> String {
+    match next_token(rest) {
+        Some(token) => format!("unexpected `{}`{}", token, location(input, rest)),
+        None => format!("unexpected end of input{}", location(input, rest)),
+    }
+}
+
+/// The input left at the point where the error was detected.
+pub fn error_position<'a>(error: &VerboseError<&'a str>) -> Option<&'a str> {
+    main_entry(error).map(|(rest, _)| *rest)
+}
+
+// The innermost context label says the most about what was expected.
+// nom lists the errors from the innermost to the outermost parser.
+fn main_entry<'a, 'b>(error: &'b VerboseError<&'a str>) -> Option<&'b (&'a str, VerboseErrorKind)> {
+    error
+        .errors
+        .iter()
+        .find(|(_, kind)| matches!(kind, VerboseErrorKind::Context(_)))
+        .or_else(|| error.errors.first())
+}
+
+// Renders " after `token` at column N" for the first non-blank character of `rest`,
+// adding the line when `input` spans several lines
+fn location(input: &str, rest: &str) -> String {
+    let rest = rest.trim_start();
+    if !input.ends_with(rest) {
+        return String::new();
+    }
+    let consumed = &input[..input.len() - rest.len()];
+    let line = consumed.matches('\n').count() + 1;
+    let column = match consumed.rfind('\n') {
+        Some(newline) => consumed[newline + 1..].chars().count() + 1,
+        None => consumed.chars().count() + 1,
+    };
+    let position = if input.contains('\n') {
+        format!("at line {}, column {}", line, column)
+    } else {
+        format!("at column {}", column)
+    };
+    match previous_token(consumed) {
+        Some(token) => format!(" after `{}` {}", token, position),
+        None => format!(" {}", position),
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '.'
+}
+
+fn is_operator_char(c: char) -> bool {
+    !is_word_char(c) && !c.is_whitespace() && !DELIMITERS.contains(c)
+}
+
+fn previous_token(text: &str) -> Option<&str> {
+    let text = text.trim_end();
+    let last = text.chars().last()?;
+    let start = if is_word_char(last) {
+        text.trim_end_matches(is_word_char).len()
+    } else if DELIMITERS.contains(last) {
+        text.len() - last.len_utf8()
+    } else {
+        text.trim_end_matches(is_operator_char).len()
+    };
+    Some(&text[start..])
+}
+
+fn next_token(text: &str) -> Option<&str> {
+    let text = text.trim_start();
+    let first = text.chars().next()?;
+    let end = if is_word_char(first) {
+        text.len() - text.trim_start_matches(is_word_char).len()
+    } else if DELIMITERS.contains(first) {
+        first.len_utf8()
+    } else {
+        text.len() - text.trim_start_matches(is_operator_char).len()
+    };
+    Some(&text[..end])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{parse, parse_expression, parse_statement};
+
+    fn expression_error(input: &str) -> String {
+        match parse_expression(input) {
+            Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => describe_parse_error(input, &e),
+            other => panic!("expected an error, got {:?}", other),
+        }
+    }
+
+    fn statement_error(input: &str) -> String {
+        match parse_statement(input) {
+            Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => describe_parse_error(input, &e),
+            other => panic!("expected an error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_describe_missing_operand() {
+        assert_eq!(
+            expression_error("x >"),
+            "expected expression after `>` at column 4"
+        );
+        assert_eq!(
+            expression_error("1 + 2 **"),
+            "expected expression after `**` at column 9"
+        );
+        assert_eq!(
+            expression_error("f(1, )"),
+            "expected expression after `,` at column 6"
+        );
+    }
+
+    #[test]
+    fn test_describe_missing_delimiter() {
+        assert_eq!(
+            expression_error("(a + b"),
+            "expected `)` after `b` at column 7"
+        );
+        assert_eq!(
+            expression_error("[1, 2"),
+            "expected `]` after `2` at column 6"
+        );
+        assert_eq!(
+            expression_error("\"abc"),
+            "expected closing `\"` after `abc` at column 5"
+        );
+    }
+
+    #[test]
+    fn test_describe_statement_errors() {
+        assert_eq!(
+            statement_error("if x > : y = 1; end"),
+            "expected expression after `>` at column 8"
+        );
+        assert_eq!(
+            statement_error("while x: y = 1 z = 2 end"),
+            "expected `;` or `end` after `1` at column 16"
+        );
+        assert_eq!(
+            statement_error("var = 1"),
+            "expected identifier after `var` at column 5"
+        );
+    }
+
+    #[test]
+    fn test_describe_error_on_later_line() {
+        let input = "x = 1;\nif x:\n    y = ;\nend";
+        match parse(input) {
+            Err(nom::Err::Failure(e)) => assert_eq!(
+                describe_parse_error(input, &e),
+                "expected expression after `=` at line 3, column 9"
+            ),
+            other => panic!("expected a failure, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_describe_unterminated_comment() {
+        let input = "x = 1 /* the answer";
+        match parse(input) {
+            Err(nom::Err::Failure(e)) => assert_eq!(
+                describe_parse_error(input, &e),
+                "expected `*/` closing the comment after `/*` at column 10"
+            ),
+            other => panic!("expected a failure, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_describe_unparsed() {
+        assert_eq!(
+            describe_unparsed("x y", "y"),
+            "unexpected `y` after `x` at column 3"
+        );
+        assert_eq!(
+            describe_unparsed("a == b c", " c"),
+            "unexpected `c` after `b` at column 8"
+        );
+    }
+}
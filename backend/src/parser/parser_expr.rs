@@ -1,212 +1,260 @@
 use nom::{
-    IResult,
     branch::alt,
-    bytes::complete::{tag, take_while},
-    character::complete::{char, digit1, multispace0, multispace1},
-    combinator::{map, map_res, opt, value, verify},
-    error::Error,
-    multi::{fold_many0, separated_list0},
-    sequence::{delimited, pair, preceded, terminated, tuple},
+    combinator::{map, map_res, value},
+    error::{ErrorKind, ParseError, VerboseError},
+    multi::separated_list0,
+    sequence::{delimited, preceded, terminated, tuple},
 };
 
 use std::str::FromStr;
 
+use crate::ir::ast::Comparison;
 use crate::ir::ast::Function;
 use crate::ir::ast::Statement;
+use crate::parser::lexer::{TokenKind, Tokens};
+use crate::parser::operators::{
+    Associativity, BINARY_OPERATORS, BinaryOperator, NEGATION_OPERATOR, NEGATION_PRECEDENCE,
+    NOT_OPERATOR, NOT_PRECEDENCE, OR_PRECEDENCE, comparison,
+};
 use crate::parser::parser_common::{
-    COLON_CHAR,
-    // Other character constants
-    COMMA_CHAR,
+    COLON_SYMBOL,
+    // Other symbol constants
+    COMMA_SYMBOL,
+    ERR_KEYWORD,
+    FALSE_KEYWORD,
     FUNCTION_ARROW,
+    IS_ERROR_KEYWORD,
+    IS_NOTHING_KEYWORD,
+    JUST_KEYWORD,
     LAMBDA_KEYWORD,
     // Bracket and parentheses constants
-    LEFT_BRACKET,
-    LEFT_PAREN,
-    RIGHT_BRACKET,
-    RIGHT_PAREN,
+    LEFT_BRACKET_SYMBOL,
+    LEFT_PAREN_SYMBOL,
+    NOTHING_KEYWORD,
+    OK_KEYWORD,
+    ParseResult,
+    RIGHT_BRACKET_SYMBOL,
+    RIGHT_PAREN_SYMBOL,
+    TRUE_KEYWORD,
+    TRY_UNWRAP_KEYWORD,
+    TokenResult,
+    UNWRAP_KEYWORD,
+    expect,
+    from_text,
     identifier,
-    is_string_char,
     keyword,
+    literal,
+    symbol,
 };
-use crate::parser::parser_stmt::{parse_formal_argument, parse_return_statement};
-use crate::parser::parser_type::parse_type;
-use crate::{
-    ir::ast::Expression,
-    parser::{parser_common::END_KEYWORD, parser_stmt::parse_block},
-};
+use crate::parser::parser_stmt::{formal_argument, return_statement};
+use crate::parser::parser_type::type_expression;
+use crate::{ir::ast::Expression, parser::parser_common::END_KEYWORD};
 
-pub fn parse_expression(input: &str) -> IResult<&str, Expression> {
-    delimited(multispace0, parse_or, multispace0)(input)
-    //parse_or(input)
+pub fn parse_expression(input: &str) -> ParseResult<'_, Expression> {
+    from_text(input, expression)
 }
 
-fn parse_or(input: &str) -> IResult<&str, Expression> {
-    let (input, init) = parse_and(input)?;
-    fold_many0(
-        preceded(keyword("or"), parse_and),
-        move || init.clone(),
-        |acc, val| Expression::Or(Box::new(acc), Box::new(val)),
-    )(input)
+pub fn expression(input: Tokens) -> TokenResult<Expression> {
+    parse_binary_expression(input, OR_PRECEDENCE)
 }
 
-fn parse_and(input: &str) -> IResult<&str, Expression> {
-    let (input, init) = parse_not(input)?;
-    fold_many0(
-        preceded(keyword("and"), parse_not),
-        move || init.clone(),
-        |acc, val| Expression::And(Box::new(acc), Box::new(val)),
-    )(input)
+/// Pratt parser over the operators of `BINARY_OPERATORS`: parses an operand and
+/// then folds in every following operator that binds at least as tightly as
+/// `min_precedence`.
+fn parse_binary_expression(input: Tokens, min_precedence: u8) -> TokenResult<Expression> {
+    let (mut input, mut lhs) = parse_prefix(input, min_precedence)?;
+    // Whether the last operator was a comparison, which the next one extends
+    // into a chain such as `a < b < c`
+    let mut chained = false;
+
+    loop {
+        let (rest, op) = match parse_binary_operator(input) {
+            Ok((rest, op)) if op.precedence >= min_precedence => (rest, op),
+            _ => break,
+        };
+        let (rest, rhs) = expect("expression", |input| {
+            parse_binary_expression(input, op.right_precedence)
+        })(rest)?;
+        let is_chained = op.associativity == Associativity::Chained;
+        lhs = if chained && is_chained {
+            extend_chain(lhs, comparison(op), rhs)
+        } else {
+            (op.build)(Box::new(lhs), Box::new(rhs))
+        };
+        chained = is_chained;
+        input = rest;
+    }
+    Ok((input, lhs))
 }
 
-fn parse_not(input: &str) -> IResult<&str, Expression> {
-    alt((
-        map(preceded(keyword("not"), parse_not), |e| {
-            Expression::Not(Box::new(e))
-        }),
-        parse_relational,
-    ))(input)
+// Appends a comparison to a chain, turning a single comparison into a chain
+// of two, so that the operand they share is evaluated once
+fn extend_chain(lhs: Expression, comparison: Comparison, rhs: Expression) -> Expression {
+    let (first, mut links) = match lhs {
+        Expression::Chain(first, links) => (first, links),
+        Expression::EQ(l, r) => (l, vec![(Comparison::EQ, *r)]),
+        Expression::NEQ(l, r) => (l, vec![(Comparison::NEQ, *r)]),
+        Expression::GT(l, r) => (l, vec![(Comparison::GT, *r)]),
+        Expression::LT(l, r) => (l, vec![(Comparison::LT, *r)]),
+        Expression::GTE(l, r) => (l, vec![(Comparison::GTE, *r)]),
+        Expression::LTE(l, r) => (l, vec![(Comparison::LTE, *r)]),
+        _ => unreachable!("a chain extends the comparison parsed before it"),
+    };
+    links.push((comparison, rhs));
+    Expression::Chain(first, links)
 }
 
-fn parse_relational(input: &str) -> IResult<&str, Expression> {
-    let (input, init) = parse_add_sub(input)?;
-    fold_many0(
-        pair(
-            alt((
-                operator("<="),
-                operator("<"),
-                operator(">="),
-                operator(">"),
-                operator("=="),
-                operator("!="),
-            )),
-            parse_add_sub,
-        ),
-        move || init.clone(),
-        |acc, (op, val)| match op {
-            "<" => Expression::LT(Box::new(acc), Box::new(val)),
-            "<=" => Expression::LTE(Box::new(acc), Box::new(val)),
-            ">" => Expression::GT(Box::new(acc), Box::new(val)),
-            ">=" => Expression::GTE(Box::new(acc), Box::new(val)),
-            "==" => Expression::EQ(Box::new(acc), Box::new(val)),
-            "!=" => Expression::NEQ(Box::new(acc), Box::new(val)),
-            _ => unreachable!(),
-        },
-    )(input)
+// Prefix operators are only accepted where they bind at least as tightly as
+// the surrounding operator, so `a == not b` must be written `a == (not b)`
+fn parse_prefix(input: Tokens, min_precedence: u8) -> TokenResult<Expression> {
+    if min_precedence <= NOT_PRECEDENCE {
+        if let Ok((rest, _)) = keyword(NOT_OPERATOR)(input) {
+            let (rest, exp) = expect("expression", |input| {
+                parse_binary_expression(input, NOT_PRECEDENCE)
+            })(rest)?;
+            return Ok((rest, Expression::Not(Box::new(exp))));
+        }
+    }
+    if min_precedence <= NEGATION_PRECEDENCE {
+        if let Ok(result) = parse_negation(input) {
+            return Ok(result);
+        }
+    }
+    parse_factor(input)
 }
 
-fn parse_add_sub(input: &str) -> IResult<&str, Expression> {
-    let (input, init) = parse_term(input)?;
-    fold_many0(
-        pair(alt((operator("+"), operator("-"))), parse_term),
-        move || init.clone(),
-        |acc, (op, val)| match op {
-            "+" => Expression::Add(Box::new(acc), Box::new(val)),
-            "-" => Expression::Sub(Box::new(acc), Box::new(val)),
-            _ => unreachable!(),
-        },
-    )(input)
+// `-5` is folded into a negative literal, while `-(5)` and `-5 ** 2` stay negations.
+// `-2147483648` is read as a whole, since the literal only fits an i32 with its sign.
+fn parse_negation(input: Tokens) -> TokenResult<Expression> {
+    let (rest, _) = symbol(NEGATION_OPERATOR)(input)?;
+    let (after_literal, next) = rest.next_token();
+    let is_literal = matches!(next.kind, TokenKind::Int | TokenKind::Real);
+    let (rest, operand) = match parse_binary_expression(rest, NEGATION_PRECEDENCE) {
+        Ok(result) => result,
+        Err(nom::Err::Error(e)) => {
+            return match i32::from_str(&format!("{}{}", NEGATION_OPERATOR, next.text)) {
+                Ok(value) if next.kind == TokenKind::Int => {
+                    Ok((after_literal, Expression::CInt(value)))
+                }
+                _ => Err(nom::Err::Error(e)),
+            };
+        }
+        Err(e) => return Err(e),
+    };
+    let exp = match operand {
+        Expression::CInt(value) if is_literal => Expression::CInt(-value),
+        Expression::CReal(value) if is_literal => Expression::CReal(-value),
+        operand => Expression::Neg(Box::new(operand)),
+    };
+    Ok((rest, exp))
 }
 
-fn parse_term(input: &str) -> IResult<&str, Expression> {
-    let (input, init) = parse_factor(input)?;
-    fold_many0(
-        pair(alt((operator("*"), operator("/"))), parse_factor),
-        move || init.clone(),
-        |acc, (op, val)| match op {
-            "*" => Expression::Mul(Box::new(acc), Box::new(val)),
-            "/" => Expression::Div(Box::new(acc), Box::new(val)),
-            _ => unreachable!(),
-        },
-    )(input)
+fn parse_binary_operator(input: Tokens) -> TokenResult<&'static BinaryOperator> {
+    let (rest, token) = input.next_token();
+    let found = BINARY_OPERATORS.iter().find(|op| {
+        let kind = if op.is_keyword {
+            TokenKind::Keyword
+        } else {
+            TokenKind::Symbol
+        };
+        token.kind == kind && token.text == op.symbol
+    });
+    match found {
+        Some(op) => Ok((rest, op)),
+        None => Err(nom::Err::Error(VerboseError::from_error_kind(
+            input,
+            ErrorKind::Tag,
+        ))),
+    }
 }
 
-fn parse_factor(input: &str) -> IResult<&str, Expression> {
+fn parse_factor(input: Tokens) -> TokenResult<Expression> {
     alt((
         parse_bool,
         parse_number,
         parse_string,
         parse_list,
+        lambda,
+        parse_builtin,
         parse_function_call,
-        parse_lambda,
         parse_var,
-        delimited(
-            char::<&str, Error<&str>>(LEFT_PAREN),
-            parse_expression,
-            char::<&str, Error<&str>>(RIGHT_PAREN),
+        preceded(
+            symbol(LEFT_PAREN_SYMBOL),
+            terminated(
+                expect("expression", expression),
+                expect("`)`", symbol(RIGHT_PAREN_SYMBOL)),
+            ),
         ),
     ))(input)
 }
 
-fn parse_bool(input: &str) -> IResult<&str, Expression> {
+fn parse_bool(input: Tokens) -> TokenResult<Expression> {
     alt((
-        value(Expression::CTrue, keyword("True")),
-        value(Expression::CFalse, keyword("False")),
+        value(Expression::CTrue, keyword(TRUE_KEYWORD)),
+        value(Expression::CFalse, keyword(FALSE_KEYWORD)),
     ))(input)
 }
 
-fn parse_number(input: &str) -> IResult<&str, Expression> {
-    let float_parser = map_res(
-        verify(
-            tuple((
-                opt(char::<&str, Error<&str>>('-')),
-                digit1,
-                char::<&str, Error<&str>>('.'),
-                digit1,
-            )),
-            |(_, _, _, _)| true,
-        ),
-        |(sign, d1, _, d2)| {
-            let s = match sign {
-                Some(_) => format!("-{}.{}", d1, d2),
-                None => format!("{}.{}", d1, d2),
-            };
-            f64::from_str(&s)
-        },
-    );
-
-    let int_parser = map_res(
-        tuple((opt(char::<&str, Error<&str>>('-')), digit1)),
-        |(sign, digits)| {
-            let s = match sign {
-                Some(_) => format!("-{}", digits),
-                None => digits.to_string(),
-            };
-            i32::from_str(&s)
-        },
-    );
-
+fn parse_number(input: Tokens) -> TokenResult<Expression> {
     alt((
-        map(float_parser, Expression::CReal),
-        map(int_parser, Expression::CInt),
+        map_res(literal(TokenKind::Real), |text| {
+            f64::from_str(text).map(Expression::CReal)
+        }),
+        map_res(literal(TokenKind::Int), |text| {
+            i32::from_str(text).map(Expression::CInt)
+        }),
     ))(input)
 }
 
-fn parse_string(input: &str) -> IResult<&str, Expression> {
-    map(
-        delimited(
-            multispace0,
-            delimited(
-                char::<&str, Error<&str>>('"'),
-                map(take_while(is_string_char), |s: &str| s.to_string()),
-                char::<&str, Error<&str>>('"'),
-            ),
-            multispace0,
-        ),
-        |s| Expression::CString(s),
-    )(input)
+fn parse_string(input: Tokens) -> TokenResult<Expression> {
+    // The token keeps its quotes
+    map(literal(TokenKind::Str), |text| {
+        Expression::CString(text[1..text.len() - 1].to_string())
+    })(input)
 }
 
-fn parse_var(input: &str) -> IResult<&str, Expression> {
-    map(identifier, |v| Expression::Var(*v))(input)
+fn parse_var(input: Tokens) -> TokenResult<Expression> {
+    map(identifier, Expression::Var)(input)
 }
 
-fn parse_function_call(input: &str) -> IResult<&str, Expression> {
+fn parse_function_call(input: Tokens) -> TokenResult<Expression> {
     let (input, name) = identifier(input)?;
     let (input, args) = parse_actual_arguments(input)?;
-    Ok((input, Expression::FuncCall(name.to_string(), args)))
+    Ok((input, Expression::FuncCall(name, args, None)))
 }
 
-//pub fn parse_lambda(input: &str) -> IResult<&str, Expression> {
+// The constructors and operations of Maybe and Result are keywords written like calls
+fn parse_builtin(input: Tokens) -> TokenResult<Expression> {
+    alt((
+        value(Expression::CNothing, keyword(NOTHING_KEYWORD)),
+        builtin(JUST_KEYWORD, Expression::CJust),
+        builtin(OK_KEYWORD, Expression::COk),
+        builtin(ERR_KEYWORD, Expression::CErr),
+        builtin(UNWRAP_KEYWORD, Expression::Unwrap),
+        builtin(TRY_UNWRAP_KEYWORD, Expression::Propagate),
+        builtin(IS_NOTHING_KEYWORD, Expression::IsNothing),
+        builtin(IS_ERROR_KEYWORD, Expression::IsError),
+    ))(input)
+}
+
+fn builtin<'a>(
+    kw: &'static str,
+    build: fn(Box<Expression>) -> Expression,
+) -> impl FnMut(Tokens<'a>) -> TokenResult<'a, Expression> {
+    map(
+        preceded(
+            keyword(kw),
+            delimited(
+                expect("`(`", symbol(LEFT_PAREN_SYMBOL)),
+                expect("expression", expression),
+                expect("`)`", symbol(RIGHT_PAREN_SYMBOL)),
+            ),
+        ),
+        move |exp| build(Box::new(exp)),
+    )
+}
+
+//pub fn parse_lambda(input: &str) -> ParseResult<Expression> {
 //    let mut rest = input;
 //
 //    // 1. keyword "lambda" + espaços
@@ -224,16 +272,16 @@ fn parse_function_call(input: &str) -> IResult<&str, Expression> {
 //    // 2. argumentos formais entre parênteses
 //    let args;
 //    match delimited(
-//        char::<&str, Error<&str>>(LEFT_PAREN),
+//        char::<&str, VerboseError<&str>>(LEFT_PAREN),
 //        separated_list0(
 //            tuple((
 //                multispace0,
-//                char::<&str, Error<&str>>(COMMA_CHAR),
+//                char::<&str, VerboseError<&str>>(COMMA_CHAR),
 //                multispace0,
 //            )),
 //            terminated(parse_formal_argument, multispace0),
 //        ),
-//        char::<&str, Error<&str>>(RIGHT_PAREN),
+//        char::<&str, VerboseError<&str>>(RIGHT_PAREN),
 //    )(rest)
 //    {
 //        Ok((r, a)) => {
@@ -264,7 +312,7 @@ fn parse_function_call(input: &str) -> IResult<&str, Expression> {
 //    match delimited(
 //        multispace0,
 //        parse_type,
-//        char::<&str, Error<&str>>(COLON_CHAR),
+//        char::<&str, VerboseError<&str>>(COLON_CHAR),
 //    )(rest)
 //    {
 //        Ok((r, t)) => {
@@ -304,29 +352,28 @@ fn parse_function_call(input: &str) -> IResult<&str, Expression> {
 //    ))
 //}
 
+pub fn parse_lambda(input: &str) -> ParseResult<'_, Expression> {
+    from_text(input, lambda)
+}
 
-pub fn parse_lambda(input: &str) -> IResult<&str, Expression> {  map(
+pub fn lambda(input: Tokens) -> TokenResult<Expression> {
+    map(
         tuple((
-            preceded(keyword(LAMBDA_KEYWORD), multispace0),
+            keyword(LAMBDA_KEYWORD),
             delimited(
-                char::<&str, Error<&str>>(LEFT_PAREN),
-                separated_list0(
-                    tuple((
-                        multispace0,
-                        char::<&str, Error<&str>>(COMMA_CHAR),
-                        multispace0,
-                    )),
-                    terminated(parse_formal_argument, multispace0),
-                ),
-                char::<&str, Error<&str>>(RIGHT_PAREN),
+                expect("`(`", symbol(LEFT_PAREN_SYMBOL)),
+                separated_list0(symbol(COMMA_SYMBOL), formal_argument),
+                expect("`)`", symbol(RIGHT_PAREN_SYMBOL)),
             ),
-            preceded(multispace0, tag(FUNCTION_ARROW)),
-            delimited(
-                multispace0,
-                parse_type,
-                char::<&str, Error<&str>>(COLON_CHAR),
+            expect("`->`", symbol(FUNCTION_ARROW)),
+            terminated(
+                expect("type", type_expression),
+                expect("`:`", symbol(COLON_SYMBOL)),
+            ),
+            terminated(
+                expect("`ret` statement", return_statement),
+                expect("`end`", keyword(END_KEYWORD)),
             ),
-            delimited(multispace0, parse_return_statement, keyword(END_KEYWORD)),
         )),
         |(_, args, _, t, return_stmt)| {
             Expression::Lambda(Function {
@@ -339,45 +386,223 @@ pub fn parse_lambda(input: &str) -> IResult<&str, Expression> {  map(
     )(input)
 }
 
+fn parse_actual_arguments(input: Tokens) -> TokenResult<Vec<Expression>> {
+    delimited(
+        symbol(LEFT_PAREN_SYMBOL),
+        expression_list,
+        expect("`)`", symbol(RIGHT_PAREN_SYMBOL)),
+    )(input)
+}
+
+pub fn parse_expression_list(input: &str) -> ParseResult<'_, Vec<Expression>> {
+    from_text(input, expression_list)
+}
 
-pub fn parse_actual_arguments(input: &str) -> IResult<&str, Vec<Expression>> {
+/// Parses comma separated expressions, where every comma must be followed by another expression.
+pub fn expression_list(input: Tokens) -> TokenResult<Vec<Expression>> {
+    let (mut input, first) = match expression(input) {
+        Ok(result) => result,
+        Err(nom::Err::Error(_)) => return Ok((input, Vec::new())),
+        Err(e) => return Err(e),
+    };
+    let mut elements = vec![first];
+    while let Ok((rest, _)) = symbol(COMMA_SYMBOL)(input) {
+        let (rest, element) = expect("expression", expression)(rest)?;
+        elements.push(element);
+        input = rest;
+    }
+    Ok((input, elements))
+}
+
+fn parse_list(input: Tokens) -> TokenResult<Expression> {
     map(
-        tuple((
-            multispace0,
-            char::<&str, Error<&str>>(LEFT_PAREN),
-            separated_list0(
-                tuple((
-                    multispace0,
-                    char::<&str, Error<&str>>(COMMA_CHAR),
-                    multispace0,
-                )),
-                parse_expression,
-            ),
-            multispace0,
-            char::<&str, Error<&str>>(RIGHT_PAREN),
-        )),
-        |(_, _, args, _, _)| args,
+        delimited(
+            symbol(LEFT_BRACKET_SYMBOL),
+            expression_list,
+            expect("`]`", symbol(RIGHT_BRACKET_SYMBOL)),
+        ),
+        Expression::ListValue,
     )(input)
 }
 
-fn parse_list(input: &str) -> IResult<&str, Expression> {
-    let (input, _) = multispace0(input)?;
-    let (input, _) = char(LEFT_BRACKET)(input)?;
-    let (input, _) = multispace0(input)?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::generators;
+    use proptest::prelude::*;
 
-    let (input, elements) = separated_list0(
-        delimited(multispace0, char(COMMA_CHAR), multispace0),
-        parse_expression,
-    )(input)?;
+    fn var(name: &str) -> Box<Expression> {
+        Box::new(Expression::Var(name.to_string()))
+    }
 
-    let (input, _) = multispace0(input)?;
-    let (input, _) = char(RIGHT_BRACKET)(input)?;
-    let (input, _) = multispace0(input)?;
+    fn int(value: i32) -> Box<Expression> {
+        Box::new(Expression::CInt(value))
+    }
 
-    Ok((input, Expression::ListValue(elements)))
-}
+    #[test]
+    fn test_parse_left_associativity() {
+        assert_eq!(
+            parse_expression("a - b - c"),
+            Ok(("", Expression::Sub(Box::new(Expression::Sub(var("a"), var("b"))), var("c"))))
+        );
+        assert_eq!(
+            parse_expression("a / b % c"),
+            Ok(("", Expression::Mod(Box::new(Expression::Div(var("a"), var("b"))), var("c"))))
+        );
+    }
+
+    #[test]
+    fn test_parse_power_is_right_associative() {
+        assert_eq!(
+            parse_expression("a ** b ** c"),
+            Ok(("", Expression::Pow(var("a"), Box::new(Expression::Pow(var("b"), var("c"))))))
+        );
+        assert_eq!(
+            parse_expression("2 * a ** 2"),
+            Ok(("", Expression::Mul(int(2), Box::new(Expression::Pow(var("a"), int(2))))))
+        );
+    }
+
+    #[test]
+    fn test_parse_unary_minus() {
+        assert_eq!(
+            parse_expression("-x * 2"),
+            Ok(("", Expression::Mul(Box::new(Expression::Neg(var("x"))), int(2))))
+        );
+        assert_eq!(
+            parse_expression("-x ** 2"),
+            Ok(("", Expression::Neg(Box::new(Expression::Pow(var("x"), int(2))))))
+        );
+        assert_eq!(
+            parse_expression("2 ** -1"),
+            Ok(("", Expression::Pow(int(2), int(-1))))
+        );
+        assert_eq!(
+            parse_expression("a - -(b)"),
+            Ok(("", Expression::Sub(var("a"), Box::new(Expression::Neg(var("b"))))))
+        );
+        assert_eq!(parse_expression("-5"), Ok(("", Expression::CInt(-5))));
+        assert_eq!(
+            parse_expression("-2147483648"),
+            Ok(("", Expression::CInt(i32::MIN)))
+        );
+    }
+
+    #[test]
+    fn test_parse_chained_comparison() {
+        assert_eq!(
+            parse_expression("0 <= i < n"),
+            Ok((
+                "",
+                Expression::Chain(
+                    int(0),
+                    vec![(Comparison::LTE, *var("i")), (Comparison::LT, *var("n"))],
+                )
+            ))
+        );
+        assert_eq!(
+            parse_expression("a == b != c and d"),
+            Ok((
+                "",
+                Expression::And(
+                    Box::new(Expression::Chain(
+                        var("a"),
+                        vec![(Comparison::EQ, *var("b")), (Comparison::NEQ, *var("c"))],
+                    )),
+                    var("d"),
+                )
+            ))
+        );
+        assert_eq!(
+            parse_expression("(a < b) == c"),
+            Ok((
+                "",
+                Expression::EQ(Box::new(Expression::LT(var("a"), var("b"))), var("c"))
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_not_binds_looser_than_comparisons() {
+        assert_eq!(
+            parse_expression("not a == b and c"),
+            Ok((
+                "",
+                Expression::And(
+                    Box::new(Expression::Not(Box::new(Expression::EQ(var("a"), var("b"))))),
+                    var("c"),
+                )
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_maybe_and_result_keywords() {
+        assert_eq!(
+            parse_expression("Just(unwrap(x)) "),
+            Ok((
+                "",
+                Expression::CJust(Box::new(Expression::Unwrap(var("x"))))
+            ))
+        );
+        assert_eq!(
+            parse_expression("isError(Err(\"e\")) or isNothing(Nothing)"),
+            Ok((
+                "",
+                Expression::Or(
+                    Box::new(Expression::IsError(Box::new(Expression::CErr(Box::new(
+                        Expression::CString("e".to_string())
+                    ))))),
+                    Box::new(Expression::IsNothing(Box::new(Expression::CNothing))),
+                )
+            ))
+        );
+        assert!(matches!(parse_expression("Ok 1"), Err(nom::Err::Failure(_))));
+    }
+
+    #[test]
+    fn test_parse_builtin_names_as_calls_and_identifiers() {
+        let call = |name: &str, args| Expression::FuncCall(name.to_string(), args, None);
+        assert_eq!(
+            parse_expression("range(0, n + 1)"),
+            Ok((
+                "",
+                call(
+                    "range",
+                    vec![
+                        Expression::CInt(0),
+                        Expression::Add(var("n"), Box::new(Expression::CInt(1))),
+                    ]
+                )
+            ))
+        );
+        assert_eq!(
+            parse_expression("unwrap_or(map(m, f), 0)"),
+            Ok((
+                "",
+                call(
+                    "unwrap_or",
+                    vec![call("map", vec![*var("m"), *var("f")]), Expression::CInt(0)]
+                )
+            ))
+        );
+        assert_eq!(
+            parse_expression("map + ok_or"),
+            Ok(("", Expression::Add(var("map"), var("ok_or"))))
+        );
+    }
+
+    #[test]
+    fn test_parse_dangling_operator_fails() {
+        assert!(matches!(parse_expression("a + "), Err(nom::Err::Failure(_))));
+        assert!(matches!(parse_expression("f(1, )"), Err(nom::Err::Failure(_))));
+        assert!(matches!(parse_expression("(a"), Err(nom::Err::Failure(_))));
+    }
 
-/// Parses an operator.
-fn operator<'a>(op: &'static str) -> impl FnMut(&'a str) -> IResult<&'a str, &'a str> {
-    delimited(multispace0, tag(op), multispace0)
+    proptest! {
+        #[test]
+        fn prop_parse_expression_never_panics(input in prop_oneof![any::<String>(), generators::token_soup()]) {
+            let _ = parse_expression(&input);
+        }
+    }
 }
@@ -0,0 +1,644 @@
+use crate::ir::ast::{
+    Comparison, Expression, FormalArgument, Function, Statement, Type, ValueConstructor,
+};
+use crate::ir::builtins::{
+    AND_THEN_BUILTIN, MAP_BUILTIN, OK_OR_BUILTIN, RANGE_BUILTIN, UNWRAP_OR_BUILTIN,
+};
+use crate::parser::operators::{
+    NEGATION_OPERATOR, NEGATION_PRECEDENCE, NOT_OPERATOR, NOT_PRECEDENCE, binary_operator,
+    comparison_operator, precedence,
+};
+use crate::parser::parser_common::{
+    ANY_TYPE, ASSERT_KEYWORD, BOOLEAN_TYPE, BREAK_KEYWORD, CASE_KEYWORD, CATCH_KEYWORD,
+    CONTINUE_KEYWORD, DATA_KEYWORD, DEF_KEYWORD, ELIF_KEYWORD, ELSE_KEYWORD, END_KEYWORD,
+    ERR_KEYWORD, FALSE_KEYWORD, FOR_KEYWORD, FUNCTION_ARROW, GLOBAL_KEYWORD, IF_KEYWORD,
+    IN_KEYWORD, INT_TYPE, IS_ERROR_KEYWORD, IS_NOTHING_KEYWORD, JUST_KEYWORD, LAMBDA_KEYWORD,
+    MAYBE_TYPE, NOTHING_KEYWORD, OK_KEYWORD, REAL_TYPE, RESULT_TYPE, RET_KEYWORD, STRING_TYPE,
+    TRUE_KEYWORD, TRY_KEYWORD, TRY_UNWRAP_KEYWORD, UNIT_TYPE, UNWRAP_KEYWORD, VAL_KEYWORD,
+    VAR_KEYWORD, WHEN_KEYWORD, WHILE_KEYWORD,
+};
+
+// Number of spaces used for each nesting level of a block
+const INDENT_WIDTH: usize = 4;
+
+/// Renders a program (a list of top-level statements) in the textual syntax accepted by `parser::parse`.
+pub fn print_program(statements: &[Statement]) -> String {
+    print_statement_list(statements, 0)
+}
+
+/// Renders a statement in the textual syntax accepted by `parse_statement`.
+pub fn print_statement(stmt: &Statement) -> String {
+    print_statement_at(stmt, 0)
+}
+
+/// Renders an expression in the textual syntax accepted by `parse_expression`,
+/// adding parentheses only where the grammar requires them.
+pub fn print_expression(exp: &Expression) -> String {
+    match exp {
+        Expression::CTrue => TRUE_KEYWORD.to_string(),
+        Expression::CFalse => FALSE_KEYWORD.to_string(),
+        Expression::CInt(value) => value.to_string(),
+        Expression::CReal(value) => print_real(*value),
+        Expression::CString(value) => format!("\"{}\"", value),
+        Expression::CVoid => "()".to_string(),
+        Expression::Default(exp) => print_expression(exp),
+
+        Expression::Var(name) => name.clone(),
+        Expression::FuncRef(func_signature) => func_signature.name.clone(),
+        Expression::FuncCall(name, args, _) => format!("{}({})", name, print_arguments(args)),
+
+        Expression::Add(..)
+        | Expression::Sub(..)
+        | Expression::Mul(..)
+        | Expression::Div(..)
+        | Expression::Mod(..)
+        | Expression::Pow(..)
+        | Expression::And(..)
+        | Expression::Or(..)
+        | Expression::EQ(..)
+        | Expression::NEQ(..)
+        | Expression::GT(..)
+        | Expression::LT(..)
+        | Expression::GTE(..)
+        | Expression::LTE(..) => print_binary(exp),
+        Expression::Chain(first, links) => print_chain(first, links),
+
+        // The conversions the type checker inserts are implicit in the source
+        Expression::ToReal(e) => print_expression(e),
+        Expression::Not(e) => format!("{} {}", NOT_OPERATOR, print_operand(e, NOT_PRECEDENCE)),
+        // A literal right after `-` would be read back as a negative literal
+        Expression::Neg(e) => match e.as_ref() {
+            Expression::CInt(_) | Expression::CReal(_) => {
+                format!("{}({})", NEGATION_OPERATOR, print_expression(e))
+            }
+            _ => format!(
+                "{}{}",
+                NEGATION_OPERATOR,
+                print_operand(e, NEGATION_PRECEDENCE)
+            ),
+        },
+
+        Expression::COk(e) => print_builtin(OK_KEYWORD, e),
+        Expression::CErr(e) => print_builtin(ERR_KEYWORD, e),
+        Expression::CJust(e) => print_builtin(JUST_KEYWORD, e),
+        Expression::CNothing => NOTHING_KEYWORD.to_string(),
+        Expression::Unwrap(e) => print_builtin(UNWRAP_KEYWORD, e),
+        Expression::IsError(e) => print_builtin(IS_ERROR_KEYWORD, e),
+        Expression::IsNothing(e) => print_builtin(IS_NOTHING_KEYWORD, e),
+        Expression::Propagate(e) => print_builtin(TRY_UNWRAP_KEYWORD, e),
+        Expression::Map(e, func) => print_binary_builtin(MAP_BUILTIN, e, func),
+        Expression::AndThen(e, func) => print_binary_builtin(AND_THEN_BUILTIN, e, func),
+        Expression::UnwrapOr(e, default) => print_binary_builtin(UNWRAP_OR_BUILTIN, e, default),
+        Expression::OkOr(e, error) => print_binary_builtin(OK_OR_BUILTIN, e, error),
+
+        Expression::ListValue(elements) => format!("[{}]", print_arguments(elements)),
+        Expression::Range(start, end) => print_binary_builtin(RANGE_BUILTIN, start, end),
+        Expression::Lambda(func) => print_lambda(func),
+        Expression::Constructor(name, args) => format!(
+            "{}({})",
+            name,
+            args.iter()
+                .map(|arg| print_expression(arg))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+/// Renders a type in the textual syntax accepted by `parse_type`.
+pub fn print_type(t: &Type) -> String {
+    match t {
+        Type::TInteger => INT_TYPE.to_string(),
+        Type::TBool => BOOLEAN_TYPE.to_string(),
+        Type::TReal => REAL_TYPE.to_string(),
+        Type::TString => STRING_TYPE.to_string(),
+        Type::TVoid => UNIT_TYPE.to_string(),
+        Type::TAny => ANY_TYPE.to_string(),
+        Type::TList(inner) => format!("[{}]", print_type(inner)),
+        Type::TTuple(elements) => format!("({})", print_types(elements)),
+        Type::TMaybe(inner) => format!("{}[{}]", MAYBE_TYPE, print_type(inner)),
+        Type::TResult(ok, err) => {
+            format!("{}[{}, {}]", RESULT_TYPE, print_type(ok), print_type(err))
+        }
+        Type::TFunction(ret, params) => format!(
+            "({}) {} {}",
+            print_types(params),
+            FUNCTION_ARROW,
+            print_type(ret)
+        ),
+        Type::TAlgebraicData(name, constructors) => format!(
+            "{} {}: {} {}",
+            DATA_KEYWORD,
+            name,
+            constructors
+                .iter()
+                .map(print_value_constructor)
+                .collect::<Vec<_>>()
+                .join(" "),
+            END_KEYWORD
+        ),
+    }
+}
+
+fn print_statement_at(stmt: &Statement, level: usize) -> String {
+    match stmt {
+        Statement::VarDeclaration(name, exp) => {
+            format!("{} {} = {}", VAR_KEYWORD, name, print_expression(exp))
+        }
+        Statement::ValDeclaration(name, exp) => {
+            format!("{} {} = {}", VAL_KEYWORD, name, print_expression(exp))
+        }
+        Statement::Assignment(name, exp) => format!("{} = {}", name, print_expression(exp)),
+        Statement::IfThenElse(cond, then_stmt, else_stmt) => format!(
+            "{} {}",
+            IF_KEYWORD,
+            print_conditional_branches(cond, then_stmt, else_stmt.as_deref(), level)
+        ),
+        Statement::When(exp, cases, default) => {
+            let mut text = format!("{} {}:\n", WHEN_KEYWORD, print_expression(exp));
+            for (value, body) in cases {
+                text.push_str(&format!(
+                    "{}{} {}{}\n",
+                    indent(level + 1),
+                    CASE_KEYWORD,
+                    print_expression(value),
+                    print_block(body, level + 1)
+                ));
+            }
+            if let Some(default) = default {
+                text.push_str(&format!(
+                    "{}{}{}\n",
+                    indent(level + 1),
+                    ELSE_KEYWORD,
+                    print_block(default, level + 1)
+                ));
+            }
+            text.push_str(&indent(level));
+            text.push_str(END_KEYWORD);
+            text
+        }
+        Statement::While(cond, body) => format!(
+            "{} {}{}",
+            WHILE_KEYWORD,
+            print_expression(cond),
+            print_block(body, level)
+        ),
+        Statement::For(var, iterable, body) => format!(
+            "{} {} {} {}{}",
+            FOR_KEYWORD,
+            var,
+            IN_KEYWORD,
+            print_expression(iterable),
+            print_block(body, level)
+        ),
+        Statement::TryCatch(body, name, handler) => format!(
+            "{}{} {} {}{}",
+            TRY_KEYWORD,
+            print_block(body, level),
+            CATCH_KEYWORD,
+            name,
+            print_block(handler, level)
+        ),
+        // A bare block has no syntax of its own: its statements are laid out one after the other
+        Statement::Block(stmts) => print_statement_list(stmts, level),
+        Statement::Sequence(first, second) => format!(
+            "{}{}\n{}{}",
+            print_statement_at(first, level),
+            terminator(first),
+            indent(level),
+            print_statement_at(second, level)
+        ),
+        Statement::Assert(left, right) => format!(
+            "{}({}, {})",
+            ASSERT_KEYWORD,
+            print_expression(left),
+            print_expression(right)
+        ),
+        Statement::AssertTrue(exp, msg) => {
+            format!("assertTrue({}, \"{}\")", print_expression(exp), msg)
+        }
+        Statement::AssertFalse(exp, msg) => {
+            format!("assertFalse({}, \"{}\")", print_expression(exp), msg)
+        }
+        Statement::AssertEQ(left, right, msg) => format!(
+            "assertEQ({}, {}, \"{}\")",
+            print_expression(left),
+            print_expression(right),
+            msg
+        ),
+        Statement::AssertNEQ(left, right, msg) => format!(
+            "assertNEQ({}, {}, \"{}\")",
+            print_expression(left),
+            print_expression(right),
+            msg
+        ),
+        Statement::AssertFails(msg) => format!("assertFails(\"{}\")", msg),
+        Statement::TestDef(func) => format!("test {}", print_function(func, level)),
+        Statement::ModTestDef(name, body) => {
+            format!("modTest {}{}", name, print_block(body, level))
+        }
+        Statement::FuncDef(func) => format!("{} {}", DEF_KEYWORD, print_function(func, level)),
+        Statement::SingleFuncCall(name, args, _) => format!("{}({})", name, print_arguments(args)),
+        Statement::Return(exp) => format!("{} {}", RET_KEYWORD, print_expression(exp)),
+        Statement::Break => BREAK_KEYWORD.to_string(),
+        Statement::Continue => CONTINUE_KEYWORD.to_string(),
+        Statement::Global(name) => format!("{} {}", GLOBAL_KEYWORD, name),
+        Statement::TypeDeclaration(name, constructors) => {
+            print_type(&Type::TAlgebraicData(name.clone(), constructors.clone()))
+        }
+        Statement::Print(exp) => format!("print({})", print_expression(exp)),
+        Statement::Comment(text) => text.clone(),
+    }
+}
+
+// Renders `cond: ... end` and what follows it: an else branch that is itself an
+// IfThenElse (rather than a Block) is printed as `elif`.
+fn print_conditional_branches(
+    cond: &Expression,
+    then_stmt: &Statement,
+    else_stmt: Option<&Statement>,
+    level: usize,
+) -> String {
+    let mut text = format!(
+        "{}{}",
+        print_expression(cond),
+        print_block(then_stmt, level)
+    );
+    match else_stmt {
+        Some(Statement::IfThenElse(cond, then_stmt, else_stmt)) => text.push_str(&format!(
+            " {} {}",
+            ELIF_KEYWORD,
+            print_conditional_branches(cond, then_stmt, else_stmt.as_deref(), level)
+        )),
+        Some(else_stmt) => text.push_str(&format!(
+            " {}{}",
+            ELSE_KEYWORD,
+            print_block(else_stmt, level)
+        )),
+        None => {}
+    }
+    text
+}
+
+// Lays out statements one per line, separated by `;`.
+fn print_statement_list(stmts: &[Statement], level: usize) -> String {
+    let mut text = String::new();
+    for (i, stmt) in stmts.iter().enumerate() {
+        if i > 0 {
+            text.push_str(terminator(&stmts[i - 1]));
+            text.push('\n');
+            text.push_str(&indent(level));
+        }
+        text.push_str(&print_statement_at(stmt, level));
+    }
+    text
+}
+
+// A `#` comment runs to the end of the line, so statements that end with a
+// comment are not followed by `;`.
+fn terminator(stmt: &Statement) -> &'static str {
+    match stmt {
+        Statement::Comment(_) => "",
+        Statement::Block(stmts) => stmts.last().map_or(";", terminator),
+        Statement::Sequence(_, second) => terminator(second),
+        _ => ";",
+    }
+}
+
+// Renders `: stmt; ...; end`, with the statements indented one level deeper than `level`.
+// A body that is not a Statement::Block is printed as a block holding that single statement.
+fn print_block(body: &Statement, level: usize) -> String {
+    let stmts = match body {
+        Statement::Block(stmts) => stmts.clone(),
+        other => vec![other.clone()],
+    };
+    let mut text = ":\n".to_string();
+    for stmt in &stmts {
+        text.push_str(&indent(level + 1));
+        text.push_str(&print_statement_at(stmt, level + 1));
+        text.push_str(terminator(stmt));
+        text.push('\n');
+    }
+    text.push_str(&indent(level));
+    text.push_str(END_KEYWORD);
+    text
+}
+
+fn print_function(func: &Function, level: usize) -> String {
+    let body = match &func.body {
+        Some(body) => print_block(body, level),
+        None => format!(": {}", END_KEYWORD),
+    };
+    format!(
+        "{}({}) {} {}{}",
+        func.name,
+        print_formal_arguments(&func.params),
+        FUNCTION_ARROW,
+        print_type(&func.kind),
+        body
+    )
+}
+
+// parse_lambda only accepts a single `ret` statement between `:` and `end`
+fn print_lambda(func: &Function) -> String {
+    let header = format!(
+        "{} ({}) {} {}",
+        LAMBDA_KEYWORD,
+        print_formal_arguments(&func.params),
+        FUNCTION_ARROW,
+        print_type(&func.kind)
+    );
+    match func.body.as_deref() {
+        Some(Statement::Block(stmts)) if stmts.len() == 1 => match &stmts[0] {
+            Statement::Return(exp) => format!(
+                "{}: {} {} {}",
+                header,
+                RET_KEYWORD,
+                print_expression(exp),
+                END_KEYWORD
+            ),
+            other => format!("{}{}", header, print_block(other, 0)),
+        },
+        Some(body) => format!("{}{}", header, print_block(body, 0)),
+        None => format!("{}: {}", header, END_KEYWORD),
+    }
+}
+
+fn print_formal_arguments(params: &[FormalArgument]) -> String {
+    params
+        .iter()
+        .map(|arg| format!("{}: {}", arg.argument_name, print_type(&arg.argument_type)))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn print_value_constructor(constructor: &ValueConstructor) -> String {
+    let mut text = format!("| {}", constructor.name);
+    for t in &constructor.types {
+        text.push(' ');
+        text.push_str(&print_type(t));
+    }
+    text
+}
+
+fn print_builtin(keyword: &str, exp: &Expression) -> String {
+    format!("{}({})", keyword, print_expression(exp))
+}
+
+fn print_binary_builtin(keyword: &str, lhs: &Expression, rhs: &Expression) -> String {
+    format!(
+        "{}({}, {})",
+        keyword,
+        print_expression(lhs),
+        print_expression(rhs)
+    )
+}
+
+fn print_arguments(args: &[Expression]) -> String {
+    args.iter()
+        .map(print_expression)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn print_types(types: &[Type]) -> String {
+    types.iter().map(print_type).collect::<Vec<_>>().join(", ")
+}
+
+fn print_binary(exp: &Expression) -> String {
+    let (op, left, right) =
+        binary_operator(exp).expect("every binary expression has an entry in BINARY_OPERATORS");
+    format!(
+        "{} {} {}",
+        print_operand(left, op.left_precedence()),
+        op.symbol,
+        print_operand(right, op.right_precedence)
+    )
+}
+
+// Every comparison binds alike, so the first operand is parenthesized like the
+// left operand of any of them
+fn print_chain(first: &Expression, links: &[(Comparison, Expression)]) -> String {
+    let mut text = print_operand(first, comparison_operator(Comparison::EQ).left_precedence());
+    for (comparison, operand) in links {
+        let op = comparison_operator(*comparison);
+        text.push_str(&format!(
+            " {} {}",
+            op.symbol,
+            print_operand(operand, op.right_precedence)
+        ));
+    }
+    text
+}
+
+fn print_operand(exp: &Expression, min_precedence: u8) -> String {
+    if precedence(exp) < min_precedence {
+        format!("({})", print_expression(exp))
+    } else {
+        print_expression(exp)
+    }
+}
+
+// parse_number only recognizes reals written as `digits.digits`
+fn print_real(value: f64) -> String {
+    let text = value.to_string();
+    if text.contains('.') {
+        text
+    } else {
+        format!("{}.0", text)
+    }
+}
+
+fn indent(level: usize) -> String {
+    " ".repeat(level * INDENT_WIDTH)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::generators;
+    use crate::parser::{parse, parse_expression, parse_statement, parse_type};
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_print_expression_parenthesizes_only_when_needed() {
+        let exp = Expression::Mul(
+            Box::new(Expression::Add(
+                Box::new(Expression::Var("a".to_string())),
+                Box::new(Expression::CInt(1)),
+            )),
+            Box::new(Expression::Sub(
+                Box::new(Expression::Var("b".to_string())),
+                Box::new(Expression::CInt(-2)),
+            )),
+        );
+        assert_eq!(print_expression(&exp), "(a + 1) * (b - -2)");
+
+        let exp = Expression::Sub(
+            Box::new(Expression::Sub(
+                Box::new(Expression::CInt(1)),
+                Box::new(Expression::CInt(2)),
+            )),
+            Box::new(Expression::CInt(3)),
+        );
+        assert_eq!(print_expression(&exp), "1 - 2 - 3");
+    }
+
+    #[test]
+    fn test_print_power_and_negation() {
+        let exp = Expression::Pow(
+            Box::new(Expression::Pow(
+                Box::new(Expression::Var("a".to_string())),
+                Box::new(Expression::Neg(Box::new(Expression::Var("b".to_string())))),
+            )),
+            Box::new(Expression::CInt(2)),
+        );
+        assert_eq!(print_expression(&exp), "(a ** -b) ** 2");
+
+        let exp = Expression::Neg(Box::new(Expression::CInt(5)));
+        assert_eq!(print_expression(&exp), "-(5)");
+
+        let exp = Expression::Pow(
+            Box::new(Expression::CInt(-1)),
+            Box::new(Expression::Var("n".to_string())),
+        );
+        assert_eq!(print_expression(&exp), "(-1) ** n");
+    }
+
+    #[test]
+    fn test_print_nested_comparison() {
+        let exp = Expression::LT(
+            Box::new(Expression::LT(
+                Box::new(Expression::Var("a".to_string())),
+                Box::new(Expression::Var("b".to_string())),
+            )),
+            Box::new(Expression::Var("c".to_string())),
+        );
+        assert_eq!(print_expression(&exp), "(a < b) < c");
+    }
+
+    #[test]
+    fn test_print_chained_comparison() {
+        let exp = Expression::Chain(
+            Box::new(Expression::Add(
+                Box::new(Expression::Var("a".to_string())),
+                Box::new(Expression::CInt(1)),
+            )),
+            vec![
+                (Comparison::LT, Expression::Var("b".to_string())),
+                (
+                    Comparison::GTE,
+                    Expression::LT(
+                        Box::new(Expression::Var("c".to_string())),
+                        Box::new(Expression::Var("d".to_string())),
+                    ),
+                ),
+            ],
+        );
+        assert_eq!(print_expression(&exp), "a + 1 < b >= (c < d)");
+    }
+
+    #[test]
+    fn test_print_real() {
+        assert_eq!(print_expression(&Expression::CReal(10.0)), "10.0");
+        assert_eq!(print_expression(&Expression::CReal(-0.25)), "-0.25");
+    }
+
+    #[test]
+    fn test_print_statement() {
+        let stmt = Statement::While(
+            Box::new(Expression::CTrue),
+            Box::new(Statement::Block(vec![Statement::Assignment(
+                "x".to_string(),
+                Box::new(Expression::CInt(1)),
+            )])),
+        );
+        assert_eq!(print_statement(&stmt), "while True:\n    x = 1;\nend");
+    }
+
+    #[test]
+    fn test_print_elif_and_when() {
+        let body = |n| {
+            Box::new(Statement::Block(vec![Statement::Assignment(
+                "y".to_string(),
+                Box::new(Expression::CInt(n)),
+            )]))
+        };
+        let x = || Box::new(Expression::Var("x".to_string()));
+        let stmt = Statement::IfThenElse(
+            x(),
+            body(1),
+            Some(Box::new(Statement::IfThenElse(x(), body(2), Some(body(3))))),
+        );
+        assert_eq!(
+            print_statement(&stmt),
+            "if x:\n    y = 1;\nend elif x:\n    y = 2;\nend else:\n    y = 3;\nend"
+        );
+
+        let stmt = Statement::When(x(), vec![(Expression::CInt(1), *body(1))], Some(body(0)));
+        assert_eq!(
+            print_statement(&stmt),
+            "when x:\n    case 1:\n        y = 1;\n    end\n    else:\n        y = 0;\n    end\nend"
+        );
+    }
+
+    #[test]
+    fn test_print_comments() {
+        let program = vec![
+            Statement::Comment("# counts to ten".to_string()),
+            Statement::VarDeclaration("x".to_string(), Box::new(Expression::CInt(0))),
+            Statement::While(
+                Box::new(Expression::LT(
+                    Box::new(Expression::Var("x".to_string())),
+                    Box::new(Expression::CInt(10)),
+                )),
+                Box::new(Statement::Block(vec![
+                    Statement::Comment("/* step */".to_string()),
+                    Statement::Assignment(
+                        "x".to_string(),
+                        Box::new(Expression::Add(
+                            Box::new(Expression::Var("x".to_string())),
+                            Box::new(Expression::CInt(1)),
+                        )),
+                    ),
+                ])),
+            ),
+        ];
+        assert_eq!(
+            print_program(&program),
+            "# counts to ten\nvar x = 0;\nwhile x < 10:\n    /* step */\n    x = x + 1;\nend"
+        );
+    }
+
+    #[test]
+    fn test_print_function_type() {
+        let t = Type::TFunction(Box::new(Type::TBool), vec![Type::TInteger, Type::TReal]);
+        assert_eq!(print_type(&t), "(int, float) -> bool");
+    }
+
+    proptest! {
+        #[test]
+        fn prop_expression_round_trip(exp in generators::expression()) {
+            let text = print_expression(&exp);
+            prop_assert_eq!(parse_expression(&text), Ok(("", exp)), "printed as: {}", text);
+        }
+
+        #[test]
+        fn prop_type_round_trip(t in generators::any_type()) {
+            let text = print_type(&t);
+            prop_assert_eq!(parse_type(&text), Ok(("", t)), "printed as: {}", text);
+        }
+
+        #[test]
+        fn prop_statement_round_trip(stmt in generators::statement()) {
+            let text = print_statement(&stmt);
+            prop_assert_eq!(parse_statement(&text), Ok(("", stmt)), "printed as: {}", text);
+        }
+
+        #[test]
+        fn prop_program_round_trip(program in generators::program()) {
+            let text = print_program(&program);
+            prop_assert_eq!(parse(&text), Ok(("", program)), "printed as: {}", text);
+        }
+    }
+}
@@ -0,0 +1,24 @@
+//! Helpers shared by the tests of the interpreter and of the bytecode VM.
+
+use crate::environment::environment::Environment;
+use crate::ir::ast::{Expression, Statement, Type};
+use crate::parser::parse;
+use crate::type_checker::check_stmt;
+
+// Parses and type checks the source, printing the given variables at the end
+pub fn checked_program(source: &str, printed: &[&str]) -> Result<Statement, String> {
+    let (_, mut stmts) = parse(source).expect("program should parse");
+    for name in printed {
+        stmts.push(Statement::Print(Box::new(Expression::Var(
+            name.to_string(),
+        ))));
+    }
+    let mut program = Statement::Block(stmts);
+    let env: Environment<Type> = Environment::new();
+    check_stmt(&mut program, &env)?;
+    Ok(program)
+}
+
+pub fn lines(lines: &[&str]) -> Result<Vec<String>, String> {
+    Ok(lines.iter().map(|line| line.to_string()).collect())
+}
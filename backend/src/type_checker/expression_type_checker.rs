@@ -1,67 +1,88 @@
 use std::fmt::format;
+use std::rc::Rc;
 
 use crate::environment::environment::{Environment, FuncOrVar};
-use crate::ir::ast::{Expression, FuncSignature, Function, Name, Type};
+use crate::ir::ast::{
+    Comparison, Expression, FormalArgument, FuncSignature, Function, Name, Resolution, Type,
+};
+use crate::ir::builtins::{builtin_call, builtin_operands};
 use crate::{show, show_counter};
 
 type ErrorMessage = String;
 
-pub fn check_expr(exp: Expression, env: &Environment<Type>) -> Result<Type, ErrorMessage> {
-    match exp.clone() {
+pub fn check_expr(exp: &mut Expression, env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+    match exp {
         Expression::CTrue => Ok(Type::TBool),
         Expression::CFalse => Ok(Type::TBool),
         Expression::CVoid => Ok(Type::TVoid),
         Expression::CInt(_) => Ok(Type::TInteger),
         Expression::CReal(_) => Ok(Type::TReal),
         Expression::CString(_) => Ok(Type::TString),
-        Expression::Add(l, r) => check_add_arithmetic_expression(*l, *r, env),
-        Expression::Sub(l, r) => check_bin_arithmetic_expression(*l, *r, env),
-        Expression::Mul(l, r) => check_mul_arithmetic_expression(*l, *r, env),
-        Expression::Div(l, r) => check_bin_arithmetic_expression(*l, *r, env),
-        Expression::And(l, r) => check_bin_boolean_expression(*l, *r, env),
-        Expression::Or(l, r) => check_bin_boolean_expression(*l, *r, env),
-        Expression::Not(e) => check_not_expression(*e, env),
-        Expression::EQ(l, r) => check_bin_relational_expression(*l, *r, env),
-        Expression::NEQ(l, r) => check_bin_relational_expression(*l, *r, env),
-        Expression::GT(l, r) => check_bin_relational_expression(*l, *r, env),
-        Expression::LT(l, r) => check_bin_relational_expression(*l, *r, env),
-        Expression::GTE(l, r) => check_bin_relational_expression(*l, *r, env),
-        Expression::LTE(l, r) => check_bin_relational_expression(*l, *r, env),
+        Expression::Default(e) => check_expr(e, env),
+        Expression::Add(l, r) => check_add_arithmetic_expression(l, r, env),
+        Expression::Sub(l, r) => check_bin_arithmetic_expression(l, r, env),
+        Expression::Mul(l, r) => check_mul_arithmetic_expression(l, r, env),
+        Expression::Div(l, r) => check_bin_arithmetic_expression(l, r, env),
+        Expression::Mod(l, r) => check_bin_arithmetic_expression(l, r, env),
+        Expression::Pow(l, r) => check_bin_arithmetic_expression(l, r, env),
+        Expression::Neg(e) => check_neg_expression(e, env),
+        Expression::ToReal(e) => check_to_real(e, env),
+        Expression::And(l, r) => check_bin_boolean_expression(l, r, env),
+        Expression::Or(l, r) => check_bin_boolean_expression(l, r, env),
+        Expression::Not(e) => check_not_expression(e, env),
+        Expression::EQ(l, r) => check_bin_relational_expression(l, r, env),
+        Expression::NEQ(l, r) => check_bin_relational_expression(l, r, env),
+        Expression::GT(l, r) => check_bin_relational_expression(l, r, env),
+        Expression::LT(l, r) => check_bin_relational_expression(l, r, env),
+        Expression::GTE(l, r) => check_bin_relational_expression(l, r, env),
+        Expression::LTE(l, r) => check_bin_relational_expression(l, r, env),
+        Expression::Chain(first, links) => check_chain_expression(first, links, env),
         Expression::Var(name) => {
+            let name = name.clone();
             show_counter_tp_exp();
             show_tp_exp(format!("Check Var:"));
             show_tp_exp(format!("Exp: {:?}", exp));
             show_tp_exp(format!("Env: {:?}", env));
             check_var_name(name, env)
         }
-        Expression::COk(e) => check_result_ok(*e, env),
-        Expression::CErr(e) => check_result_err(*e, env),
-        Expression::CJust(e) => check_maybe_just(*e, env),
+        Expression::COk(e) => check_result_ok(e, env),
+        Expression::CErr(e) => check_result_err(e, env),
+        Expression::CJust(e) => check_maybe_just(e, env),
         Expression::CNothing => Ok(Type::TMaybe(Box::new(Type::TAny))),
-        Expression::IsError(e) => check_iserror_type(*e, env),
-        Expression::IsNothing(e) => check_isnothing_type(*e, env),
-        Expression::Unwrap(e) => check_unwrap_type(*e, env),
-        Expression::Propagate(e) => check_propagate_type(*e, env),
-        Expression::ListValue(elements) => check_list_value(&elements, env),
-        Expression::Constructor(name, args) => check_adt_constructor(name, args, env),
-        Expression::FuncCall(func_name, exp_vec) => {
+        Expression::IsError(e) => check_iserror_type(e, env),
+        Expression::IsNothing(e) => check_isnothing_type(e, env),
+        Expression::Unwrap(e) => check_unwrap_type(e, env),
+        Expression::Propagate(e) => check_propagate_type(e, env),
+        Expression::Map(e, func) => check_map_type(e, func, env),
+        Expression::AndThen(e, func) => check_and_then_type(e, func, env),
+        Expression::UnwrapOr(e, default) => check_unwrap_or_type(e, default, env),
+        Expression::OkOr(e, error) => check_ok_or_type(e, error, env),
+        Expression::ListValue(elements) => check_list_value(elements, env),
+        Expression::Range(start, end) => check_range(start, end, env),
+        Expression::Constructor(name, args) => check_adt_constructor(name.clone(), args, env),
+        Expression::FuncCall(func_name, exp_vec, resolution) => {
             show_counter_tp_exp();
             show_tp_exp(format!("Func Call:"));
-            show_tp_exp(format!("Exp: {:?}", exp));
+            show_tp_exp(format!("Exp: {}({:?})", func_name, exp_vec));
             show_tp_exp(format!("Env: {:?}", env));
-            check_func_call(func_name.clone(), exp_vec.clone(), env)
+            check_func_call(func_name.clone(), exp_vec, resolution, env)
         }
 
+        Expression::FuncRef(func_signature) => match env.lookup_function(func_signature) {
+            Some(func) => Ok(func_to_type(func)),
+            None => Err(format!("Function {} was never declared", func_signature)),
+        },
+
         Expression::Lambda(func) => {
             show_counter_tp_exp();
             show_tp_exp(format!("Check Lambda:"));
-            show_tp_exp(format!("Exp: {:?}", exp));
+            show_tp_exp(format!("Exp: {:?}", func));
             show_tp_exp(format!("Env: {:?}", env));
-            check_lambda(&func)
+            check_lambda(func)
         }
         _ => Err(format!(
             "Type checker still does not support expression of type {:?}",
-            exp.clone()
+            exp
         )),
     }
 }
@@ -80,72 +101,185 @@ pub fn func_to_type(func: &Function) -> Type {
 
 pub fn check_func_call(
     func_name: Name,
-    exp_vector: Vec<Expression>,
+    exp_vector: &mut [Expression],
+    resolution: &mut Option<Resolution>,
     env: &Environment<Type>,
 ) -> Result<Type, ErrorMessage> {
+    if let Some(mut builtin) = builtin_call(&func_name, exp_vector)
+        && env.lookup_functions_by_name(&func_name).is_empty()
+    {
+        let builtin_type = check_expr(&mut builtin, env)?;
+        // The call keeps the conversions inserted in the operands of the builtin
+        for (arg, operand) in exp_vector.iter_mut().zip(builtin_operands(builtin)) {
+            *arg = operand;
+        }
+        *resolution = Some(Resolution::Builtin);
+        return Ok(builtin_type);
+    }
+    // The types each argument may have. A function given by name has one for
+    // each of its overloads, and the overload of the call picks one of them
     let mut actual_arg_types = Vec::new();
-    for arg in exp_vector.iter() {
+    for arg in exp_vector.iter_mut() {
         match arg {
             Expression::Var(name) => match env.lookup_var_or_func(name) {
                 Some(FuncOrVar::Var((_, var_type))) => {
-                    actual_arg_types.push(var_type);
+                    actual_arg_types.push(vec![var_type]);
                 }
-                Some(FuncOrVar::Func(func)) => {
-                    actual_arg_types.push(func_to_type(&func));
+                Some(FuncOrVar::Func(..)) => {
+                    let overloads = env.lookup_functions_by_name(name);
+                    actual_arg_types
+                        .push(overloads.iter().map(|func| func_to_type(func)).collect());
                 }
                 None => {
                     return Err(format!("Identifier '{}' was never declared", name));
                 }
             },
             _ => {
-                let arg_type = check_expr(arg.clone(), env)?;
-                actual_arg_types.push(arg_type);
+                let arg_type = check_expr(arg, env)?;
+                actual_arg_types.push(vec![arg_type]);
             }
         }
     }
-    let func_signature = FuncSignature {
+    let func = resolve_overload(&func_name, &actual_arg_types, env)?;
+    for ((arg, arg_types), param) in exp_vector
+        .iter_mut()
+        .zip(&actual_arg_types)
+        .zip(&func.params)
+    {
+        match (&*arg, &param.argument_type) {
+            (Expression::Var(name), Type::TFunction(_, param_types))
+                if matches!(env.lookup_var_or_func(name), Some(FuncOrVar::Func(..))) =>
+            {
+                *arg = Expression::FuncRef(FuncSignature {
+                    name: name.clone(),
+                    argument_types: param_types.clone(),
+                });
+            }
+            (_, Type::TReal) if *arg_types == [Type::TInteger] => convert_to_real(arg),
+            _ => {}
+        }
+    }
+    *resolution = Some(Resolution::Overload(FuncSignature::from_func(&func)));
+    return Ok(func.kind.clone());
+}
+
+// Wraps an expression giving an int, or an int in a Just or an Ok, in its
+// conversion to real
+fn convert_to_real(exp: &mut Expression) {
+    let int = std::mem::replace(exp, Expression::CVoid);
+    *exp = Expression::ToReal(Box::new(int));
+}
+
+// Picks the overload of a function that a call with arguments of the given
+// types runs. An overload taking the arguments as they are comes first; then
+// those that widen the fewest int arguments to real, and a tie between these
+// makes the call ambiguous
+fn resolve_overload(
+    func_name: &Name,
+    actual_arg_types: &[Vec<Type>],
+    env: &Environment<Type>,
+) -> Result<Rc<Function>, ErrorMessage> {
+    let call_signature = FuncSignature {
         name: func_name.clone(),
-        argument_types: actual_arg_types.clone(),
+        argument_types: actual_arg_types
+            .iter()
+            .filter_map(|arg_types| arg_types.first().cloned())
+            .collect(),
     };
+    if actual_arg_types
+        .iter()
+        .all(|arg_types| arg_types.len() == 1)
+        && let Some(func) = env.lookup_function(&call_signature)
+    {
+        return Ok(func.clone());
+    }
 
-    let func = env.lookup_function(&func_signature);
-    if func.is_none() {
+    let candidates = env.lookup_functions_by_name(func_name);
+    if candidates.is_empty() {
         return Err(format!(
             "Function {} was called but never declared",
-            func_signature
+            call_signature
         ));
     }
-    let func = func.unwrap();
 
-    let mut formal_arg_types = Vec::new();
+    let mut best: Vec<&Rc<Function>> = Vec::new();
+    let mut fewest_widenings = usize::MAX;
+    for func in &candidates {
+        let Some(widenings) = count_widenings(&func.params, actual_arg_types) else {
+            continue;
+        };
+        if widenings < fewest_widenings {
+            fewest_widenings = widenings;
+            best.clear();
+        }
+        if widenings == fewest_widenings {
+            best.push(func);
+        }
+    }
 
-    for param in func.params.iter() {
-        formal_arg_types.push(param.argument_type.clone());
+    let candidate_list = || {
+        candidates
+            .iter()
+            .map(|func| FuncSignature::from_func(func).to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    match best.as_slice() {
+        [func] => Ok(Rc::clone(func)),
+        [] => Err(format!(
+            "No overload of function {} accepts the call {}. Candidates: {}",
+            func_name,
+            call_signature,
+            candidate_list()
+        )),
+        _ => Err(format!(
+            "Call {} is ambiguous between the overloads: {}",
+            call_signature,
+            best.iter()
+                .map(|func| FuncSignature::from_func(func).to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )),
     }
+}
 
-    for (formal_type, actual_type) in formal_arg_types.iter().zip(actual_arg_types.iter()) {
-        if formal_type != actual_type {
-            return Err(format!(
-                "Mismatched types in function {} call \n
-            Expected:{:?}\n
-            Received: {:?}",
-                func_signature, formal_arg_types, actual_arg_types
-            ));
+// How many int arguments must be widened to real to pass them to the formal
+// arguments, or None when some argument cannot be passed at all
+fn count_widenings(params: &[FormalArgument], actual_arg_types: &[Vec<Type>]) -> Option<usize> {
+    if params.len() != actual_arg_types.len() {
+        return None;
+    }
+    let mut widenings = 0;
+    for (param, arg_types) in params.iter().zip(actual_arg_types) {
+        if arg_types.contains(&param.argument_type) {
+            continue;
+        }
+        match (&param.argument_type, arg_types.as_slice()) {
+            (Type::TReal, [Type::TInteger]) => widenings += 1,
+            _ => return None,
         }
     }
-    return Ok(func.kind.clone());
+    Some(widenings)
 }
 
 fn check_var_name(name: Name, env: &Environment<Type>) -> Result<Type, ErrorMessage> {
     match env.lookup(&name) {
         Some((_, t)) => Ok(t.clone()),
+        None if env.ended_variables.contains(&name) => Err(format!(
+            "[Name Error] '{}' is not defined here, since it was declared in a block that has ended.",
+            name
+        )),
+        None if env.hidden_from_function(&name) => Err(format!(
+            "[Name Error] '{}' is not defined here, since function '{}' may run before '{}' is declared.",
+            name, env.current_func, name
+        )),
         None => Err(format!("[Name Error] '{}' is not defined.", name)),
     }
 }
 
 fn check_bin_arithmetic_expression(
-    left: Expression,
-    right: Expression,
+    left: &mut Expression,
+    right: &mut Expression,
     env: &Environment<Type>,
 ) -> Result<Type, ErrorMessage> {
     let left_type = check_expr(left, env)?;
@@ -161,8 +295,8 @@ fn check_bin_arithmetic_expression(
 }
 
 fn check_add_arithmetic_expression(
-    left: Expression,
-    right: Expression,
+    left: &mut Expression,
+    right: &mut Expression,
     env: &Environment<Type>,
 ) -> Result<Type, ErrorMessage> {
     let left_type = check_expr(left, env)?;
@@ -179,8 +313,8 @@ fn check_add_arithmetic_expression(
 }
 
 fn check_mul_arithmetic_expression(
-    left: Expression,
-    right: Expression,
+    left: &mut Expression,
+    right: &mut Expression,
     env: &Environment<Type>,
 ) -> Result<Type, ErrorMessage> {
     let left_type = check_expr(left, env)?;
@@ -198,8 +332,8 @@ fn check_mul_arithmetic_expression(
 }
 
 fn check_bin_boolean_expression(
-    left: Expression,
-    right: Expression,
+    left: &mut Expression,
+    right: &mut Expression,
     env: &Environment<Type>,
 ) -> Result<Type, ErrorMessage> {
     let left_type = check_expr(left, env)?;
@@ -210,7 +344,23 @@ fn check_bin_boolean_expression(
     }
 }
 
-fn check_not_expression(exp: Expression, env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+fn check_neg_expression(
+    exp: &mut Expression,
+    env: &Environment<Type>,
+) -> Result<Type, ErrorMessage> {
+    let exp_type = check_expr(exp, env)?;
+
+    match exp_type {
+        Type::TInteger => Ok(Type::TInteger),
+        Type::TReal => Ok(Type::TReal),
+        _ => Err(String::from("[Type Error] expecting a numeric type value.")),
+    }
+}
+
+fn check_not_expression(
+    exp: &mut Expression,
+    env: &Environment<Type>,
+) -> Result<Type, ErrorMessage> {
     let exp_type = check_expr(exp, env)?;
 
     match exp_type {
@@ -220,8 +370,8 @@ fn check_not_expression(exp: Expression, env: &Environment<Type>) -> Result<Type
 }
 
 fn check_bin_relational_expression(
-    left: Expression,
-    right: Expression,
+    left: &mut Expression,
+    right: &mut Expression,
     env: &Environment<Type>,
 ) -> Result<Type, ErrorMessage> {
     let left_type = check_expr(left, env)?;
@@ -236,17 +386,46 @@ fn check_bin_relational_expression(
     }
 }
 
-fn check_result_ok(exp: Expression, env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+fn check_to_real(exp: &mut Expression, env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+    match check_expr(exp, env)? {
+        Type::TInteger => Ok(Type::TReal),
+        Type::TMaybe(t) if *t == Type::TInteger => Ok(Type::TMaybe(Box::new(Type::TReal))),
+        Type::TResult(t, err) if *t == Type::TInteger => {
+            Ok(Type::TResult(Box::new(Type::TReal), err))
+        }
+        exp_type => Err(format!(
+            "[Type Error] only an int can be converted to a real, but found {}.",
+            exp_type
+        )),
+    }
+}
+
+fn check_chain_expression(
+    first: &mut Expression,
+    links: &mut [(Comparison, Expression)],
+    env: &Environment<Type>,
+) -> Result<Type, ErrorMessage> {
+    let operands = std::iter::once(first).chain(links.iter_mut().map(|(_, operand)| operand));
+    for operand in operands {
+        match check_expr(operand, env)? {
+            Type::TInteger | Type::TReal => {}
+            _ => return Err(String::from("[Type Error] expecting numeric type values.")),
+        }
+    }
+    Ok(Type::TBool)
+}
+
+fn check_result_ok(exp: &mut Expression, env: &Environment<Type>) -> Result<Type, ErrorMessage> {
     let exp_type = check_expr(exp, env)?;
     return Ok(Type::TResult(Box::new(exp_type), Box::new(Type::TAny)));
 }
 
-fn check_result_err(exp: Expression, env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+fn check_result_err(exp: &mut Expression, env: &Environment<Type>) -> Result<Type, ErrorMessage> {
     let exp_type = check_expr(exp, env)?;
     return Ok(Type::TResult(Box::new(Type::TAny), Box::new(exp_type)));
 }
 
-fn check_unwrap_type(exp: Expression, env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+fn check_unwrap_type(exp: &mut Expression, env: &Environment<Type>) -> Result<Type, ErrorMessage> {
     let exp_type = check_expr(exp, env)?;
 
     match exp_type {
@@ -258,24 +437,204 @@ fn check_unwrap_type(exp: Expression, env: &Environment<Type>) -> Result<Type, E
     }
 }
 
-fn check_propagate_type(exp: Expression, env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+// `tryUnwrap` returns a Nothing or an Err from the enclosing function, so this
+// must return a Maybe, or a Result with the same type of error
+fn check_propagate_type(
+    exp: &mut Expression,
+    env: &Environment<Type>,
+) -> Result<Type, ErrorMessage> {
     let exp_type = check_expr(exp, env)?;
+    if !matches!(exp_type, Type::TMaybe(_) | Type::TResult(_, _)) {
+        return Err(String::from(
+            "[Type Error] expecting a maybe or result type value.",
+        ));
+    }
+    let Some(current_func) = env
+        .lookup_function(&env.current_func)
+        .filter(|_| env.in_function())
+    else {
+        return Err(String::from(
+            "[Type Error] 'tryUnwrap' can only be used inside a function.",
+        ));
+    };
+
+    match (exp_type, &current_func.kind) {
+        (Type::TMaybe(t), Type::TMaybe(_)) => Ok(*t),
+        (Type::TResult(tl, err), Type::TResult(_, func_err)) if fits(&err, func_err) => Ok(*tl),
+        (exp_type, func_type) => Err(format!(
+            "[Type Error] 'tryUnwrap' cannot return the failure of a value of type {} from function '{}', which returns {}.",
+            exp_type, env.current_func, func_type
+        )),
+    }
+}
 
+// Whether a value of the actual type may be given where the expected type is
+// required. Values such as Nothing or Ok(1) leave part of their type unknown
+pub fn fits(actual: &Type, expected: &Type) -> bool {
+    match (actual, expected) {
+        (Type::TAny, _) => true,
+        (Type::TMaybe(actual), Type::TMaybe(expected))
+        | (Type::TList(actual), Type::TList(expected)) => fits(actual, expected),
+        (Type::TResult(actual_ok, actual_err), Type::TResult(expected_ok, expected_err)) => {
+            fits(actual_ok, expected_ok) && fits(actual_err, expected_err)
+        }
+        _ => actual == expected,
+    }
+}
+
+// The type of a function given to a combinator, either by its name or as a
+// lambda. Of the overloads of a function given by name, the combinator calls
+// the one taking the value as it is, or else the one taking it as a real
+fn check_function_argument(
+    exp: &mut Expression,
+    inner_type: &Type,
+    env: &Environment<Type>,
+) -> Result<Type, ErrorMessage> {
+    if let Expression::Var(name) = exp
+        && let Some(FuncOrVar::Func(..)) = env.lookup_var_or_func(name)
+    {
+        let overloads = env.lookup_functions_by_name(name);
+        let taking = |param_type: &Type| {
+            overloads.iter().find(|func| {
+                matches!(func.params.as_slice(), [param] if param.argument_type == *param_type)
+            })
+        };
+        let widened = taking(&Type::TReal).filter(|_| *inner_type == Type::TInteger);
+        if let Some(func) = taking(inner_type).or(widened).or(overloads.first()) {
+            *exp = Expression::FuncRef(FuncSignature::from_func(func));
+        }
+    }
+    match exp {
+        Expression::Var(name) => match env.lookup_var_or_func(name) {
+            Some(FuncOrVar::Var((_, var_type))) => Ok(var_type),
+            Some(FuncOrVar::Func(func, _)) => Ok(func_to_type(&func)),
+            None => Err(format!("Identifier '{}' was never declared", name)),
+        },
+        exp => check_expr(exp, env),
+    }
+}
+
+// Checks that a combinator is given a Maybe or a Result and a function taking
+// the value inside it, giving the type that the function returns. An int value
+// given to a function taking a real is converted to one
+fn check_combinator_function(
+    keyword: &str,
+    exp: &mut Expression,
+    exp_type: &Type,
+    func: &mut Expression,
+    env: &Environment<Type>,
+) -> Result<Type, ErrorMessage> {
+    let inner_type = match exp_type {
+        Type::TMaybe(t) | Type::TResult(t, _) => t.as_ref(),
+        _ => {
+            return Err(format!(
+                "[Type Error] '{}' expects a maybe or result type value, but found {}.",
+                keyword, exp_type
+            ));
+        }
+    };
+    // As in calls, an int may be widened to real
+    let accepts = |param_type: &Type| {
+        param_type == inner_type
+            || *inner_type == Type::TAny
+            || (*param_type == Type::TReal && *inner_type == Type::TInteger)
+    };
+    match check_function_argument(func, inner_type, env)? {
+        Type::TFunction(ret_type, param_types)
+            if param_types.len() == 1 && accepts(&param_types[0]) =>
+        {
+            if param_types[0] == Type::TReal && *inner_type == Type::TInteger {
+                convert_to_real(exp);
+            }
+            Ok(*ret_type)
+        }
+        func_type => Err(format!(
+            "[Type Error] '{}' expects a function taking a value of type {}, but found {}.",
+            keyword, inner_type, func_type
+        )),
+    }
+}
+
+fn check_map_type(
+    exp: &mut Expression,
+    func: &mut Expression,
+    env: &Environment<Type>,
+) -> Result<Type, ErrorMessage> {
+    let exp_type = check_expr(exp, env)?;
+    let ret_type = Box::new(check_combinator_function("map", exp, &exp_type, func, env)?);
     match exp_type {
-        Type::TMaybe(t) => Ok(*t),
-        Type::TResult(tl, _) => Ok(*tl),
+        Type::TResult(_, err) => Ok(Type::TResult(ret_type, err)),
+        _ => Ok(Type::TMaybe(ret_type)),
+    }
+}
+
+fn check_and_then_type(
+    exp: &mut Expression,
+    func: &mut Expression,
+    env: &Environment<Type>,
+) -> Result<Type, ErrorMessage> {
+    let exp_type = check_expr(exp, env)?;
+    let ret_type = check_combinator_function("and_then", exp, &exp_type, func, env)?;
+    match (exp_type, ret_type) {
+        (Type::TMaybe(_), ret_type @ Type::TMaybe(_)) => Ok(ret_type),
+        (Type::TResult(_, err), Type::TResult(ok, func_err)) if fits(&err, &func_err) => {
+            Ok(Type::TResult(ok, func_err))
+        }
+        (Type::TResult(_, err), Type::TResult(ok, func_err)) if fits(&func_err, &err) => {
+            Ok(Type::TResult(ok, err))
+        }
+        (Type::TMaybe(_), ret_type) => Err(format!(
+            "[Type Error] the function given to 'and_then' must return a maybe type value, but it returns {}.",
+            ret_type
+        )),
+        (exp_type, ret_type) => Err(format!(
+            "[Type Error] the function given to 'and_then' on {} must return a result with the same type of error, but it returns {}.",
+            exp_type, ret_type
+        )),
+    }
+}
+
+fn check_unwrap_or_type(
+    exp: &mut Expression,
+    default: &mut Expression,
+    env: &Environment<Type>,
+) -> Result<Type, ErrorMessage> {
+    let exp_type = check_expr(exp, env)?;
+    let default_type = check_expr(default, env)?;
+
+    match exp_type {
+        Type::TMaybe(t) | Type::TResult(t, _) if fits(&t, &default_type) => Ok(default_type),
+        Type::TMaybe(t) | Type::TResult(t, _) if fits(&default_type, &t) => Ok(*t),
+        Type::TMaybe(t) | Type::TResult(t, _) => Err(format!(
+            "[Type Error] the default value of 'unwrap_or' must be of type {}, but it is of type {}.",
+            t, default_type
+        )),
         _ => Err(String::from(
             "[Type Error] expecting a maybe or result type value.",
         )),
     }
 }
 
-fn check_maybe_just(exp: Expression, env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+fn check_ok_or_type(
+    exp: &mut Expression,
+    error: &mut Expression,
+    env: &Environment<Type>,
+) -> Result<Type, ErrorMessage> {
+    let exp_type = check_expr(exp, env)?;
+    let error_type = check_expr(error, env)?;
+
+    match exp_type {
+        Type::TMaybe(t) => Ok(Type::TResult(t, Box::new(error_type))),
+        _ => Err(String::from("[Type Error] expecting a maybe type value.")),
+    }
+}
+
+fn check_maybe_just(exp: &mut Expression, env: &Environment<Type>) -> Result<Type, ErrorMessage> {
     let exp_type = check_expr(exp, env)?;
     Ok(Type::TMaybe(Box::new(exp_type)))
 }
 
-fn check_iserror_type(exp: Expression, env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+fn check_iserror_type(exp: &mut Expression, env: &Environment<Type>) -> Result<Type, ErrorMessage> {
     let v = check_expr(exp, env)?;
 
     match v {
@@ -284,7 +643,10 @@ fn check_iserror_type(exp: Expression, env: &Environment<Type>) -> Result<Type,
     }
 }
 
-fn check_isnothing_type(exp: Expression, env: &Environment<Type>) -> Result<Type, ErrorMessage> {
+fn check_isnothing_type(
+    exp: &mut Expression,
+    env: &Environment<Type>,
+) -> Result<Type, ErrorMessage> {
     let exp_type = check_expr(exp, env)?;
 
     match exp_type {
@@ -294,7 +656,7 @@ fn check_isnothing_type(exp: Expression, env: &Environment<Type>) -> Result<Type
 }
 
 fn check_list_value(
-    elements: &[Expression],
+    elements: &mut [Expression],
     env: &Environment<Type>,
 ) -> Result<Type, ErrorMessage> {
     if elements.is_empty() {
@@ -302,11 +664,11 @@ fn check_list_value(
     }
 
     // Check the type of the first element
-    let first_type = check_expr(elements[0].clone(), env)?;
+    let first_type = check_expr(&mut elements[0], env)?;
 
     // Check that all other elements have the same type
-    for element in elements.iter().skip(1) {
-        let element_type = check_expr(element.clone(), env)?;
+    for element in elements.iter_mut().skip(1) {
+        let element_type = check_expr(element, env)?;
         if element_type != first_type {
             return Err(format!(
                 "[Type Error] List elements must have the same type. Expected '{:?}', found '{:?}'.",
@@ -318,9 +680,25 @@ fn check_list_value(
     Ok(Type::TList(Box::new(first_type)))
 }
 
+fn check_range(
+    start: &mut Expression,
+    end: &mut Expression,
+    env: &Environment<Type>,
+) -> Result<Type, ErrorMessage> {
+    let start_type = check_expr(start, env)?;
+    let end_type = check_expr(end, env)?;
+    if start_type != Type::TInteger || end_type != Type::TInteger {
+        return Err(format!(
+            "[Type Error] range bounds must be of type int, but found '{:?}' and '{:?}'.",
+            start_type, end_type
+        ));
+    }
+    Ok(Type::TList(Box::new(Type::TInteger)))
+}
+
 fn check_adt_constructor(
     name: Name,
-    args: Vec<Box<Expression>>,
+    args: &mut [Box<Expression>],
     env: &Environment<Type>,
 ) -> Result<Type, ErrorMessage> {
     // Gather all ADTs from all scopes (stack and globals)
@@ -358,8 +736,8 @@ fn check_adt_constructor(
                 ));
             }
             // Check each argument's type
-            for (arg, expected_type) in args.iter().zip(constructor.types.iter()) {
-                let arg_type = check_expr(*arg.clone(), env)?;
+            for (arg, expected_type) in args.iter_mut().zip(constructor.types.iter()) {
+                let arg_type = check_expr(arg, env)?;
                 if arg_type != *expected_type {
                     return Err(format!(
                         "[Type Error] Argument type mismatch in constructor '{}'. Expected '{:?}', found '{:?}'.",
@@ -1,5 +1,7 @@
 pub mod expression_type_checker;
 pub mod statement_type_checker;
+pub mod static_analysis;
 
-pub use expression_type_checker::{check_expr, check_func_call};
+pub use expression_type_checker::check_func_call;
 pub use statement_type_checker::check_stmt;
+pub use static_analysis::analyze;
@@ -1,9 +1,10 @@
-use crate::environment::environment::Environment;
+use crate::environment::environment::{Environment, run_before_definition};
 use crate::ir::ast::{
     Expression, FormalArgument, FuncSignature, Function, Name, Statement, Type, ValueConstructor,
 };
+use crate::parser::pretty_printer::print_expression;
 use crate::type_checker::check_func_call;
-use crate::type_checker::expression_type_checker::check_expr;
+use crate::type_checker::expression_type_checker::{check_expr, fits};
 use crate::{environment, show, show_counter};
 use std::collections::HashSet;
 use tokio::sync::watch::error;
@@ -11,100 +12,108 @@ use tokio::sync::watch::error;
 type ErrorMessage = String;
 
 pub fn check_stmt(
-    stmt: Statement,
+    stmt: &mut Statement,
     env: &Environment<Type>,
 ) -> Result<Environment<Type>, ErrorMessage> {
-    match stmt.clone() {
+    let logged = stmt.clone();
+    match stmt {
         Statement::VarDeclaration(var, expr) => {
             show_counter_tp_statement();
             show_tp_statement(format!("Check VarDeclaration: "));
-            show_tp_statement(format!("Statement: {:?}", stmt));
+            show_tp_statement(format!("Statement: {:?}", logged));
             show_tp_statement(format!("Env: {:?}", env));
-            check_var_declaration_stmt(var, expr, env)
+            check_var_declaration_stmt(var.clone(), expr, env)
         }
         Statement::ValDeclaration(var, expr) => {
             show_counter_tp_statement();
             show_tp_statement(format!("Check ValDeclaration: "));
-            show_tp_statement(format!("Statement: {:?}", stmt));
+            show_tp_statement(format!("Statement: {:?}", logged));
             show_tp_statement(format!("Env: {:?}", env));
-            check_val_declaration_stmt(var, expr, env)
+            check_val_declaration_stmt(var.clone(), expr, env)
         }
         Statement::Sequence(stmt1, stmt2) => {
             show_counter_tp_statement();
             show_tp_statement(format!("Check Sequence: "));
-            show_tp_statement(format!("Statement: {:?}", stmt));
+            show_tp_statement(format!("Statement: {:?}", logged));
             show_tp_statement(format!("Env: {:?}", env));
             check_squence_stmt(stmt1, stmt2, env)
         }
         Statement::Assignment(name, exp) => {
             show_counter_tp_statement();
             show_tp_statement(format!("Check Assignment: "));
-            show_tp_statement(format!("Statement: {:?}", stmt));
+            show_tp_statement(format!("Statement: {:?}", logged));
             show_tp_statement(format!("Env: {:?}", env));
-            check_assignment_stmt(name, exp, env)
+            check_assignment_stmt(name.clone(), exp, env)
         }
         Statement::IfThenElse(cond, stmt_then, stmt_else_opt) => {
             show_counter_tp_statement();
             show_tp_statement(format!("Check IfThenElse: "));
-            show_tp_statement(format!("Statement: {:?}", stmt));
+            show_tp_statement(format!("Statement: {:?}", logged));
             show_tp_statement(format!("Env: {:?}", env));
             check_if_then_else_stmt(cond, stmt_then, stmt_else_opt, env)
         }
+        Statement::When(exp, cases, default) => {
+            show_counter_tp_statement();
+            show_tp_statement(format!("Check When: "));
+            show_tp_statement(format!("Statement: {:?}", logged));
+            show_tp_statement(format!("Env: {:?}", env));
+            check_when_stmt(exp, cases, default, env)
+        }
         Statement::While(cond, stmt) => {
             show_counter_tp_statement();
             show_tp_statement(format!("Check While: "));
-            show_tp_statement(format!("Statement: {:?}", stmt));
+            show_tp_statement(format!("Statement: {:?}", logged));
             show_tp_statement(format!("Env: {:?}", env));
             check_while_stmt(cond, stmt, env)
         }
         Statement::For(var, expr, stmt) => {
             show_counter_tp_statement();
             show_tp_statement(format!("Check For: "));
-            show_tp_statement(format!("Statement: {:?}", stmt));
+            show_tp_statement(format!("Statement: {:?}", logged));
             show_tp_statement(format!("Env: {:?}", env));
-            check_for_stmt(var, expr, stmt, env)
+            check_for_stmt(var.clone(), expr, stmt, env)
         }
         Statement::FuncDef(function) => {
             show_counter_tp_statement();
             show_tp_statement(format!("Check FuncDef: "));
-            show_tp_statement(format!("Statement: {:?}", stmt));
+            show_tp_statement(format!("Statement: {:?}", logged));
             show_tp_statement(format!("Env: {:?}", env));
             check_func_def_stmt(function, env)
         }
         Statement::TypeDeclaration(name, cons) => {
             show_counter_tp_statement();
             show_tp_statement(format!("Check TypeDeclaration: "));
-            show_tp_statement(format!("Statement: {:?}", stmt));
+            show_tp_statement(format!("Statement: {:?}", logged));
             show_tp_statement(format!("Env: {:?}", env));
-            check_adt_declarations_stmt(name, cons, env)
+            check_adt_declarations_stmt(name.clone(), cons.clone(), env)
         }
         Statement::Return(exp) => {
             show_counter_tp_statement();
             show_tp_statement(format!("Check Return: "));
-            show_tp_statement(format!("Statement: {:?}", stmt));
+            show_tp_statement(format!("Statement: {:?}", logged));
             show_tp_statement(format!("Env: {:?}", env));
             check_return_stmt(exp, env)
         }
         Statement::Block(statements_vector) => {
             show_counter_tp_statement();
             show_tp_statement(format!("Check Block: "));
-            show_tp_statement(format!("Statement: {:?}", stmt));
+            show_tp_statement(format!("Statement: {:?}", logged));
             show_tp_statement(format!("Env: {:?}", env));
             check_block_statement(statements_vector, env)
         }
         Statement::Print(exp) => {
             show_counter_tp_statement();
             show_tp_statement(format!("Check Print: "));
-            show_tp_statement(format!("Statement: {:?}", stmt));
+            show_tp_statement(format!("Statement: {:?}", logged));
             show_tp_statement(format!("Env: {:?}", env));
             check_print_statement(exp, env)
         }
-        Statement::SingleFuncCall(name, args) => {
+        Statement::SingleFuncCall(name, args, resolution) => {
             show_counter_tp_statement();
             show_tp_statement(format!("Check SingleFuncCall: "));
-            show_tp_statement(format!("Statement: {:?}", stmt));
+            show_tp_statement(format!("Statement: {:?}", logged));
             show_tp_statement(format!("Env: {:?}", env));
-            match check_func_call(name, args, env) {
+            match check_func_call(name.clone(), args, resolution, env) {
                 Ok(_) => {
                     return Ok(env.clone());
                 }
@@ -113,38 +122,103 @@ pub fn check_stmt(
                 }
             }
         }
+        Statement::Break | Statement::Continue => {
+            show_counter_tp_statement();
+            show_tp_statement(format!("Check Break/Continue: "));
+            show_tp_statement(format!("Statement: {:?}", logged));
+            show_tp_statement(format!("Env: {:?}", env));
+            check_loop_jump_stmt(stmt, env)
+        }
+        Statement::Global(name) => {
+            show_counter_tp_statement();
+            show_tp_statement(format!("Check Global: {}", name));
+            show_tp_statement(format!("Statement: {:?}", logged));
+            show_tp_statement(format!("Env: {:?}", env));
+            check_global_stmt(name.clone(), env)
+        }
+        Statement::TryCatch(body, name, handler) => {
+            show_counter_tp_statement();
+            show_tp_statement(format!("Check TryCatch: {}", name));
+            show_tp_statement(format!("Statement: {:?}", logged));
+            show_tp_statement(format!("Env: {:?}", env));
+            check_try_catch_stmt(body, name.clone(), handler, env)
+        }
+        Statement::Comment(_) => Ok(env.clone()),
         _ => Err("Not implemented yet".to_string()),
     }
 }
 
 fn check_squence_stmt(
-    stmt1: Box<Statement>,
-    stmt2: Box<Statement>,
+    stmt1: &mut Statement,
+    stmt2: &mut Statement,
     env: &Environment<Type>,
 ) -> Result<Environment<Type>, ErrorMessage> {
-    let new_env = check_stmt(*stmt1, &env)?;
-    check_stmt(*stmt2, &new_env)
+    let new_env = check_stmt(stmt1, env)?;
+    check_stmt(stmt2, &new_env)
 }
 
 fn check_block_statement(
-    statements_vector: Vec<Statement>,
+    statements_vector: &mut [Statement],
     env: &Environment<Type>,
 ) -> Result<Environment<Type>, ErrorMessage> {
     let mut new_env = env.clone();
     new_env.push();
-    for statement in &statements_vector {
-        new_env = check_stmt(statement.clone(), &new_env)?;
+    declare_functions(statements_vector, &mut new_env)?;
+    for statement in statements_vector.iter_mut() {
+        new_env = check_stmt(statement, &new_env)?;
     }
+    let declared: Vec<Name> = new_env
+        .get_current_scope()
+        .variables
+        .keys()
+        .cloned()
+        .collect();
     new_env.pop();
+    new_env.ended_variables.extend(declared);
     return Ok(new_env);
 }
 
+// The body of an `if`, `when`, `while` or `for` is a block of its own, even if
+// it is not a Statement::Block, so its variables are local to it
+fn check_body(
+    stmt: &mut Statement,
+    env: &Environment<Type>,
+) -> Result<Environment<Type>, ErrorMessage> {
+    match stmt {
+        Statement::Block(statements_vector) => check_block_statement(statements_vector, env),
+        stmt => check_block_statement(std::slice::from_mut(stmt), env),
+    }
+}
+
+// Maps the functions a block defines before checking any of its statements, so
+// that they can be called before their definition and call each other
+fn declare_functions(
+    statements: &[Statement],
+    env: &mut Environment<Type>,
+) -> Result<(), ErrorMessage> {
+    let mut declared = HashSet::new();
+    let early = run_before_definition(statements);
+    for statement in statements {
+        if let Statement::FuncDef(function) = statement {
+            let func_signature = FuncSignature::from_func(function);
+            if !declared.insert(func_signature.clone()) {
+                return Err(format!(
+                    "Function {} is defined multiple times",
+                    func_signature
+                ));
+            }
+            env.hoist_function(function.clone(), early.contains(&function.name));
+        }
+    }
+    Ok(())
+}
+
 fn check_print_statement(
-    exp: Box<Expression>,
+    exp: &mut Expression,
     env: &Environment<Type>,
 ) -> Result<Environment<Type>, ErrorMessage> {
     let new_env = env.clone();
-    let exp_type = check_expr(*exp, &new_env)?;
+    let exp_type = check_expr(exp, &new_env)?;
     match exp_type {
         Type::TString | Type::TInteger | Type::TReal | Type::TBool => Ok(new_env),
         _ => Err("Print statement does not support values of this type".into()),
@@ -153,21 +227,32 @@ fn check_print_statement(
 
 fn check_assignment_stmt(
     name: Name,
-    exp: Box<Expression>,
+    exp: &mut Expression,
     env: &Environment<Type>,
 ) -> Result<Environment<Type>, ErrorMessage> {
     let mut new_env = env.clone();
-    let exp_type = check_expr(*exp.clone(), &new_env)?;
-
-    match *exp {
-        Expression::Lambda(mut func) => {
-            func.name = name;
-            new_env = check_func_def_stmt(func, env)?;
+    let exp_type = check_expr(exp, &new_env)?;
+
+    match exp {
+        Expression::Lambda(func) => {
+            // The function is defined under the name of the variable
+            let mut named = func.clone();
+            named.name = name;
+            new_env = check_func_def_stmt(&mut named, env)?;
+            func.body = named.body;
             Ok(new_env)
         }
         _ => match new_env.lookup(&name) {
             Some((mutable, var_type)) => {
-                if !mutable {
+                if !mutable && env.in_function() && env.lookup_global(&name).is_some_and(|(m, _)| m)
+                {
+                    Err(format!(
+                        "[Type Error] cannot assign global variable '{}' in function '{}' without declaring it with 'global {}'.",
+                        name, env.current_func, name
+                    ))
+                } else if let Some(site) = new_env.declaration_site(&name).filter(|_| !mutable) {
+                    Err(format!("[Type Error] cannot assign to {}.", site))
+                } else if !mutable {
                     Err(format!(
                         "[Type Error] cannot reassign '{:?}' variable, since it was declared as a constant value.",
                         name
@@ -184,6 +269,10 @@ fn check_assignment_stmt(
                     ))
                 }
             }
+            None if env.ended_variables.contains(&name) => Err(format!(
+                "[Type Error] variable '{}' cannot be assigned here, since it was declared in a block that has ended.",
+                name
+            )),
             None => Err(format!("[Type Error] variable '{:?}' not declared.", name)),
         },
     }
@@ -191,60 +280,62 @@ fn check_assignment_stmt(
 
 fn check_var_declaration_stmt(
     name: Name,
-    exp: Box<Expression>,
+    exp: &mut Expression,
     env: &Environment<Type>,
 ) -> Result<Environment<Type>, ErrorMessage> {
     let mut new_env = env.clone();
-    let var_type = new_env.lookup(&name);
-    let exp_type = check_expr(*exp, &new_env)?;
+    let var_type = new_env.lookup_local(&name);
+    let exp_type = check_expr(exp, &new_env)?;
 
     if var_type.is_none() {
         new_env.create_variable(name.clone(), true, exp_type)?;
         Ok(new_env)
     } else {
-        Err(format!(
-            "[Type Error] variable '{:?}' already declared",
-            name
-        ))
+        Err(format!("[Type Error] variable '{}' already declared", name))
     }
 }
 
 fn check_val_declaration_stmt(
     name: Name,
-    exp: Box<Expression>,
+    exp: &mut Expression,
     env: &Environment<Type>,
 ) -> Result<Environment<Type>, ErrorMessage> {
     let mut new_env = env.clone();
-    let var_type = new_env.lookup(&name);
-    let exp_type = check_expr(*exp, &new_env)?;
+    let var_type = new_env.lookup_local(&name);
+    let site = format!(
+        "val '{}', declared as 'val {} = {}' in {}",
+        name,
+        name,
+        print_expression(exp),
+        place(env)
+    );
+    let exp_type = check_expr(exp, &new_env)?;
 
     if var_type.is_none() {
         new_env.create_variable(name.clone(), false, exp_type)?;
+        new_env.set_declaration_site(name, site);
         Ok(new_env)
     } else {
-        Err(format!(
-            "[Type Error] variable '{:?}' already declared",
-            name
-        ))
+        Err(format!("[Type Error] variable '{}' already declared", name))
     }
 }
 
 fn check_if_then_else_stmt(
-    cond: Box<Expression>,
-    stmt_then: Box<Statement>,
-    stmt_else_opt: Option<Box<Statement>>,
+    cond: &mut Expression,
+    stmt_then: &mut Statement,
+    stmt_else_opt: &mut Option<Box<Statement>>,
     env: &Environment<Type>,
 ) -> Result<Environment<Type>, ErrorMessage> {
     let mut new_env = env.clone();
-    let cond_type = check_expr(*cond, &new_env)?;
+    let cond_type = check_expr(cond, &new_env)?;
     if cond_type != Type::TBool {
         return Err(
             "[Type Error] a condition in a 'if' statement must be of type boolean.".to_string(),
         );
     }
-    let then_env = check_stmt(*stmt_then, &new_env)?;
+    let then_env = check_body(stmt_then, &new_env)?;
     if let Some(stmt_else) = stmt_else_opt {
-        let else_env = check_stmt(*stmt_else, &new_env)?;
+        let else_env = check_body(stmt_else, &new_env)?;
         new_env = merge_environments(&then_env, &else_env)?;
     } else {
         new_env = merge_environments(&new_env, &then_env)?;
@@ -252,74 +343,172 @@ fn check_if_then_else_stmt(
     Ok(new_env)
 }
 
+fn check_when_stmt(
+    exp: &mut Expression,
+    cases: &mut [(Expression, Statement)],
+    default: &mut Option<Box<Statement>>,
+    env: &Environment<Type>,
+) -> Result<Environment<Type>, ErrorMessage> {
+    let exp_type = check_expr(exp, env)?;
+    if exp_type != Type::TInteger && exp_type != Type::TString {
+        return Err(format!(
+            "[Type Error] the value of a 'when' statement must be of type int or string, but found a {:?}.",
+            exp_type
+        ));
+    }
+
+    let mut seen_values: Vec<Expression> = Vec::new();
+    let mut case_envs = Vec::new();
+    for (value, body) in cases {
+        let value_type = check_expr(value, env)?;
+        if value_type != exp_type {
+            return Err(format!(
+                "[Type Error] a 'when' case of type {:?} can not match a value of type {:?}.",
+                value_type, exp_type
+            ));
+        }
+        if seen_values.contains(value) {
+            return Err(format!("[Type Error] duplicate 'when' case {:?}.", value));
+        }
+        seen_values.push(value.clone());
+        case_envs.push(check_body(body, env)?);
+    }
+
+    // Without a default body, it is possible that no branch runs at all
+    let mut new_env = match default {
+        Some(default) => check_body(default, env)?,
+        None => env.clone(),
+    };
+    for case_env in case_envs {
+        new_env = merge_environments(&new_env, &case_env)?;
+    }
+    Ok(new_env)
+}
+
 fn check_while_stmt(
-    cond: Box<Expression>,
-    stmt: Box<Statement>,
+    cond: &mut Expression,
+    stmt: &mut Statement,
     env: &Environment<Type>,
 ) -> Result<Environment<Type>, ErrorMessage> {
     let mut new_env = env.clone();
-    let cond_type = check_expr(*cond, &new_env)?;
+    let cond_type = check_expr(cond, &new_env)?;
     if cond_type != Type::TBool {
         return Err(
             "[Type Error] a condition in a 'while' statement must be of type boolean.".to_string(),
         );
     }
-    new_env = check_stmt(*stmt, &new_env)?;
+    new_env.enter_loop();
+    new_env = check_body(stmt, &new_env)?;
+    new_env.exit_loop();
     Ok(new_env)
 }
 
 fn check_for_stmt(
     var: Name,
-    expr: Box<Expression>,
-    stmt: Box<Statement>,
+    expr: &mut Expression,
+    stmt: &mut Statement,
     env: &Environment<Type>,
 ) -> Result<Environment<Type>, ErrorMessage> {
     let mut new_env = env.clone();
-    let _var_type = env.lookup(&var);
-    let expr_type = check_expr(*expr, &new_env)?;
-    match expr_type {
-        Type::TList(base_type) => {
-            if let Some((_, t)) = env.lookup(&var) {
-                if t == *base_type || *base_type == Type::TAny {
-                    new_env = check_stmt(*stmt, &new_env)?;
-                    return Ok(new_env);
-                } else {
-                    return Err(format!(
-                        "[TypeError] Type mismatch between {:?} and {:?}",
-                        t, base_type
-                    ));
-                }
-            } else {
-                new_env.create_variable(var.clone(), false, *base_type)?;
-                new_env = check_stmt(*stmt, &new_env)?;
-                return Ok(new_env);
-            }
+    let site = format!(
+        "loop variable '{}' of 'for {} in {}' in {}",
+        var,
+        var,
+        print_expression(expr),
+        place(env)
+    );
+    let expr_type = check_expr(expr, &new_env)?;
+    // The type of the values the loop variable is bound to
+    let element_type = match expr_type.clone() {
+        Type::TList(base_type) => Some(base_type),
+        Type::TString => Some(Box::new(Type::TString)),
+        Type::TMaybe(base_type) => Some(base_type),
+        _ => None,
+    };
+    match element_type {
+        Some(base_type) => {
+            // Like the interpreter, the loop binds its variable as a constant of a
+            // scope of its own, which ends with the loop
+            new_env.push();
+            new_env.map_variable(var.clone(), false, *base_type);
+            new_env.set_declaration_site(var.clone(), site);
+            new_env.enter_loop();
+            new_env = check_body(stmt, &new_env)?;
+            new_env.exit_loop();
+            new_env.pop();
+            new_env.ended_variables.insert(var);
+            return Ok(new_env);
         }
-        _ => {
+        None => {
             return Err(format!(
-                "[TypeError] Expecting a List type, but found a {:?}",
+                "[TypeError] Expecting a List, string or Maybe type, but found a {:?}",
                 expr_type
             ));
         }
     }
 }
 
+// A function body starts with a fresh environment, so a loop around the
+// definition does not allow `break` or `continue` inside the function
+fn check_loop_jump_stmt(
+    stmt: &Statement,
+    env: &Environment<Type>,
+) -> Result<Environment<Type>, ErrorMessage> {
+    if !env.in_loop() {
+        let name = match stmt {
+            Statement::Break => "break",
+            _ => "continue",
+        };
+        return Err(format!(
+            "[Type Error] '{}' can only be used inside a 'while' or 'for' loop.",
+            name
+        ));
+    }
+    Ok(env.clone())
+}
+
+// Only a top-level `var` can be declared global, inside a function that has no
+// local variable with its name
+fn check_global_stmt(
+    name: Name,
+    env: &Environment<Type>,
+) -> Result<Environment<Type>, ErrorMessage> {
+    if !env.in_function() {
+        return Err(format!(
+            "[Type Error] 'global {}' can only be used inside a function.",
+            name
+        ));
+    }
+    match env.lookup_global(&name) {
+        Some((true, _)) => {
+            let mut new_env = env.clone();
+            new_env.declare_global(name);
+            Ok(new_env)
+        }
+        Some((false, _)) => Err(format!(
+            "[Type Error] cannot declare '{}' global, since it was declared as a constant value.",
+            name
+        )),
+        None => Err(format!(
+            "[Name Error] '{}' is not a top-level variable of the program.",
+            name
+        )),
+    }
+}
+
 fn check_func_def_stmt(
-    function: Function,
+    function: &mut Function,
     env: &Environment<Type>,
 ) -> Result<Environment<Type>, ErrorMessage> {
-    let mut new_env = Environment::new();
-    let func_signature = FuncSignature::from_func(&function);
-    //new_env.push(); -> Push and pop will happen in check_block_statement
-    new_env.set_current_func(&func_signature);
-    // Previous environment functions and the formal parameters are regarded as global
-    new_env.set_global_functions(env.get_all_functions());
+    let func_signature = FuncSignature::from_func(function);
 
-    // Ensure that each function is defined only once in current scope
+    // Ensure that each function is defined only once in current scope. Its block
+    // already declared it, so only a different function counts as a redefinition
     let current_scope = env.get_current_scope();
     if current_scope
         .functions
-        .contains_key(&func_signature)
+        .get(&func_signature)
+        .is_some_and(|declared| **declared != *function)
     {
         return Err(format!(
             "Function {} is defined multiple times",
@@ -338,6 +527,16 @@ fn check_func_def_stmt(
         }
     }
 
+    let mut final_env = env.clone();
+    final_env.define_function(function.clone());
+
+    // As in the interpreter, the body sees the functions of the scope that
+    // defines it, including itself, and the variables of the enclosing scopes.
+    // Its formal parameters are regarded as global
+    let mut new_env = final_env.clone();
+    new_env.enter_function(&func_signature, final_env.definition_link(&func_signature));
+    //new_env.push(); -> Push and pop will happen in check_block_statement
+
     for formal_arg in function.params.iter() {
         match formal_arg.argument_type.clone() {
             Type::TFunction(arg_func_ret_type, arg_func_params_type) => {
@@ -363,17 +562,27 @@ fn check_func_def_stmt(
                     false,
                     formal_arg.argument_type.clone(),
                 )?;
+                new_env.set_declaration_site(
+                    formal_arg.argument_name.clone(),
+                    format!(
+                        "parameter '{}' of function '{}'",
+                        formal_arg.argument_name, func_signature
+                    ),
+                );
             }
         }
     }
 
-    new_env.map_function(function.clone());
-    if let Some(body) = function.body.clone() {
-        check_stmt(*body, &new_env)?; //new_env is only used to check function body 
+    if let Some(body) = &mut function.body {
+        check_stmt(body, &new_env)?; //new_env is only used to check function body 
+        if function.kind != Type::TVoid && !always_returns(body) {
+            return Err(format!(
+                "[Type Error] function '{}' may end without returning a value of type {}.",
+                func_signature, function.kind
+            ));
+        }
     }
 
-    let mut final_env = env.clone();
-    final_env.map_function(function.clone());
     Ok(final_env) // if function body is ok, return original env with new function
 }
 
@@ -388,24 +597,28 @@ fn check_adt_declarations_stmt(
 }
 
 fn check_return_stmt(
-    exp: Box<Expression>,
+    exp: &mut Expression,
     env: &Environment<Type>,
 ) -> Result<Environment<Type>, ErrorMessage> {
     let new_env = env.clone();
 
-    assert!(new_env.scoped_function());
-
-    let ret_type = check_expr(*exp, &new_env)?;
+    if !new_env.scoped_function() {
+        return Err("[Type Error] 'ret' can only be used inside a function".to_string());
+    }
 
-    let current_func = env.lookup_function(&env.current_func);
+    let ret_type = check_expr(exp, &new_env)?;
 
-    if current_func.is_none() {
+    let Some(current_func) = env.lookup_function(&env.current_func) else {
         return Err(format!("Type checker: No function to return from"));
-    }
-
-    let current_func = current_func.unwrap();
+    };
 
-    if ret_type != current_func.kind {
+    if current_func.kind == Type::TVoid && ret_type != Type::TVoid {
+        return Err(format!(
+            "[Type Error] function '{}' is void, so it cannot return a value of type {}.",
+            env.current_func, ret_type
+        ));
+    }
+    if !fits(&ret_type, &current_func.kind) {
         return Err(format!(
             "Error in function {}:
         Actual return type cannot be different from formal return type \n 
@@ -426,6 +639,57 @@ fn check_return_stmt(
     */
 }
 
+// Whether every path through the statement ends with `ret`. Loops may not run
+// their body, and a `when` without a default may match no case
+pub fn always_returns(stmt: &Statement) -> bool {
+    match stmt {
+        Statement::Return(_) => true,
+        Statement::Block(stmts) => stmts.iter().any(always_returns),
+        Statement::Sequence(stmt1, stmt2) => always_returns(stmt1) || always_returns(stmt2),
+        Statement::IfThenElse(_, stmt_then, Some(stmt_else)) => {
+            always_returns(stmt_then) && always_returns(stmt_else)
+        }
+        Statement::When(_, cases, Some(default)) => {
+            cases.iter().all(|(_, body)| always_returns(body)) && always_returns(default)
+        }
+        Statement::TryCatch(body, _, handler) => always_returns(body) && always_returns(handler),
+        _ => false,
+    }
+}
+
+// The handler sees the message of the failure as a string constant of its own
+// scope. As with if/else, the variables of either body are local to it
+fn check_try_catch_stmt(
+    body: &mut Statement,
+    name: Name,
+    handler: &mut Statement,
+    env: &Environment<Type>,
+) -> Result<Environment<Type>, ErrorMessage> {
+    let body_env = check_body(body, env)?;
+
+    let mut handler_env = env.clone();
+    handler_env.push();
+    handler_env.map_variable(name.clone(), false, Type::TString);
+    handler_env.set_declaration_site(
+        name.clone(),
+        format!("error '{}' of 'catch {}' in {}", name, name, place(env)),
+    );
+    handler_env = check_body(handler, &handler_env)?;
+    handler_env.pop();
+    handler_env.ended_variables.insert(name);
+
+    merge_environments(&body_env, &handler_env)
+}
+
+// Where a statement being checked is, for the messages about its declarations
+fn place(env: &Environment<Type>) -> String {
+    if env.in_function() {
+        format!("function '{}'", env.current_func)
+    } else {
+        "the program".to_string()
+    }
+}
+
 fn merge_environments(
     env1: &Environment<Type>,
     env2: &Environment<Type>,
@@ -434,35 +698,40 @@ fn merge_environments(
 
     // Get all variables defined in either environment
     for (name, (mutable2, type2)) in env2.get_all_variables() {
-        match env1.lookup(&name) {
-            Some((mutable1, type1)) => {
-                // Variable exists in both branches
-                // Check mutability first - if either is constant, result must be constant
-                let final_mutable = mutable1 && mutable2;
-
-                // Then check types
-                if type1 == Type::TAny {
-                    // If type1 is TAny, use type2
-                    merged.map_variable(name.clone(), final_mutable, type2.clone());
-                } else if type2 == Type::TAny {
-                    // If type2 is TAny, keep type1
-                    merged.map_variable(name.clone(), final_mutable, type1.clone());
-                } else if type1 != type2 {
-                    return Err(format!(
-                        "[Type Error] Variable '{}' has inconsistent types in different branches: '{:?}' and '{:?}'",
-                        name, type1, type2
-                    ));
-                } else {
-                    // Types match, update with combined mutability
-                    merged.map_variable(name.clone(), final_mutable, type1.clone());
-                }
-            }
-            None => {
-                // Variable only exists in else branch - it's conditionally defined
-                merged.map_variable(name.clone(), mutable2, type2.clone());
-            }
+        // Variables declared in a single branch are local to its block
+        let Some((mutable1, type1)) = env1.lookup(&name) else {
+            continue;
+        };
+        // Variable exists in both branches
+        // Check mutability first - if either is constant, result must be constant
+        let final_mutable = mutable1 && mutable2;
+        if mutable1
+            && !mutable2
+            && let Some(site) = env2.declaration_site(&name)
+        {
+            merged.set_declaration_site(name.clone(), site);
+        }
+
+        // Then check types
+        if type1 == Type::TAny {
+            // If type1 is TAny, use type2
+            merged.map_variable(name.clone(), final_mutable, type2.clone());
+        } else if type2 == Type::TAny {
+            // If type2 is TAny, keep type1
+            merged.map_variable(name.clone(), final_mutable, type1.clone());
+        } else if type1 != type2 {
+            return Err(format!(
+                "[Type Error] Variable '{}' has inconsistent types in different branches: '{:?}' and '{:?}'",
+                name, type1, type2
+            ));
+        } else {
+            // Types match, update with combined mutability
+            merged.map_variable(name.clone(), final_mutable, type1.clone());
         }
     }
+    merged
+        .ended_variables
+        .extend(env2.ended_variables.iter().cloned());
 
     //TODO: should we merge ADTs and functions?
 
@@ -477,6 +746,107 @@ fn show_counter_tp_statement() {
     show_counter("tp_statement.txt");
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ir::ast::Resolution;
+    use crate::parser::parse;
+
+    // Parses and type checks the source
+    fn check(source: &str) -> Result<(), ErrorMessage> {
+        let (_, stmts) = parse(source).expect("program should parse");
+        check_stmt(&mut Statement::Block(stmts), &Environment::new()).map(|_| ())
+    }
+
+    #[test]
+    fn test_functions_defined_twice_fail_to_type_check() {
+        assert_eq!(
+            check(
+                "def f(a: int) -> int: ret 1; end; var x = f(1); def f(b: int) -> int: ret 2; end;"
+            ),
+            Err("Function f(int) is defined multiple times".to_string())
+        );
+    }
+
+    #[test]
+    fn test_every_path_of_a_function_returns_a_value_of_its_type() {
+        assert_eq!(
+            check(
+                "def f(n: int) -> int: if n > 0: ret 1; end elif n < 0: ret -1; end else: ret 0; end; end;"
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            check("def f(n: int) -> int: if n > 0: ret 1; end; while n < 0: ret -1; end; end;"),
+            Err(
+                "[Type Error] function 'f(int)' may end without returning a value of type int."
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            check("def f(n: int) -> Unit: ret n; end;"),
+            Err(
+                "[Type Error] function 'f(int)' is void, so it cannot return a value of type int."
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_calls_record_what_they_resolve_to() {
+        let (_, stmts) =
+            parse("def f(a: int) -> int: ret a; end; val x = f(1); val y = range(0, x);")
+                .expect("program should parse");
+        let mut program = Statement::Block(stmts);
+        check_stmt(&mut program, &Environment::new()).expect("program should type check");
+        let resolutions: Vec<_> = match program {
+            Statement::Block(stmts) => stmts
+                .into_iter()
+                .filter_map(|stmt| match stmt {
+                    Statement::ValDeclaration(_, exp) => match *exp {
+                        Expression::FuncCall(_, _, resolution) => resolution,
+                        _ => None,
+                    },
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        assert_eq!(
+            resolutions,
+            vec![
+                Resolution::Overload(FuncSignature {
+                    name: "f".to_string(),
+                    argument_types: vec![Type::TInteger],
+                }),
+                Resolution::Builtin,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_calls_without_a_single_best_overload_fail_to_type_check() {
+        let ambiguous = check(
+            "def f(a: int, b: float) -> int: ret 1; end; \
+             def f(a: float, b: int) -> int: ret 2; end; var x = f(1, 1);",
+        );
+        assert_eq!(
+            ambiguous,
+            Err("Call f(int, int) is ambiguous between the overloads: \
+                 f(int, real), f(real, int)"
+                .to_string())
+        );
+        let unmatched = check("def f(a: string) -> int: ret 1; end; var x = f(1.5);");
+        assert_eq!(
+            unmatched,
+            Err(
+                "No overload of function f accepts the call f(real). Candidates: f(string)"
+                    .to_string()
+            )
+        );
+    }
+}
+
 /*
 #[cfg(test)]
 mod tests {
@@ -0,0 +1,414 @@
+use std::collections::HashSet;
+
+use crate::ir::ast::{Expression, Function, Name, Statement};
+use crate::parser::pretty_printer::print_statement;
+
+type Warning = String;
+
+// Finds mistakes in a program that type checks, which do not keep it from
+// running: variables read before a declaration block assigns them, variables
+// and parameters never used and unreachable statements
+pub fn analyze(program: &Statement) -> Vec<Warning> {
+    let mut analyzer = Analyzer {
+        warnings: Vec::new(),
+        scopes: Vec::new(),
+        unassigned: HashSet::new(),
+        current_func: None,
+    };
+    analyzer.statement(program);
+    analyzer.warnings
+}
+
+struct Declaration {
+    name: Name,
+    parameter: bool,
+    used: bool,
+}
+
+struct Analyzer {
+    warnings: Vec<Warning>,
+    // The declarations of the blocks enclosing the statement being analyzed
+    scopes: Vec<Vec<Declaration>>,
+    // Variables declared without a value that may not have been assigned yet
+    unassigned: HashSet<Name>,
+    current_func: Option<Name>,
+}
+
+impl Analyzer {
+    fn declare(&mut self, name: &Name, parameter: bool) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.push(Declaration {
+                name: name.clone(),
+                parameter,
+                used: false,
+            });
+        }
+    }
+
+    fn use_name(&mut self, name: &Name) {
+        if self.unassigned.remove(name) {
+            self.warnings.push(format!(
+                "[Warning] variable '{}' may be read before it is assigned a value.",
+                name
+            ));
+        }
+        let declaration = self
+            .scopes
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.iter_mut().rev().find(|d| &d.name == name));
+        if let Some(declaration) = declaration {
+            declaration.used = true;
+        }
+    }
+
+    fn report_unused(&mut self, scope: Vec<Declaration>) {
+        for declaration in scope {
+            if declaration.used || declaration.name.starts_with('_') {
+                continue;
+            }
+            let warning = match (&self.current_func, declaration.parameter) {
+                (Some(func_name), true) => format!(
+                    "[Warning] parameter '{}' of function '{}' is never used.",
+                    declaration.name, func_name
+                ),
+                _ => format!(
+                    "[Warning] variable '{}' is declared but never used.",
+                    declaration.name
+                ),
+            };
+            self.warnings.push(warning);
+        }
+    }
+
+    fn statement(&mut self, stmt: &Statement) {
+        match stmt {
+            Statement::VarDeclaration(name, exp) | Statement::ValDeclaration(name, exp) => {
+                self.expression(exp);
+                match exp.as_ref() {
+                    Expression::Default(_) => self.unassigned.insert(name.clone()),
+                    _ => self.unassigned.remove(name),
+                };
+                self.declare(name, false);
+            }
+            Statement::Assignment(name, exp) => {
+                self.expression(exp);
+                self.unassigned.remove(name);
+            }
+            Statement::IfThenElse(cond, stmt_then, stmt_else) => {
+                self.expression(cond);
+                let before = self.unassigned.clone();
+                self.statement(stmt_then);
+                let after_then = std::mem::replace(&mut self.unassigned, before);
+                if let Some(stmt_else) = stmt_else {
+                    self.statement(stmt_else);
+                }
+                self.unassigned.extend(after_then);
+            }
+            Statement::When(exp, cases, default) => {
+                self.expression(exp);
+                let before = self.unassigned.clone();
+                let mut after = HashSet::new();
+                for (value, body) in cases {
+                    self.expression(value);
+                    self.statement(body);
+                    after.extend(std::mem::replace(&mut self.unassigned, before.clone()));
+                }
+                if let Some(default) = default {
+                    self.statement(default);
+                }
+                self.unassigned.extend(after);
+            }
+            // The body of a loop may not run at all
+            Statement::While(cond, body) => {
+                self.expression(cond);
+                let before = self.unassigned.clone();
+                self.statement(body);
+                self.unassigned.extend(before);
+            }
+            Statement::For(var, iterable, body) => {
+                self.expression(iterable);
+                // The loop variable shadows an outer variable until the loop ends
+                let shadowed = self.unassigned.remove(var);
+                let before = self.unassigned.clone();
+                self.statement(body);
+                self.unassigned.extend(before);
+                if shadowed {
+                    self.unassigned.insert(var.clone());
+                }
+            }
+            // The body may fail before any of its assignments, running the handler
+            Statement::TryCatch(body, name, handler) => {
+                let before = self.unassigned.clone();
+                self.statement(body);
+                let after_body = std::mem::replace(&mut self.unassigned, before);
+                self.scopes.push(Vec::new());
+                self.declare(name, false);
+                self.statement(handler);
+                let scope = self.scopes.pop().unwrap_or_default();
+                self.report_unused(scope);
+                self.unassigned.extend(after_body);
+            }
+            Statement::Block(stmts) => self.block(stmts),
+            Statement::Sequence(stmt1, stmt2) => {
+                self.statement(stmt1);
+                self.statement(stmt2);
+            }
+            Statement::Assert(exp1, exp2)
+            | Statement::AssertEQ(exp1, exp2, _)
+            | Statement::AssertNEQ(exp1, exp2, _) => {
+                self.expression(exp1);
+                self.expression(exp2);
+            }
+            Statement::AssertTrue(exp, _)
+            | Statement::AssertFalse(exp, _)
+            | Statement::Return(exp)
+            | Statement::Print(exp) => self.expression(exp),
+            Statement::FuncDef(func) | Statement::TestDef(func) => self.function(func),
+            Statement::ModTestDef(_, body) => self.statement(body),
+            Statement::SingleFuncCall(name, args, _) => {
+                self.use_name(name);
+                args.iter().for_each(|arg| self.expression(arg));
+            }
+            Statement::AssertFails(_)
+            | Statement::Break
+            | Statement::Continue
+            | Statement::Global(_)
+            | Statement::TypeDeclaration(_, _)
+            | Statement::Comment(_) => {}
+        }
+    }
+
+    // The functions of a block are analyzed after its other statements, since
+    // they are declared before the block runs and may read its variables
+    fn block(&mut self, stmts: &[Statement]) {
+        self.scopes.push(Vec::new());
+        let mut functions = Vec::new();
+        let mut left = false;
+        let mut reported = false;
+        for stmt in stmts {
+            if left && !reported && !matches!(stmt, Statement::Comment(_)) {
+                let place = match &self.current_func {
+                    Some(func_name) => format!("function '{}'", func_name),
+                    None => "the program".to_string(),
+                };
+                let first_line = print_statement(stmt);
+                let first_line = first_line.lines().next().unwrap_or_default();
+                self.warnings.push(format!(
+                    "[Warning] unreachable code in {}: '{}' comes after a statement that always leaves the block.",
+                    place, first_line
+                ));
+                reported = true;
+            }
+            match stmt {
+                Statement::FuncDef(func) => functions.push(func),
+                _ => self.statement(stmt),
+            }
+            left |= always_leaves(stmt);
+        }
+        for func in functions {
+            self.function(func);
+        }
+        let scope = self.scopes.pop().unwrap_or_default();
+        self.report_unused(scope);
+    }
+
+    fn function(&mut self, func: &Function) {
+        let enclosing_func = self.current_func.replace(func.name.clone());
+        let enclosing_unassigned = std::mem::take(&mut self.unassigned);
+        self.scopes.push(Vec::new());
+        for param in &func.params {
+            self.declare(&param.argument_name, true);
+        }
+        if let Some(body) = &func.body {
+            self.statement(body);
+        }
+        let params = self.scopes.pop().unwrap_or_default();
+        self.report_unused(params);
+        self.unassigned = enclosing_unassigned;
+        self.current_func = enclosing_func;
+    }
+
+    fn expression(&mut self, exp: &Expression) {
+        match exp {
+            Expression::CTrue
+            | Expression::CFalse
+            | Expression::CInt(_)
+            | Expression::CReal(_)
+            | Expression::CString(_)
+            | Expression::CVoid
+            | Expression::CNothing => {}
+            Expression::Var(name) => self.use_name(name),
+            Expression::FuncRef(func_signature) => self.use_name(&func_signature.name),
+            Expression::FuncCall(name, args, _) => {
+                self.use_name(name);
+                args.iter().for_each(|arg| self.expression(arg));
+            }
+            Expression::Add(l, r)
+            | Expression::Sub(l, r)
+            | Expression::Mul(l, r)
+            | Expression::Div(l, r)
+            | Expression::Mod(l, r)
+            | Expression::Pow(l, r)
+            | Expression::And(l, r)
+            | Expression::Or(l, r)
+            | Expression::EQ(l, r)
+            | Expression::NEQ(l, r)
+            | Expression::GT(l, r)
+            | Expression::LT(l, r)
+            | Expression::GTE(l, r)
+            | Expression::LTE(l, r)
+            | Expression::Range(l, r)
+            | Expression::Map(l, r)
+            | Expression::AndThen(l, r)
+            | Expression::UnwrapOr(l, r)
+            | Expression::OkOr(l, r) => {
+                self.expression(l);
+                self.expression(r);
+            }
+            Expression::Chain(first, links) => {
+                self.expression(first);
+                links.iter().for_each(|(_, e)| self.expression(e));
+            }
+            Expression::Default(e)
+            | Expression::Neg(e)
+            | Expression::ToReal(e)
+            | Expression::Not(e)
+            | Expression::COk(e)
+            | Expression::CErr(e)
+            | Expression::CJust(e)
+            | Expression::Unwrap(e)
+            | Expression::IsError(e)
+            | Expression::IsNothing(e)
+            | Expression::Propagate(e) => self.expression(e),
+            Expression::ListValue(elements) => elements.iter().for_each(|e| self.expression(e)),
+            Expression::Constructor(_, args) => args.iter().for_each(|e| self.expression(e)),
+            Expression::Lambda(func) => self.function(func),
+        }
+    }
+}
+
+// Whether running the statement always ends with a `ret`, `break` or `continue`
+fn always_leaves(stmt: &Statement) -> bool {
+    match stmt {
+        Statement::Return(_) | Statement::Break | Statement::Continue => true,
+        Statement::Block(stmts) => stmts.iter().any(always_leaves),
+        Statement::Sequence(stmt1, stmt2) => always_leaves(stmt1) || always_leaves(stmt2),
+        Statement::IfThenElse(_, stmt_then, Some(stmt_else)) => {
+            always_leaves(stmt_then) && always_leaves(stmt_else)
+        }
+        Statement::When(_, cases, Some(default)) => {
+            cases.iter().all(|(_, body)| always_leaves(body)) && always_leaves(default)
+        }
+        Statement::TryCatch(body, _, handler) => always_leaves(body) && always_leaves(handler),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    fn warnings(source: &str) -> Vec<Warning> {
+        let (_, stmts) = parse(source).expect("program should parse");
+        analyze(&Statement::Block(stmts))
+    }
+
+    fn print(name: &str) -> Statement {
+        Statement::Print(Box::new(Expression::Var(name.to_string())))
+    }
+
+    #[test]
+    fn test_programs_without_mistakes_have_no_warnings() {
+        let source = "var total = 0; \
+                      def add(x: int) -> int: global total; total = total + x; ret total; end; \
+                      for i in range(0, 3): total = add(i); end; \
+                      val r = total;";
+        let (_, mut stmts) = parse(source).unwrap();
+        stmts.push(print("r"));
+        assert_eq!(analyze(&Statement::Block(stmts)), Vec::<Warning>::new());
+    }
+
+    #[test]
+    fn test_variables_read_before_being_assigned() {
+        let declare = |name: &str| {
+            Statement::VarDeclaration(
+                name.to_string(),
+                Box::new(Expression::Default(Box::new(Expression::CInt(0)))),
+            )
+        };
+        let assign =
+            |name: &str| Statement::Assignment(name.to_string(), Box::new(Expression::CInt(1)));
+        let program = Statement::Block(vec![
+            declare("a"),
+            declare("b"),
+            declare("c"),
+            assign("a"),
+            Statement::IfThenElse(
+                Box::new(Expression::CTrue),
+                Box::new(Statement::Block(vec![assign("b")])),
+                Some(Box::new(Statement::Block(vec![assign("b"), assign("c")]))),
+            ),
+            print("a"),
+            print("b"),
+            print("c"),
+            print("c"),
+        ]);
+        assert_eq!(
+            analyze(&program),
+            vec!["[Warning] variable 'c' may be read before it is assigned a value.".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_loop_variables_do_not_assign_outer_variables() {
+        let program = Statement::Block(vec![
+            Statement::VarDeclaration(
+                "x".to_string(),
+                Box::new(Expression::Default(Box::new(Expression::CInt(0)))),
+            ),
+            Statement::For(
+                "x".to_string(),
+                Box::new(Expression::ListValue(vec![Expression::CInt(1)])),
+                Box::new(Statement::Block(vec![])),
+            ),
+            print("x"),
+        ]);
+        assert_eq!(
+            analyze(&program),
+            vec!["[Warning] variable 'x' may be read before it is assigned a value.".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_unused_variables_and_parameters() {
+        let source = "val unused = 1; val _ignored = 2; \
+                      def f(a: int, b: int) -> int: val local = a; ret a; end; \
+                      val r = f(1, 2);";
+        assert_eq!(
+            warnings(source),
+            vec![
+                "[Warning] variable 'local' is declared but never used.".to_string(),
+                "[Warning] parameter 'b' of function 'f' is never used.".to_string(),
+                "[Warning] variable 'unused' is declared but never used.".to_string(),
+                "[Warning] variable 'r' is declared but never used.".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unreachable_code() {
+        let source = "def f(n: int) -> int: \
+                      if n > 0: ret 1; end else: ret 2; end; \
+                      ret 3; \
+                      end; \
+                      val r = f(1);";
+        let (_, mut stmts) = parse(source).unwrap();
+        stmts.push(print("r"));
+        assert_eq!(
+            analyze(&Statement::Block(stmts)),
+            vec!["[Warning] unreachable code in function 'f': 'ret 3' comes after a statement that always leaves the block.".to_string()]
+        );
+    }
+}
@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+
+use crate::interpreter::Value;
+use crate::ir::ast::FuncSignature;
+
+// Instructions of the stack machine. Operands are indices into the locals of
+// the current frame, the constants or strings of the program, or the code of
+// the current chunk
+#[derive(Debug, Clone, PartialEq)]
+pub enum Instruction {
+    Constant(usize),
+    Load(usize),
+    Store(usize),
+    Pop,
+
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Pow,
+    Neg,
+    ToReal,
+    And,
+    Or,
+    Not,
+    Eq,
+    Neq,
+    Gt,
+    Lt,
+    Gte,
+    Lte,
+    // Structural equality, used to select the case of a `when`
+    Matches,
+
+    Just,
+    Ok,
+    Err,
+    Unwrap,
+    Propagate,
+    IsError,
+    IsNothing,
+    UnwrapOr,
+    OkOr,
+    // Builds a list from the given number of values on top of the stack
+    List(usize),
+    Range,
+
+    Jump(usize),
+    // Jumps when the boolean on top of the stack is false
+    JumpIfFalse(usize),
+    // Calls the overload of the named function that matches the types of the
+    // given number of arguments on top of the stack
+    Call(usize, usize),
+    // Calls the overload the type checker resolved, at the given index of the
+    // overloads of the program, with the given number of arguments
+    CallOverload(usize, usize),
+    Return,
+
+    Print,
+
+    // Starts iterating over the value on top of the stack
+    IterStart,
+    // Stores the next value of the innermost iteration in a local, or ends the
+    // iteration and jumps when there are no values left
+    IterNext(usize, usize),
+    // Ends the innermost iteration before all of its values are visited
+    IterEnd,
+
+    // Marks the start of a statement of the program: `propagate` discards the
+    // output printed since then, as the tree-walking interpreter does
+    Statement,
+    // Fails with the given message
+    Fail(usize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chunk {
+    pub name: String,
+    pub code: Vec<Instruction>,
+    pub params: usize,
+    pub locals: usize,
+}
+
+impl Chunk {
+    pub fn new(name: String, params: usize) -> Chunk {
+        Chunk {
+            name,
+            code: Vec::new(),
+            params,
+            locals: params,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    pub main: Chunk,
+    pub functions: Vec<Chunk>,
+    pub signatures: HashMap<FuncSignature, usize>,
+    pub overloads: Vec<FuncSignature>,
+    pub constants: Vec<Value>,
+    pub strings: Vec<String>,
+}
@@ -0,0 +1,573 @@
+use std::collections::{HashMap, HashSet};
+
+use super::bytecode::{Chunk, Instruction, Program};
+use crate::interpreter::Value;
+use crate::ir::ast::{
+    Comparison, Expression, FuncSignature, Function, Name, Resolution, Statement, Type,
+};
+use crate::ir::builtins::builtin_call;
+
+// Compiles a type-checked program into bytecode. Programs using a feature the
+// VM does not implement are rejected, so that they can run on the interpreter
+pub fn compile(stmt: &Statement) -> Result<Program, String> {
+    let mut compiler = Compiler::new();
+    match stmt {
+        Statement::Block(stmts) => {
+            compiler.scopes.push(Vec::new());
+            for stmt in stmts {
+                compiler.emit(Instruction::Statement);
+                compiler.statement(stmt)?;
+            }
+        }
+        _ => {
+            compiler.emit(Instruction::Statement);
+            compiler.statement(stmt)?;
+        }
+    }
+    Ok(Program {
+        main: compiler.chunk,
+        functions: compiler.functions,
+        signatures: compiler.signatures,
+        overloads: compiler.overloads,
+        constants: compiler.constants,
+        strings: compiler.strings,
+    })
+}
+
+fn unsupported(feature: &str) -> String {
+    format!("The bytecode VM does not support {}", feature)
+}
+
+struct Local {
+    name: Name,
+    slot: usize,
+    mutable: bool,
+}
+
+struct Loop {
+    start: usize,
+    breaks: Vec<usize>,
+    // A `for` loop, whose iteration must be ended by `break`
+    iterates: bool,
+}
+
+struct Compiler {
+    chunk: Chunk,
+    current_func: FuncSignature,
+    // The first scope holds the parameters of a function, which, like the
+    // globals of the interpreter, can be read but not assigned
+    scopes: Vec<Vec<Local>>,
+    loops: Vec<Loop>,
+    in_function: bool,
+    functions: Vec<Chunk>,
+    function_names: HashSet<Name>,
+    signatures: HashMap<FuncSignature, usize>,
+    overloads: Vec<FuncSignature>,
+    constants: Vec<Value>,
+    strings: Vec<String>,
+}
+
+impl Compiler {
+    fn new() -> Compiler {
+        Compiler {
+            chunk: Chunk::new("main".to_string(), 0),
+            current_func: FuncSignature::new(),
+            scopes: vec![Vec::new()],
+            loops: Vec::new(),
+            in_function: false,
+            functions: Vec::new(),
+            function_names: HashSet::new(),
+            signatures: HashMap::new(),
+            overloads: Vec::new(),
+            constants: Vec::new(),
+            strings: Vec::new(),
+        }
+    }
+
+    fn emit(&mut self, instruction: Instruction) -> usize {
+        self.chunk.code.push(instruction);
+        self.chunk.code.len() - 1
+    }
+
+    fn here(&self) -> usize {
+        self.chunk.code.len()
+    }
+
+    // Makes the jump at the given position target the next instruction
+    fn patch(&mut self, at: usize) {
+        let target = self.here();
+        match &mut self.chunk.code[at] {
+            Instruction::Jump(t) | Instruction::JumpIfFalse(t) | Instruction::IterNext(_, t) => {
+                *t = target
+            }
+            _ => {}
+        }
+    }
+
+    fn constant(&mut self, value: Value) {
+        self.constants.push(value);
+        self.emit(Instruction::Constant(self.constants.len() - 1));
+    }
+
+    fn string(&mut self, string: String) -> usize {
+        self.strings.push(string);
+        self.strings.len() - 1
+    }
+
+    fn fail(&mut self, message: String) {
+        let message = self.string(message);
+        self.emit(Instruction::Fail(message));
+    }
+
+    fn new_slot(&mut self) -> usize {
+        self.chunk.locals += 1;
+        self.chunk.locals - 1
+    }
+
+    fn declare(&mut self, name: &Name, mutable: bool) -> usize {
+        let slot = self.new_slot();
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.push(Local {
+                name: name.clone(),
+                slot,
+                mutable,
+            });
+        }
+        slot
+    }
+
+    fn resolve(&self, name: &Name) -> Option<&Local> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.iter().rev().find(|local| &local.name == name))
+    }
+
+    fn statement(&mut self, stmt: &Statement) -> Result<(), String> {
+        match stmt {
+            Statement::VarDeclaration(name, exp) => self.declaration(name, exp, true),
+            Statement::ValDeclaration(name, exp) => self.declaration(name, exp, false),
+            Statement::Assignment(name, exp) => self.assignment(name, exp),
+            Statement::IfThenElse(cond, stmt_then, stmt_else) => {
+                self.expression(cond)?;
+                let to_else = self.emit(Instruction::JumpIfFalse(0));
+                self.body(stmt_then)?;
+                match stmt_else {
+                    Some(stmt_else) => {
+                        let to_end = self.emit(Instruction::Jump(0));
+                        self.patch(to_else);
+                        self.body(stmt_else)?;
+                        self.patch(to_end);
+                    }
+                    None => self.patch(to_else),
+                }
+                Ok(())
+            }
+            Statement::When(exp, cases, default) => {
+                self.expression(exp)?;
+                let value = self.new_slot();
+                self.emit(Instruction::Store(value));
+                let mut to_end = Vec::new();
+                for (case_value, case_body) in cases {
+                    self.emit(Instruction::Load(value));
+                    self.expression(case_value)?;
+                    self.emit(Instruction::Matches);
+                    let to_next = self.emit(Instruction::JumpIfFalse(0));
+                    self.body(case_body)?;
+                    to_end.push(self.emit(Instruction::Jump(0)));
+                    self.patch(to_next);
+                }
+                if let Some(default) = default {
+                    self.body(default)?;
+                }
+                for jump in to_end {
+                    self.patch(jump);
+                }
+                Ok(())
+            }
+            Statement::While(cond, body) => {
+                let start = self.here();
+                self.expression(cond)?;
+                let to_end = self.emit(Instruction::JumpIfFalse(0));
+                self.loop_body(start, false, body)?;
+                self.patch(to_end);
+                self.end_loop();
+                Ok(())
+            }
+            Statement::For(var, iterable, body) => {
+                self.expression(iterable)?;
+                self.emit(Instruction::IterStart);
+                // Like the interpreter, the loop variable lives in a scope of its own
+                self.scopes.push(Vec::new());
+                let slot = self.declare(var, false);
+                let start = self.here();
+                let to_end = self.emit(Instruction::IterNext(slot, 0));
+                self.loop_body(start, true, body)?;
+                self.patch(to_end);
+                self.end_loop();
+                self.scopes.pop();
+                Ok(())
+            }
+            Statement::Block(stmts) => self.block(stmts),
+            Statement::Sequence(s1, s2) => {
+                self.statement(s1)?;
+                self.statement(s2)
+            }
+            Statement::FuncDef(func) => self.function(func),
+            Statement::Return(exp) => {
+                self.expression(exp)?;
+                self.emit(Instruction::Return);
+                Ok(())
+            }
+            Statement::Print(exp) => {
+                self.expression(exp)?;
+                self.emit(Instruction::Print);
+                Ok(())
+            }
+            Statement::SingleFuncCall(name, args, resolution) => {
+                self.call(name, args, resolution)?;
+                self.emit(Instruction::Pop);
+                Ok(())
+            }
+            Statement::Break => {
+                let Some(innermost) = self.loops.last() else {
+                    return Err(unsupported("'break' outside of a loop"));
+                };
+                if innermost.iterates {
+                    self.emit(Instruction::IterEnd);
+                }
+                let jump = self.emit(Instruction::Jump(0));
+                if let Some(innermost) = self.loops.last_mut() {
+                    innermost.breaks.push(jump);
+                }
+                Ok(())
+            }
+            Statement::Continue => {
+                let Some(innermost) = self.loops.last() else {
+                    return Err(unsupported("'continue' outside of a loop"));
+                };
+                let start = innermost.start;
+                self.emit(Instruction::Jump(start));
+                Ok(())
+            }
+            Statement::Comment(_) => Ok(()),
+            Statement::TypeDeclaration(..) => Err(unsupported("algebraic data types")),
+            Statement::Global(_) => Err(unsupported("global variables")),
+            Statement::TryCatch(..) => Err(unsupported("try/catch")),
+            _ => {
+                self.fail(String::from("not implemented yet"));
+                Ok(())
+            }
+        }
+    }
+
+    // Like in the interpreter, a body has a scope of its own even if it is not a block
+    fn body(&mut self, stmt: &Statement) -> Result<(), String> {
+        match stmt {
+            Statement::Block(stmts) => self.block(stmts),
+            _ => self.block(std::slice::from_ref(stmt)),
+        }
+    }
+
+    fn block(&mut self, stmts: &[Statement]) -> Result<(), String> {
+        self.scopes.push(Vec::new());
+        for stmt in stmts {
+            self.statement(stmt)?;
+        }
+        self.scopes.pop();
+        Ok(())
+    }
+
+    fn loop_body(&mut self, start: usize, iterates: bool, body: &Statement) -> Result<(), String> {
+        self.loops.push(Loop {
+            start,
+            breaks: Vec::new(),
+            iterates,
+        });
+        self.body(body)?;
+        self.emit(Instruction::Jump(start));
+        Ok(())
+    }
+
+    fn end_loop(&mut self) {
+        if let Some(innermost) = self.loops.pop() {
+            for jump in innermost.breaks {
+                self.patch(jump);
+            }
+        }
+    }
+
+    fn declaration(&mut self, name: &Name, exp: &Expression, mutable: bool) -> Result<(), String> {
+        self.expression(exp)?;
+        let declared = self
+            .scopes
+            .last()
+            .is_some_and(|scope| scope.iter().any(|local| &local.name == name));
+        if declared {
+            self.fail(format!(
+                "Variable '{}' was declared multiple times in function '{}'",
+                name, self.current_func
+            ));
+        } else {
+            let slot = self.declare(name, mutable);
+            self.emit(Instruction::Store(slot));
+        }
+        Ok(())
+    }
+
+    fn assignment(&mut self, name: &Name, exp: &Expression) -> Result<(), String> {
+        if let Expression::Lambda(_) = exp {
+            return Err(unsupported("functions assigned to variables"));
+        }
+        self.expression(exp)?;
+        let target = self.scopes[1..]
+            .iter()
+            .rev()
+            .find_map(|scope| scope.iter().rev().find(|local| &local.name == name))
+            .map(|local| (local.slot, local.mutable));
+        match target {
+            Some((slot, true)) => {
+                self.emit(Instruction::Store(slot));
+            }
+            Some((_, false)) => self.fail(format!(
+                "Variable `{}` cannot be assigned to a value because it is immutable",
+                name
+            )),
+            None if self.in_function && self.resolve(name).is_none() => {
+                return Err(unsupported("global variables"));
+            }
+            None => self.fail(format!(
+                "Variable '{}' was never declared in function '{}'",
+                name, self.current_func
+            )),
+        }
+        Ok(())
+    }
+
+    fn function(&mut self, func: &Function) -> Result<(), String> {
+        if self.in_function || self.scopes.len() > 2 {
+            return Err(unsupported("functions declared inside blocks or functions"));
+        }
+        if func
+            .params
+            .iter()
+            .any(|param| matches!(param.argument_type, Type::TFunction(..)))
+        {
+            return Err(unsupported("functions passed as arguments"));
+        }
+        let signature = FuncSignature::from_func(func);
+        if self.signatures.contains_key(&signature) {
+            return Err(unsupported("functions declared more than once"));
+        }
+        self.signatures
+            .insert(signature.clone(), self.functions.len());
+        self.function_names.insert(func.name.clone());
+
+        let main = std::mem::replace(
+            &mut self.chunk,
+            Chunk::new(signature.to_string(), func.params.len()),
+        );
+        let main_scopes = std::mem::replace(
+            &mut self.scopes,
+            vec![
+                func.params
+                    .iter()
+                    .enumerate()
+                    .map(|(slot, param)| Local {
+                        name: param.argument_name.clone(),
+                        slot,
+                        mutable: false,
+                    })
+                    .collect(),
+            ],
+        );
+        let main_loops = std::mem::take(&mut self.loops);
+        let main_func = std::mem::replace(&mut self.current_func, signature.clone());
+        self.in_function = true;
+
+        let result = match &func.body {
+            Some(body) => self.body(body),
+            None => {
+                self.fail(format!("Function {} has no body", signature));
+                Ok(())
+            }
+        };
+        if func.kind == Type::TVoid {
+            self.constant(Value::Void);
+            self.emit(Instruction::Return);
+        } else {
+            self.fail(format!("Function {:?} did not return a value", signature));
+        }
+
+        self.in_function = false;
+        self.current_func = main_func;
+        self.loops = main_loops;
+        self.scopes = main_scopes;
+        let chunk = std::mem::replace(&mut self.chunk, main);
+        self.functions.push(chunk);
+        result
+    }
+
+    fn call(
+        &mut self,
+        name: &Name,
+        args: &[Expression],
+        resolution: &Option<Resolution>,
+    ) -> Result<(), String> {
+        let builtin = match resolution {
+            Some(Resolution::Builtin) => builtin_call(name, args),
+            Some(Resolution::Overload(_)) => None,
+            None => builtin_call(name, args).filter(|_| !self.function_names.contains(name)),
+        };
+        if let Some(builtin) = builtin {
+            return self.expression(&builtin);
+        }
+        for arg in args {
+            match arg {
+                Expression::Var(var) => match self.resolve(var).map(|local| local.slot) {
+                    Some(slot) => {
+                        self.emit(Instruction::Load(slot));
+                    }
+                    None if self.function_names.contains(var) => {
+                        return Err(unsupported("functions passed as arguments"));
+                    }
+                    None if self.in_function => return Err(unsupported("global variables")),
+                    None => self.fail(format!("Identifier '{}' was never declared", var)),
+                },
+                Expression::Lambda(_) => return Err(unsupported("functions passed as arguments")),
+                _ => self.expression(arg)?,
+            }
+        }
+        match resolution {
+            Some(Resolution::Overload(func_signature)) => {
+                self.overloads.push(func_signature.clone());
+                self.emit(Instruction::CallOverload(
+                    self.overloads.len() - 1,
+                    args.len(),
+                ));
+            }
+            _ => {
+                let name = self.string(name.clone());
+                self.emit(Instruction::Call(name, args.len()));
+            }
+        }
+        Ok(())
+    }
+
+    fn binary(
+        &mut self,
+        lhs: &Expression,
+        rhs: &Expression,
+        instruction: Instruction,
+    ) -> Result<(), String> {
+        self.expression(lhs)?;
+        self.expression(rhs)?;
+        self.emit(instruction);
+        Ok(())
+    }
+
+    // Each operand shared by two comparisons is kept in a slot of its own, so
+    // that it is evaluated once
+    fn chain(
+        &mut self,
+        first: &Expression,
+        links: &[(Comparison, Expression)],
+    ) -> Result<(), String> {
+        self.expression(first)?;
+        let shared = self.new_slot();
+        for (i, (comparison, operand)) in links.iter().enumerate() {
+            if i > 0 {
+                self.emit(Instruction::Load(shared));
+            }
+            self.expression(operand)?;
+            if i + 1 < links.len() {
+                self.emit(Instruction::Store(shared));
+                self.emit(Instruction::Load(shared));
+            }
+            self.emit(match comparison {
+                Comparison::EQ => Instruction::Eq,
+                Comparison::NEQ => Instruction::Neq,
+                Comparison::GT => Instruction::Gt,
+                Comparison::LT => Instruction::Lt,
+                Comparison::GTE => Instruction::Gte,
+                Comparison::LTE => Instruction::Lte,
+            });
+            if i > 0 {
+                self.emit(Instruction::And);
+            }
+        }
+        Ok(())
+    }
+
+    fn unary(&mut self, exp: &Expression, instruction: Instruction) -> Result<(), String> {
+        self.expression(exp)?;
+        self.emit(instruction);
+        Ok(())
+    }
+
+    fn expression(&mut self, exp: &Expression) -> Result<(), String> {
+        match exp {
+            Expression::CTrue => self.constant(Value::Bool(true)),
+            Expression::CFalse => self.constant(Value::Bool(false)),
+            Expression::CInt(value) => self.constant(Value::Int(*value)),
+            Expression::CReal(value) => self.constant(Value::Real(*value)),
+            Expression::CString(value) => self.constant(Value::str(value)),
+            Expression::CVoid => self.constant(Value::Void),
+            Expression::Default(exp) => self.expression(exp)?,
+            Expression::CNothing => self.constant(Value::Nothing),
+            Expression::Var(name) => match self.resolve(name).map(|local| local.slot) {
+                Some(slot) => {
+                    self.emit(Instruction::Load(slot));
+                }
+                // The slots of the top-level block are not reachable from a function
+                None if self.in_function => return Err(unsupported("global variables")),
+                None => self.fail(format!("Variable '{}' not found", name)),
+            },
+            Expression::FuncCall(name, args, resolution) => self.call(name, args, resolution)?,
+            Expression::Add(lhs, rhs) => self.binary(lhs, rhs, Instruction::Add)?,
+            Expression::Sub(lhs, rhs) => self.binary(lhs, rhs, Instruction::Sub)?,
+            Expression::Mul(lhs, rhs) => self.binary(lhs, rhs, Instruction::Mul)?,
+            Expression::Div(lhs, rhs) => self.binary(lhs, rhs, Instruction::Div)?,
+            Expression::Mod(lhs, rhs) => self.binary(lhs, rhs, Instruction::Mod)?,
+            Expression::Pow(lhs, rhs) => self.binary(lhs, rhs, Instruction::Pow)?,
+            Expression::Neg(e) => self.unary(e, Instruction::Neg)?,
+            Expression::ToReal(e) => self.unary(e, Instruction::ToReal)?,
+            Expression::And(lhs, rhs) => self.binary(lhs, rhs, Instruction::And)?,
+            Expression::Or(lhs, rhs) => self.binary(lhs, rhs, Instruction::Or)?,
+            Expression::Not(e) => self.unary(e, Instruction::Not)?,
+            Expression::EQ(lhs, rhs) => self.binary(lhs, rhs, Instruction::Eq)?,
+            Expression::NEQ(lhs, rhs) => self.binary(lhs, rhs, Instruction::Neq)?,
+            Expression::GT(lhs, rhs) => self.binary(lhs, rhs, Instruction::Gt)?,
+            Expression::LT(lhs, rhs) => self.binary(lhs, rhs, Instruction::Lt)?,
+            Expression::GTE(lhs, rhs) => self.binary(lhs, rhs, Instruction::Gte)?,
+            Expression::LTE(lhs, rhs) => self.binary(lhs, rhs, Instruction::Lte)?,
+            Expression::Chain(first, links) => self.chain(first, links)?,
+            Expression::COk(e) => self.unary(e, Instruction::Ok)?,
+            Expression::CErr(e) => self.unary(e, Instruction::Err)?,
+            Expression::CJust(e) => self.unary(e, Instruction::Just)?,
+            Expression::Unwrap(e) => self.unary(e, Instruction::Unwrap)?,
+            Expression::IsError(e) => self.unary(e, Instruction::IsError)?,
+            Expression::IsNothing(e) => self.unary(e, Instruction::IsNothing)?,
+            Expression::Propagate(e) => self.unary(e, Instruction::Propagate)?,
+            Expression::Map(..) | Expression::AndThen(..) => {
+                return Err(unsupported("functions passed as arguments"));
+            }
+            Expression::UnwrapOr(e, default) => self.binary(e, default, Instruction::UnwrapOr)?,
+            Expression::OkOr(e, error) => self.binary(e, error, Instruction::OkOr)?,
+            Expression::ListValue(values) => {
+                for value in values {
+                    self.expression(value)?;
+                }
+                self.emit(Instruction::List(values.len()));
+            }
+            Expression::Range(start, end) => self.binary(start, end, Instruction::Range)?,
+            Expression::Constructor(..) => return Err(unsupported("algebraic data types")),
+            Expression::FuncRef(_) => return Err(unsupported("functions passed as arguments")),
+            Expression::Lambda(_) => self.fail(format!(
+                "Interpreter still does not support expression of type {:?}",
+                exp
+            )),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,253 @@
+use std::rc::Rc;
+
+use super::bytecode::{Chunk, Instruction, Program};
+use crate::interpreter::Value;
+use crate::interpreter::expression_eval::{
+    ExpressionResult, add_values, and_values, div_values, eq_values, err_value, gt_values,
+    gte_values, is_error_value, is_nothing_value, just_value, lt_values, lte_values, mod_values,
+    mul_values, neg_value, neq_values, not_value, ok_or_values, ok_value, or_values, pow_values,
+    propagate_value, range_values, sub_values, to_real_value, unwrap_or_values, unwrap_value,
+};
+use crate::interpreter::statement_execute::iteration_values;
+use crate::ir::ast::FuncSignature;
+
+// Deep recursion fails with a diagnostic instead of exhausting the memory
+const MAX_CALL_DEPTH: usize = 10000;
+
+// Runs a compiled program, returning the lines it prints
+pub fn execute(program: &Program) -> Result<Vec<String>, String> {
+    Machine::new(program).run()
+}
+
+// The state of a caller, restored when the called function returns
+struct Frame<'a> {
+    chunk: &'a Chunk,
+    ip: usize,
+    base: usize,
+    stack: usize,
+    iterators: usize,
+}
+
+struct Machine<'a> {
+    program: &'a Program,
+    stack: Vec<Value>,
+    // The locals of all frames: a frame sees the ones from its base onwards
+    locals: Vec<Value>,
+    iterators: Vec<Box<dyn Iterator<Item = Value>>>,
+    frames: Vec<Frame<'a>>,
+    output: Vec<String>,
+    // Length of the output when the current statement of the program started
+    statement_output: usize,
+}
+
+impl<'a> Machine<'a> {
+    fn new(program: &'a Program) -> Machine<'a> {
+        Machine {
+            program,
+            stack: Vec::new(),
+            locals: vec![Value::Void; program.main.locals],
+            iterators: Vec::new(),
+            frames: Vec::new(),
+            output: Vec::new(),
+            statement_output: 0,
+        }
+    }
+
+    fn pop(&mut self) -> Result<Value, String> {
+        self.stack
+            .pop()
+            .ok_or_else(|| "VM stack underflow".to_string())
+    }
+
+    fn pop_many(&mut self, count: usize) -> Result<Vec<Value>, String> {
+        if count > self.stack.len() {
+            return Err("VM stack underflow".to_string());
+        }
+        Ok(self.stack.split_off(self.stack.len() - count))
+    }
+
+    fn unary(&mut self, op: fn(Value) -> Result<Value, String>) -> Result<(), String> {
+        let value = self.pop()?;
+        self.stack.push(op(value)?);
+        Ok(())
+    }
+
+    fn binary(&mut self, op: fn(Value, Value) -> Result<Value, String>) -> Result<(), String> {
+        let v2 = self.pop()?;
+        let v1 = self.pop()?;
+        self.stack.push(op(v1, v2)?);
+        Ok(())
+    }
+
+    // Hands the value a function returns to its caller, giving back the chunk,
+    // instruction and base of the caller
+    fn return_to(
+        &mut self,
+        frame: Frame<'a>,
+        base: usize,
+        value: Value,
+    ) -> (&'a Chunk, usize, usize) {
+        self.locals.truncate(base);
+        self.stack.truncate(frame.stack);
+        self.iterators.truncate(frame.iterators);
+        self.stack.push(value);
+        (frame.chunk, frame.ip, frame.base)
+    }
+
+    // Calls the function of the signature from the given chunk, instruction and
+    // base, giving back those of the function
+    fn enter(
+        &mut self,
+        func_signature: &FuncSignature,
+        args: Vec<Value>,
+        (chunk, ip, base): (&'a Chunk, usize, usize),
+    ) -> Result<(&'a Chunk, usize, usize), String> {
+        let program = self.program;
+        let Some(&index) = program.signatures.get(func_signature) else {
+            return Err(format!("Function '{}' not found", func_signature));
+        };
+        if self.frames.len() >= MAX_CALL_DEPTH {
+            return Err(format!(
+                "Maximum recursion depth exceeded calling {}",
+                func_signature
+            ));
+        }
+        self.frames.push(Frame {
+            chunk,
+            ip,
+            base,
+            stack: self.stack.len(),
+            iterators: self.iterators.len(),
+        });
+        let chunk = &program.functions[index];
+        let base = self.locals.len();
+        self.locals.extend(args);
+        self.locals.resize(base + chunk.locals, Value::Void);
+        Ok((chunk, 0, base))
+    }
+
+    fn run(mut self) -> Result<Vec<String>, String> {
+        let program = self.program;
+        let mut chunk = &program.main;
+        let mut ip = 0;
+        let mut base = 0;
+
+        // Functions always end with a failure, so only the main chunk runs out of code
+        while let Some(instruction) = chunk.code.get(ip) {
+            ip += 1;
+            match instruction {
+                Instruction::Constant(index) => self.stack.push(program.constants[*index].clone()),
+                Instruction::Load(slot) => self.stack.push(self.locals[base + slot].clone()),
+                Instruction::Store(slot) => self.locals[base + slot] = self.pop()?,
+                Instruction::Pop => {
+                    self.pop()?;
+                }
+
+                Instruction::Add => self.binary(add_values)?,
+                Instruction::Sub => self.binary(sub_values)?,
+                Instruction::Mul => self.binary(mul_values)?,
+                Instruction::Div => self.binary(div_values)?,
+                Instruction::Mod => self.binary(mod_values)?,
+                Instruction::Pow => self.binary(pow_values)?,
+                Instruction::Neg => self.unary(neg_value)?,
+                Instruction::ToReal => self.unary(to_real_value)?,
+                Instruction::And => self.binary(and_values)?,
+                Instruction::Or => self.binary(or_values)?,
+                Instruction::Not => self.unary(not_value)?,
+                Instruction::Eq => self.binary(eq_values)?,
+                Instruction::Neq => self.binary(neq_values)?,
+                Instruction::Gt => self.binary(gt_values)?,
+                Instruction::Lt => self.binary(lt_values)?,
+                Instruction::Gte => self.binary(gte_values)?,
+                Instruction::Lte => self.binary(lte_values)?,
+                Instruction::Matches => self.binary(|v1, v2| Ok(Value::Bool(v1 == v2)))?,
+
+                Instruction::Just => self.unary(just_value)?,
+                Instruction::Ok => self.unary(ok_value)?,
+                Instruction::Err => self.unary(err_value)?,
+                Instruction::Unwrap => self.unary(unwrap_value)?,
+                Instruction::IsError => self.unary(is_error_value)?,
+                Instruction::IsNothing => self.unary(is_nothing_value)?,
+                Instruction::UnwrapOr => self.binary(unwrap_or_values)?,
+                Instruction::OkOr => self.binary(ok_or_values)?,
+                Instruction::Propagate => {
+                    let value = self.pop()?;
+                    match propagate_value(value)? {
+                        ExpressionResult::Value(value) => self.stack.push(value),
+                        // A function returns the Nothing or Err it found. Propagating out of
+                        // the program ends it, discarding what its last statement printed
+                        ExpressionResult::Propagate(failure) => {
+                            let Some(frame) = self.frames.pop() else {
+                                self.output.truncate(self.statement_output);
+                                return Ok(self.output);
+                            };
+                            (chunk, ip, base) = self.return_to(frame, base, failure);
+                        }
+                    }
+                }
+                Instruction::List(count) => {
+                    let values = self.pop_many(*count)?;
+                    self.stack.push(Value::List(Rc::new(values)));
+                }
+                Instruction::Range => self.binary(range_values)?,
+
+                Instruction::Jump(target) => ip = *target,
+                Instruction::JumpIfFalse(target) => match self.pop()? {
+                    Value::Bool(true) => {}
+                    Value::Bool(false) => ip = *target,
+                    _ => return Err("Condition must evaluate to a boolean".to_string()),
+                },
+                Instruction::Call(name, argc) => {
+                    let args = self.pop_many(*argc)?;
+                    // Calls the type checker did not resolve dispatch on the types of the values
+                    let func_signature = FuncSignature {
+                        name: program.strings[*name].clone(),
+                        argument_types: args.iter().map(Value::type_of).collect(),
+                    };
+                    (chunk, ip, base) = self.enter(&func_signature, args, (chunk, ip, base))?;
+                }
+                Instruction::CallOverload(overload, argc) => {
+                    let args = self.pop_many(*argc)?;
+                    let func_signature = &program.overloads[*overload];
+                    (chunk, ip, base) = self.enter(func_signature, args, (chunk, ip, base))?;
+                }
+                Instruction::Return => {
+                    let value = self.pop()?;
+                    // Returning from the program itself ends it
+                    let Some(frame) = self.frames.pop() else {
+                        return Ok(self.output);
+                    };
+                    (chunk, ip, base) = self.return_to(frame, base, value);
+                }
+
+                Instruction::Print => match self.pop()? {
+                    value @ (Value::Str(_) | Value::Int(_) | Value::Real(_) | Value::Bool(_)) => {
+                        self.output.push(value.to_string())
+                    }
+                    _ => return Err("Type not supported by print statement".to_string()),
+                },
+
+                Instruction::IterStart => {
+                    let value = self.pop()?;
+                    self.iterators.push(iteration_values(value)?);
+                }
+                Instruction::IterNext(slot, target) => {
+                    match self.iterators.last_mut().and_then(|values| values.next()) {
+                        Some(value) => self.locals[base + slot] = value,
+                        None => {
+                            self.iterators.pop();
+                            ip = *target;
+                        }
+                    }
+                }
+                Instruction::IterEnd => {
+                    self.iterators.pop();
+                }
+
+                Instruction::Statement => self.statement_output = self.output.len(),
+                Instruction::Fail(message) => return Err(program.strings[*message].clone()),
+            }
+        }
+        Ok(self.output)
+    }
+}
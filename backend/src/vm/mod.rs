@@ -0,0 +1,321 @@
+pub mod bytecode;
+pub mod compiler;
+pub mod machine;
+
+pub use compiler::compile;
+pub use machine::execute;
+
+#[cfg(test)]
+mod tests {
+    use super::bytecode::Instruction;
+    use super::*;
+    use crate::environment::environment::Environment;
+    use crate::interpreter::{Value, run};
+    use crate::ir::ast::{Expression, FormalArgument, Function, Statement, Type};
+    use crate::parser::parse;
+    use crate::test_utils::{checked_program, lines};
+
+    fn interpret(program: &Statement) -> Result<Vec<String>, String> {
+        let mut env: Environment<Value> = Environment::new();
+        run(program, &mut env).map(|()| env.output)
+    }
+
+    // Runs the program on both the interpreter and the VM, which must agree
+    fn assert_same_behaviour(program: &Statement) -> Result<Vec<String>, String> {
+        let bytecode = compile(program).expect("program should compile to bytecode");
+        let expected = interpret(program);
+        assert_eq!(execute(&bytecode), expected);
+        expected
+    }
+
+    fn program(source: &str, printed: &[&str]) -> Statement {
+        checked_program(source, printed).expect("program should type check")
+    }
+
+    fn print(exp: Expression) -> Statement {
+        Statement::Print(Box::new(exp))
+    }
+
+    #[test]
+    fn test_recursive_function() {
+        let source = "def fib(n: int) -> int: if n < 2: ret n; end; ret fib(n - 1) + fib(n - 2); end; \
+                      var x = fib(15);";
+        assert_eq!(
+            assert_same_behaviour(&program(source, &["x"])),
+            lines(&["610"])
+        );
+    }
+
+    #[test]
+    fn test_while_with_break_and_continue() {
+        let source = "var total = 0; var i = 0; \
+                      while i < 10: i = i + 1; \
+                      if i == 3: continue; end; if i == 8: break; end; \
+                      total = total + i; end;";
+        assert_eq!(
+            assert_same_behaviour(&program(source, &["total", "i"])),
+            lines(&["25", "8"])
+        );
+    }
+
+    #[test]
+    fn test_for_loops() {
+        let source = "var s = 0; for x in range(0, 5): s = s + x; end; \
+                      var n = 0; for c in \"abc\": n = n + 1; end; \
+                      for y in [1, 2, 3]: if y == 2: break; end; s = s + y * 10; end; \
+                      for z in Just(7): s = s + z; end;";
+        assert_eq!(
+            assert_same_behaviour(&program(source, &["s", "n"])),
+            lines(&["27", "3"])
+        );
+        // The loop variable ends with the loop, leaving an outer one untouched
+        let source = "val i = 10; var s = 0; for i in [1, 2]: s = s + i; end;";
+        assert_eq!(
+            assert_same_behaviour(&program(source, &["s", "i"])),
+            lines(&["3", "10"])
+        );
+    }
+
+    #[test]
+    fn test_chained_comparisons() {
+        let source = "def between(lo: int, n: int, hi: int) -> bool: ret lo <= n * 2 < hi; end; \
+                      val a = between(1, 3, 7); val b = between(1, 4, 7); \
+                      val c = 1 < 2 < 3 != 3; val d = 3 > 2 > 1 == 1.0;";
+        assert_eq!(
+            assert_same_behaviour(&program(source, &["a", "b", "c", "d"])),
+            lines(&["True", "False", "False", "True"])
+        );
+    }
+
+    #[test]
+    fn test_functions_hide_builtins_of_the_same_name() {
+        let source = "val r = ok_or(5, 2); def ok_or(a: int, b: int) -> int: ret a - b; end;";
+        assert_eq!(
+            assert_same_behaviour(&program(source, &["r"])),
+            lines(&["3"])
+        );
+    }
+
+    #[test]
+    fn test_return_from_inside_a_for_loop() {
+        let source = "def first_even(n: int) -> int: \
+                      for i in range(1, n): if i % 2 == 0: ret i; end; end; ret 0; end; \
+                      var a = 0; for k in [3, 5]: a = a + first_even(k); end;";
+        assert_eq!(
+            assert_same_behaviour(&program(source, &["a"])),
+            lines(&["4"])
+        );
+    }
+
+    #[test]
+    fn test_long_loop_with_calls() {
+        let source = "def step(n: int) -> int: ret n % 7; end; \
+                      var i = 0; var total = 0; \
+                      while i < 3000: total = total + step(i); i = i + 1; end;";
+        assert_eq!(
+            assert_same_behaviour(&program(source, &["total"])),
+            lines(&["8994"])
+        );
+    }
+
+    #[test]
+    fn test_when_statement() {
+        let source = "var x = 2; var y = 0; \
+                      when x: case 1: y = 10; end case 2: y = 20; end else: y = 30; end end;";
+        assert_eq!(
+            assert_same_behaviour(&program(source, &["y"])),
+            lines(&["20"])
+        );
+    }
+
+    #[test]
+    fn test_overloaded_functions() {
+        let source = "def f(a: int) -> int: ret a + 1; end; \
+                      def f(a: float) -> float: ret a * 2.0; end; \
+                      var i = f(1); var r = f(1.5);";
+        assert_eq!(
+            assert_same_behaviour(&program(source, &["i", "r"])),
+            lines(&["2", "3"])
+        );
+    }
+
+    #[test]
+    fn test_mutually_recursive_functions() {
+        let source = "var x = is_even(7); \
+                      def is_even(n: int) -> bool: if n == 0: ret True; end; ret is_odd(n - 1); end; \
+                      def is_odd(n: int) -> bool: if n == 0: ret False; end; ret is_even(n - 1); end; \
+                      var y = is_odd(7);";
+        assert_eq!(
+            assert_same_behaviour(&program(source, &["x", "y"])),
+            lines(&["False", "True"])
+        );
+    }
+
+    #[test]
+    fn test_void_functions_may_end_without_ret() {
+        let source = "def skip(n: int) -> Unit: var i = n; while i > 0: i = i - 1; end; end; \
+                      var a = skip(3); var b = skip(-1); var done = 1;";
+        assert_eq!(
+            assert_same_behaviour(&program(source, &["done"])),
+            lines(&["1"])
+        );
+    }
+
+    #[test]
+    fn test_int_arguments_widen_to_real_parameters() {
+        let source = "def f(x: float) -> float: ret x * 2.0; end; \
+                      def g(a: int, b: float) -> int: ret 1; end; \
+                      def g(a: float, b: float) -> int: ret 2; end; \
+                      def h(a: int) -> int: ret 1; end; def h(a: float) -> int: ret 2; end; \
+                      def half(x: float) -> float: ret x / 2; end; \
+                      var r = f(3); var s = g(1, 2); var t = h(1); var u = h(1.0); var v = half(3);";
+        let checked = program(source, &["r", "s", "t", "u", "v"]);
+        // The type checker converts the ints before the calls
+        let conversions = compile(&checked)
+            .expect("program should compile to bytecode")
+            .main
+            .code
+            .into_iter()
+            .filter(|instruction| *instruction == Instruction::ToReal)
+            .count();
+        assert_eq!(conversions, 3);
+        assert_eq!(
+            assert_same_behaviour(&checked),
+            lines(&["6", "1", "1", "2", "1.5"])
+        );
+    }
+
+    #[test]
+    fn test_type_checked_calls_use_the_resolved_overload() {
+        let source = "def f(a: int) -> int: ret a + 1; end; var x = f(1);";
+        let calls = |program: &Statement| {
+            compile(program)
+                .expect("program should compile to bytecode")
+                .main
+                .code
+                .into_iter()
+                .filter(|instruction| {
+                    matches!(
+                        instruction,
+                        Instruction::Call(..) | Instruction::CallOverload(..)
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let (_, stmts) = parse(source).expect("program should parse");
+        assert!(matches!(
+            calls(&Statement::Block(stmts))[..],
+            [Instruction::Call(_, 1)]
+        ));
+
+        let checked = program(source, &["x"]);
+        assert_eq!(calls(&checked), vec![Instruction::CallOverload(0, 1)]);
+        assert_eq!(assert_same_behaviour(&checked), lines(&["2"]));
+    }
+
+    #[test]
+    fn test_runtime_errors() {
+        let overflow = program("var x = 2147483647; var y = x + 1;", &["y"]);
+        assert!(assert_same_behaviour(&overflow).is_err());
+
+        let modulo = program("var x = 0; var y = 1 % x;", &["y"]);
+        assert!(assert_same_behaviour(&modulo).is_err());
+
+        let unwrap = Statement::Block(vec![print(Expression::Unwrap(Box::new(
+            Expression::CNothing,
+        )))]);
+        assert!(assert_same_behaviour(&unwrap).is_err());
+    }
+
+    #[test]
+    fn test_propagate_ends_the_program() {
+        let program = Statement::Block(vec![
+            print(Expression::CString("a".to_string())),
+            Statement::Block(vec![
+                print(Expression::CString("b".to_string())),
+                print(Expression::Propagate(Box::new(Expression::CErr(Box::new(
+                    Expression::CInt(1),
+                ))))),
+            ]),
+            print(Expression::CString("c".to_string())),
+        ]);
+        assert_eq!(assert_same_behaviour(&program), lines(&["a"]));
+    }
+
+    #[test]
+    fn test_try_unwrap_returns_from_the_function() {
+        let source = "def half(n: int) -> Maybe[int]: \
+                      if n % 2 == 0: ret Just(n / 2); end; ret Nothing; end; \
+                      def quarter(n: int) -> Maybe[int]: \
+                      var h = 0; for i in [1, 2]: h = tryUnwrap(half(n)); end; ret half(h); end; \
+                      val a = unwrap_or(quarter(12), -1); val b = unwrap_or(quarter(6), -1); \
+                      val c = isError(ok_or(quarter(2), \"odd\"));";
+        assert_eq!(
+            assert_same_behaviour(&program(source, &["a", "b", "c"])),
+            lines(&["3", "-1", "True"])
+        );
+
+        let combinators = program(
+            "val m = map(Just(1), lambda (n: int) -> int: ret n end);",
+            &[],
+        );
+        let unsupported =
+            Err("The bytecode VM does not support functions passed as arguments".to_string());
+        assert_eq!(compile(&combinators).map(|_| ()), unsupported);
+    }
+
+    #[test]
+    fn test_assigning_a_parameter_fails() {
+        let program = Statement::Block(vec![
+            Statement::FuncDef(Function {
+                name: "f".to_string(),
+                kind: Type::TInteger,
+                params: vec![FormalArgument::new("a".to_string(), Type::TInteger)],
+                body: Some(Box::new(Statement::Block(vec![
+                    Statement::Assignment("a".to_string(), Box::new(Expression::CInt(1))),
+                    Statement::Return(Box::new(Expression::Var("a".to_string()))),
+                ]))),
+            }),
+            print(Expression::FuncCall(
+                "f".to_string(),
+                vec![Expression::CInt(2)],
+                None,
+            )),
+        ]);
+        assert!(assert_same_behaviour(&program).is_err());
+    }
+
+    #[test]
+    fn test_unsupported_programs_are_not_compiled() {
+        let break_outside = Statement::Block(vec![Statement::Break]);
+        assert!(compile(&break_outside).is_err());
+
+        let try_catch = program(
+            "var x = 0; try: x = 1 / x; end catch e: x = 1; end;",
+            &["x"],
+        );
+        assert_eq!(
+            compile(&try_catch).map(|_| ()),
+            Err("The bytecode VM does not support try/catch".to_string())
+        );
+        assert_eq!(interpret(&try_catch), lines(&["1"]));
+    }
+
+    #[test]
+    fn test_functions_using_global_variables_are_left_to_the_interpreter() {
+        let reads = program(
+            "val step = 2; def f(n: int) -> int: ret n * step; end;",
+            &[],
+        );
+        let writes = program(
+            "var count = 0; def inc() -> int: global count; count = count + 1; ret count; end; \
+             val a = inc();",
+            &["count"],
+        );
+        let unsupported = Err("The bytecode VM does not support global variables".to_string());
+        assert_eq!(compile(&reads).map(|_| ()), unsupported);
+        assert_eq!(compile(&writes).map(|_| ()), unsupported);
+        assert_eq!(interpret(&writes), lines(&["1"]));
+    }
+}
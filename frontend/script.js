@@ -236,6 +236,84 @@ Blockly.defineBlocksWithJsonArray([{
   "helpUrl": ""
 }]);
 
+/***** IF-ELIF-ELSE BLOCK *****/
+Blockly.defineBlocksWithJsonArray([{
+  "type": "if_elif_else_block",
+  "message0": "if %1 then %2 elif %3 then %4 else %5",
+  "args0": [
+    {
+      "type": "input_value",
+      "name": "CONDITION",
+    },
+    {
+      "type": "input_statement",
+      "name": "IF_BODY"
+    },
+    {
+      "type": "input_value",
+      "name": "ELIF_CONDITION0",
+    },
+    {
+      "type": "input_statement",
+      "name": "ELIF_BODY0"
+    },
+    {
+      "type": "input_statement",
+      "name": "ELSE_BODY"
+    }
+  ],
+  "previousStatement": null,
+  "nextStatement": null,
+  "colour": '#465881',
+  "tooltip": "If-Elif-Else conditional",
+  "helpUrl": ""
+}]);
+
+/***** WHEN AND CASE BLOCKS *****/
+Blockly.defineBlocksWithJsonArray([{
+  "type": "when_block",
+  "message0": "when %1 %2 else %3",
+  "args0": [
+    {
+      "type": "input_value",
+      "name": "VALUE",
+    },
+    {
+      "type": "input_statement",
+      "name": "CASES",
+      "check": "case"
+    },
+    {
+      "type": "input_statement",
+      "name": "DEFAULT_BODY"
+    }
+  ],
+  "previousStatement": null,
+  "nextStatement": null,
+  "colour": '#465881',
+  "tooltip": "Runs the case whose value is equal to the int or string value",
+  "helpUrl": ""
+},
+{
+  "type": "case_block",
+  "message0": "case %1 do %2",
+  "args0": [
+    {
+      "type": "input_value",
+      "name": "VALUE",
+    },
+    {
+      "type": "input_statement",
+      "name": "CASE_BODY"
+    }
+  ],
+  "previousStatement": "case",
+  "nextStatement": "case",
+  "colour": '#465881',
+  "tooltip": "A case of a when block",
+  "helpUrl": ""
+}]);
+
 
 /***** WHILE BLOCK *****/
 Blockly.defineBlocksWithJsonArray([{
@@ -259,6 +337,67 @@ Blockly.defineBlocksWithJsonArray([{
   "helpUrl": ""
 }]);
 
+/***** BREAK AND CONTINUE BLOCKS *****/
+Blockly.defineBlocksWithJsonArray([{
+  "type": "break_block",
+  "message0": "break",
+  "previousStatement": null,
+  "colour": '#94B4C1',
+  "tooltip": "Leave the innermost loop",
+  "helpUrl": ""
+},
+{
+  "type": "continue_block",
+  "message0": "continue",
+  "previousStatement": null,
+  "colour": '#94B4C1',
+  "tooltip": "Skip to the next iteration of the innermost loop",
+  "helpUrl": ""
+}]);
+
+/***** TRY-CATCH BLOCK *****/
+Blockly.defineBlocksWithJsonArray([{
+  "type": "try_catch_block",
+  "message0": "try %1 catch %2 %3",
+  "args0": [
+    {
+      "type": "input_statement",
+      "name": "TRY_BODY"
+    },
+    {
+      "type": "field_input",
+      "name": "ERROR",
+      "text": "e"
+    },
+    {
+      "type": "input_statement",
+      "name": "CATCH_BODY"
+    }
+  ],
+  "previousStatement": null,
+  "nextStatement": null,
+  "colour": '#465881',
+  "tooltip": "Run the first blocks, and the second ones with the error message if they fail",
+  "helpUrl": ""
+}]);
+
+/***** GLOBAL BLOCK *****/
+Blockly.defineBlocksWithJsonArray([{
+  "type": "global_block",
+  "message0": "global %1",
+  "args0": [
+    {
+      "type": "field_input",
+      "name": "VARIABLE",
+    }
+  ],
+  "previousStatement": null,
+  "nextStatement": null,
+  "colour": '#547792',
+  "tooltip": "Let the function assign a variable declared outside of all functions",
+  "helpUrl": ""
+}]);
+
 /***** SINGLE FUNC CALL *****/
 Blockly.defineBlocksWithJsonArray([{
   "type": "sigle_func_call_block",
@@ -300,10 +439,26 @@ const workspace = Blockly.inject('blocklyDiv', {
         type: "if_else_block",
         inputs: {"CONDITION": {shadow:{type:"expression_block"}} }
       },
+      { kind: "block", 
+        type: "if_elif_else_block",
+        inputs: {"CONDITION": {shadow:{type:"expression_block"}}, "ELIF_CONDITION0": {shadow:{type:"expression_block"}} }
+      },
+      { kind: "block", 
+        type: "when_block",
+        inputs: {"VALUE": {shadow:{type:"expression_block"}} }
+      },
+      { kind: "block", 
+        type: "case_block",
+        inputs: {"VALUE": {shadow:{type:"expression_block"}} }
+      },
       { kind: "block", 
         type: "while_block",
         inputs: {"CONDITION": {shadow:{type:"expression_block"}} }
       },
+      { kind: "block", type: "break_block" },
+      { kind: "block", type: "continue_block" },
+      { kind: "block", type: "global_block" },
+      { kind: "block", type: "try_catch_block" },
       { kind: "block", 
         type: "function_declaration_block",
         inputs: {"RETURN_EXPRESSION": {shadow:{type:"expression_block"}} }
@@ -318,6 +473,7 @@ const workspace = Blockly.inject('blocklyDiv', {
 
 async function execute() {
     const workspaceJson = Blockly.serialization.workspaces.save(workspace, {includeShadowBlocks: true});
+    workspaceJson.bytecode = document.getElementById('bytecode').checked;
     console.log(JSON.stringify(workspaceJson));
     try {
         const response = await fetch('/hard-interpreter', {